@@ -0,0 +1,843 @@
+//! Async LFS HTTP client for tokio-based applications.
+//!
+//! Mirrors [`LfsClient`](crate::LfsClient) using `reqwest`, sharing the batch
+//! API types and pointer logic. Object transfers within a batch run
+//! concurrently.
+
+use futures::stream::{self, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use url::Url;
+
+use crate::batch::{BatchObject, BatchRequest, BatchRequestObject, BatchResponse, Operation};
+use crate::client::{
+    action_wants_credentials, derive_lfs_url, missing_from_response, unique_pointers, Auth,
+    ACTION_EXPIRY_MARGIN,
+};
+use crate::oid::HashingWriter;
+use crate::{Error, ObjectStatus, Oid, Pointer, Result, DEFAULT_BATCH_SIZE};
+
+/// Default number of object transfers run at once.
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+const LFS_MEDIA_TYPE: &str = "application/vnd.git-lfs+json";
+
+/// Async LFS client for communicating with an LFS server.
+///
+/// This type is cheaply cloneable - multiple clones share the same underlying
+/// HTTP client and configuration.
+#[derive(Clone)]
+pub struct AsyncLfsClient {
+    inner: Arc<AsyncLfsClientInner>,
+}
+
+#[derive(Clone)]
+struct AsyncLfsClientInner {
+    /// The LFS API endpoint URL.
+    lfs_url: Url,
+    /// Endpoint for uploads, if different from `lfs_url`.
+    push_url: Option<Url>,
+    /// Optional authentication.
+    auth: Option<Auth>,
+    /// HTTP client for making requests.
+    http: reqwest::Client,
+    /// Optional ref name for batch requests (e.g., "refs/heads/main").
+    ref_name: Option<String>,
+    /// Maximum number of objects per batch request.
+    batch_size: usize,
+    /// Maximum number of transfers in flight.
+    concurrency: usize,
+    /// Endpoints and OIDs this process has seen present, shared by clones.
+    known_objects: Arc<Mutex<HashSet<(Url, Oid)>>>,
+}
+
+impl AsyncLfsClient {
+    /// Create a new async LFS client for a repository URL.
+    ///
+    /// The URL should be the Git remote URL (e.g., `https://github.com/owner/repo.git`).
+    /// The LFS endpoint is derived by appending `/info/lfs` to the base URL.
+    pub fn new(repo_url: &str) -> Result<Self> {
+        Ok(Self::with_url(derive_lfs_url(repo_url)?))
+    }
+
+    /// Create a new async LFS client with a specific LFS endpoint URL.
+    pub fn with_url(lfs_url: Url) -> Self {
+        AsyncLfsClient {
+            inner: Arc::new(AsyncLfsClientInner {
+                lfs_url,
+                push_url: None,
+                auth: None,
+                http: reqwest::Client::new(),
+                ref_name: None,
+                batch_size: DEFAULT_BATCH_SIZE,
+                concurrency: DEFAULT_CONCURRENCY,
+                known_objects: Arc::default(),
+            }),
+        }
+    }
+
+    /// Create an async LFS client by reading configuration from a git
+    /// repository.
+    ///
+    /// Uses the same endpoint discovery as
+    /// [`LfsClient::from_repo`](crate::LfsClient::from_repo), including the
+    /// separate push endpoint.
+    #[cfg(feature = "git2-integration")]
+    pub fn from_repo(repo: &git2::Repository) -> Result<Self> {
+        let lfs_url = crate::builder::lfs_endpoint(repo, Operation::Download)?;
+        let push_url = crate::builder::lfs_endpoint(repo, Operation::Upload)?;
+        let client = Self::with_url(lfs_url);
+        Ok(if push_url != *client.lfs_url() {
+            client.with_push_url(push_url)
+        } else {
+            client
+        })
+    }
+
+    /// Send uploads to a different LFS endpoint.
+    pub fn with_push_url(self, push_url: Url) -> Self {
+        self.configure(|inner| inner.push_url = Some(push_url))
+    }
+
+    /// Set basic authentication credentials.
+    pub fn with_auth(self, username: &str, password: &str) -> Self {
+        self.configure(|inner| {
+            inner.auth = Some(Auth::Basic(username.to_string(), password.to_string()))
+        })
+    }
+
+    /// Set authentication from a bearer token (OAuth/PAT).
+    pub fn with_token(self, token: &str) -> Self {
+        self.configure(|inner| inner.auth = Some(Auth::Bearer(token.to_string())))
+    }
+
+    /// Set the ref name for batch requests.
+    pub fn with_ref(self, ref_name: &str) -> Self {
+        self.configure(|inner| inner.ref_name = Some(ref_name.to_string()))
+    }
+
+    /// Set the maximum number of objects sent in one batch request.
+    ///
+    /// Defaults to [`DEFAULT_BATCH_SIZE`]; a size of zero is treated as one.
+    pub fn with_batch_size(self, batch_size: usize) -> Self {
+        self.configure(|inner| inner.batch_size = batch_size.max(1))
+    }
+
+    /// Set the maximum number of object transfers run at once.
+    ///
+    /// Defaults to [`DEFAULT_CONCURRENCY`]; zero is treated as one.
+    pub fn with_concurrency(self, concurrency: usize) -> Self {
+        self.configure(|inner| inner.concurrency = concurrency.max(1))
+    }
+
+    /// Copy the configuration, apply `f`, and return a client using it.
+    fn configure(self, f: impl FnOnce(&mut AsyncLfsClientInner)) -> Self {
+        let mut inner = (*self.inner).clone();
+        f(&mut inner);
+        AsyncLfsClient {
+            inner: Arc::new(inner),
+        }
+    }
+
+    /// Get the LFS endpoint URL, used for downloads and, unless a push URL
+    /// is set, uploads.
+    pub fn lfs_url(&self) -> &Url {
+        &self.inner.lfs_url
+    }
+
+    /// Get the LFS endpoint URL used for uploads.
+    pub fn push_url(&self) -> &Url {
+        self.inner.push_url.as_ref().unwrap_or(&self.inner.lfs_url)
+    }
+
+    /// Get the maximum number of objects sent in one batch request.
+    pub fn batch_size(&self) -> usize {
+        self.inner.batch_size
+    }
+
+    /// Get the maximum number of object transfers run at once.
+    pub fn concurrency(&self) -> usize {
+        self.inner.concurrency
+    }
+
+    /// Check whether the [push endpoint](Self::push_url) is known to have
+    /// `oid`.
+    ///
+    /// Objects become known when this client (or a clone of it) uploads
+    /// them or finds them already present. Objects seen by `check_exists`
+    /// are only known to be on the download endpoint.
+    pub fn is_known_present(&self, oid: &Oid) -> bool {
+        self.is_known_at(self.push_url(), oid)
+    }
+
+    fn is_known_at(&self, endpoint: &Url, oid: &Oid) -> bool {
+        self.inner
+            .known_objects
+            .lock()
+            .unwrap()
+            .contains(&(endpoint.clone(), oid.clone()))
+    }
+
+    fn mark_present(&self, endpoint: &Url, oid: &Oid) {
+        self.inner
+            .known_objects
+            .lock()
+            .unwrap()
+            .insert((endpoint.clone(), oid.clone()));
+    }
+
+    /// Send a batch request to the LFS server.
+    ///
+    /// Upload requests go to the [push URL](Self::push_url). Fails with
+    /// [`Error::UnsupportedTransfer`] or [`Error::UnsupportedHashAlgo`] if
+    /// the server picks a transfer adapter or hash algorithm the request
+    /// didn't offer.
+    pub async fn batch(&self, request: &BatchRequest) -> Result<BatchResponse> {
+        let endpoint = match request.operation {
+            Operation::Download => self.lfs_url(),
+            Operation::Upload => self.push_url(),
+        };
+        let url = endpoint.join("objects/batch")?;
+
+        let mut req = self
+            .inner
+            .http
+            .post(url.as_str())
+            .header("Accept", LFS_MEDIA_TYPE)
+            .header("Content-Type", LFS_MEDIA_TYPE)
+            .header("User-Agent", "git2-lfs/0.1")
+            .body(serde_json::to_vec(request)?);
+
+        if let Some(auth) = &self.inner.auth {
+            req = req.header("Authorization", auth.header_value());
+        }
+
+        let response = check_status(req.send().await?).await?;
+        let batch_response: BatchResponse = serde_json::from_slice(&response.bytes().await?)?;
+        request.check_response(&batch_response)?;
+        Ok(batch_response)
+    }
+
+    /// Upload content to the LFS server.
+    ///
+    /// Does nothing if the object is already known to be on the server.
+    pub async fn upload(&self, pointer: &Pointer, content: &[u8]) -> Result<()> {
+        for (_, status) in self.upload_batch_report(&[(pointer, content)]).await? {
+            status.into_result()?;
+        }
+        Ok(())
+    }
+
+    /// Download content from the LFS server.
+    pub async fn download(&self, pointer: &Pointer) -> Result<Vec<u8>> {
+        let mut contents = self.download_batch(&[pointer]).await?;
+        Ok(contents.remove(0))
+    }
+
+    /// Upload content from a file, streaming.
+    ///
+    /// Hashes the file first, then streams it to the server. Returns the
+    /// pointer for the uploaded content.
+    pub async fn upload_file<P: AsRef<Path>>(&self, path: P) -> Result<Pointer> {
+        let path = path.as_ref();
+
+        // Compute hash by streaming through file
+        let mut file = tokio::fs::File::open(path).await?;
+        let mut hasher = HashingWriter::new(io::sink());
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = file.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            hasher.write_all(&buf[..n])?;
+        }
+        let (oid, size, _) = hasher.finish();
+        let pointer = Pointer::new(oid, size);
+
+        // Now upload - we need to read the file again
+        let file = tokio::fs::File::open(path).await?;
+        self.upload_reader(&pointer, file, size).await?;
+
+        Ok(pointer)
+    }
+
+    /// Upload content from an async reader.
+    ///
+    /// The pointer must match the content that will be read. Nothing is
+    /// read if the object is already known to be on the server.
+    pub async fn upload_reader<R>(&self, pointer: &Pointer, reader: R, size: u64) -> Result<()>
+    where
+        R: AsyncRead + Send + 'static,
+    {
+        if self.is_known_present(pointer.oid()) {
+            return Ok(());
+        }
+
+        let obj = self.request_object(Operation::Upload, pointer).await?;
+        if let Some(err) = &obj.error {
+            return Err(Error::object_error(&obj.oid, err.code, &err.message));
+        }
+
+        // Get upload action (no action means already exists)
+        let action = match obj.upload_action() {
+            Some(a) => a,
+            None => {
+                self.mark_present(self.push_url(), pointer.oid());
+                return Ok(());
+            }
+        };
+
+        let body = reqwest::Body::wrap_stream(tokio_util::io::ReaderStream::new(reader));
+        let req = self
+            .action_request(reqwest::Method::PUT, self.push_url(), &obj, action)
+            .header("Content-Type", "application/octet-stream")
+            .header("Content-Length", size)
+            .body(body);
+        self.send(req)
+            .await
+            .map_err(|e| e.with_oid(pointer.oid()))?;
+
+        self.verify_object(&obj)
+            .await
+            .map_err(|e| e.with_oid(pointer.oid()))?;
+
+        self.mark_present(self.push_url(), pointer.oid());
+        Ok(())
+    }
+
+    /// Download content to a file, streaming and verifying hash.
+    ///
+    /// Streams to a temp file while hashing, then renames atomically.
+    pub async fn download_to_file<P: AsRef<Path>>(&self, pointer: &Pointer, dest: P) -> Result<()> {
+        let dest = dest.as_ref();
+        let temp_path = dest.with_extension("tmp");
+
+        let file = tokio::fs::File::create(&temp_path).await?;
+        if let Err(e) = self.download_to_writer(pointer, file).await {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(e);
+        }
+
+        // Atomic rename
+        tokio::fs::rename(&temp_path, dest).await?;
+        Ok(())
+    }
+
+    /// Download content to an async writer, streaming and verifying hash.
+    ///
+    /// Returns the number of bytes written.
+    pub async fn download_to_writer<W>(&self, pointer: &Pointer, mut writer: W) -> Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let obj = self.request_object(Operation::Download, pointer).await?;
+        if let Some(err) = &obj.error {
+            return Err(Error::object_error(&obj.oid, err.code, &err.message));
+        }
+        let action = obj.download_action().ok_or_else(|| Error::NotFound {
+            oid: Some(pointer.oid().clone()),
+            url: None,
+        })?;
+
+        let req = self.action_request(reqwest::Method::GET, self.lfs_url(), &obj, action);
+        let mut response = self
+            .send(req)
+            .await
+            .map_err(|e| e.with_oid(pointer.oid()))?;
+
+        // Stream to writer while hashing
+        let mut hasher = HashingWriter::new(io::sink());
+        let mut remaining = pointer.size().saturating_add(1);
+        while remaining > 0 {
+            let Some(chunk) = response.chunk().await? else {
+                break;
+            };
+            let chunk = bounded(&chunk, &mut remaining);
+            hasher.write_all(chunk)?;
+            writer.write_all(chunk).await?;
+        }
+        writer.flush().await?;
+
+        let (computed_oid, size, _) = hasher.finish();
+
+        // Verify hash and size
+        Error::check_content(pointer, &computed_oid, size)?;
+
+        Ok(size)
+    }
+
+    /// Check if objects exist on the server.
+    ///
+    /// Objects already known to be present are not queried again; the rest
+    /// are queried in chunks of [`batch_size`](Self::batch_size). Returns
+    /// the distinct OIDs that exist.
+    pub async fn check_exists(&self, pointers: &[&Pointer]) -> Result<Vec<String>> {
+        let pointers = unique_pointers(pointers.iter().copied());
+        let unknown: Vec<_> = pointers
+            .iter()
+            .copied()
+            .filter(|p| !self.is_known_at(self.lfs_url(), p.oid()))
+            .collect();
+
+        for chunk in unknown.chunks(self.inner.batch_size) {
+            let objects = chunk
+                .iter()
+                .map(|p| BatchRequestObject::new(&p.oid().to_hex(), p.size()))
+                .collect();
+            let batch_resp = self
+                .batch(&self.batch_request(Operation::Download, objects))
+                .await?;
+
+            for obj in &batch_resp.objects {
+                if obj.download_action().is_some() {
+                    if let Ok(oid) = Oid::from_hex(&obj.oid) {
+                        self.mark_present(self.lfs_url(), &oid);
+                    }
+                }
+            }
+        }
+
+        Ok(pointers
+            .iter()
+            .filter(|p| self.is_known_at(self.lfs_url(), p.oid()))
+            .map(|p| p.oid().to_hex())
+            .collect())
+    }
+
+    /// Upload multiple objects using batch requests.
+    ///
+    /// Fails with the first per-object error; use
+    /// [`upload_batch_report`](Self::upload_batch_report) to see every
+    /// object's outcome.
+    pub async fn upload_batch(&self, items: &[(&Pointer, &[u8])]) -> Result<()> {
+        for (_, status) in self.upload_batch_report(items).await? {
+            status.into_result()?;
+        }
+        Ok(())
+    }
+
+    /// Upload multiple objects, reporting the outcome for each one.
+    ///
+    /// Behaves like [`LfsClient::upload_batch_report`](crate::LfsClient::upload_batch_report),
+    /// running up to [`concurrency`](Self::concurrency) uploads at once.
+    pub async fn upload_batch_report(
+        &self,
+        items: &[(&Pointer, &[u8])],
+    ) -> Result<Vec<(Oid, ObjectStatus<()>)>> {
+        let mut seen = HashSet::new();
+        let items: Vec<_> = items
+            .iter()
+            .filter(|(p, _)| seen.insert(p.oid()))
+            .copied()
+            .collect();
+        let pending: Vec<_> = items
+            .iter()
+            .filter(|(p, _)| !self.is_known_present(p.oid()))
+            .copied()
+            .collect();
+
+        let mut statuses = HashMap::with_capacity(pending.len());
+        for chunk in pending.chunks(self.inner.batch_size) {
+            statuses.extend(self.upload_chunk(chunk).await?);
+        }
+
+        Ok(items
+            .iter()
+            .map(|(p, _)| {
+                let status = statuses
+                    .remove(p.oid())
+                    .unwrap_or(ObjectStatus::AlreadyPresent);
+                (p.oid().clone(), status)
+            })
+            .collect())
+    }
+
+    async fn upload_chunk(
+        &self,
+        items: &[(&Pointer, &[u8])],
+    ) -> Result<Vec<(Oid, ObjectStatus<()>)>> {
+        // Content that doesn't match its pointer is never sent
+        let mut report = Vec::with_capacity(items.len());
+        let mut valid = Vec::with_capacity(items.len());
+        for &(pointer, content) in items {
+            let actual = Oid::from_content(content);
+            match Error::check_content(pointer, &actual, content.len() as u64) {
+                Ok(()) => valid.push((pointer, content)),
+                Err(e) => report.push((pointer.oid().clone(), ObjectStatus::Failed(e))),
+            }
+        }
+        if valid.is_empty() {
+            return Ok(report);
+        }
+
+        let objects = valid
+            .iter()
+            .map(|(p, _)| BatchRequestObject::new(&p.oid().to_hex(), p.size()))
+            .collect();
+        let batch_resp = self
+            .batch(&self.batch_request(Operation::Upload, objects))
+            .await?;
+        let received_at = batch_resp.received_at;
+        let obj_map: HashMap<_, _> = batch_resp
+            .objects
+            .into_iter()
+            .map(|o| (o.oid.clone(), o))
+            .collect();
+
+        let uploads = stream::iter(valid)
+            .map(|(pointer, content)| {
+                let obj_map = &obj_map;
+                async move {
+                    let status = match obj_map.get(&pointer.oid().to_hex()) {
+                        Some(obj) => self.upload_object(pointer, content, obj, received_at).await,
+                        None => ObjectStatus::Failed(missing_from_response(pointer)),
+                    };
+                    (pointer.oid().clone(), status)
+                }
+            })
+            .buffer_unordered(self.inner.concurrency)
+            .collect::<Vec<_>>()
+            .await;
+
+        report.extend(uploads);
+        Ok(report)
+    }
+
+    /// Upload one object according to its batch response entry.
+    async fn upload_object(
+        &self,
+        pointer: &Pointer,
+        content: &[u8],
+        obj: &BatchObject,
+        received_at: SystemTime,
+    ) -> ObjectStatus<()> {
+        let fresh = match self
+            .refresh_expiring(Operation::Upload, pointer, obj, received_at)
+            .await
+        {
+            Ok(fresh) => fresh,
+            Err(e) => return ObjectStatus::Failed(e.with_oid(pointer.oid())),
+        };
+        let obj = fresh.as_ref().unwrap_or(obj);
+
+        if let Some(err) = &obj.error {
+            return ObjectStatus::Failed(Error::object_error(&obj.oid, err.code, &err.message));
+        }
+
+        // No upload action means the server already has it
+        let action = match obj.upload_action() {
+            Some(a) => a,
+            None => {
+                self.mark_present(self.push_url(), pointer.oid());
+                return ObjectStatus::AlreadyPresent;
+            }
+        };
+
+        let req = self
+            .action_request(reqwest::Method::PUT, self.push_url(), obj, action)
+            .header("Content-Type", "application/octet-stream")
+            .body(content.to_vec());
+        let result = match self.send(req).await {
+            Ok(_) => self.verify_object(obj).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(()) => {
+                self.mark_present(self.push_url(), pointer.oid());
+                ObjectStatus::Done(())
+            }
+            Err(e) => ObjectStatus::Failed(e.with_oid(pointer.oid())),
+        }
+    }
+
+    /// Call the object's verify action, if the server asked for one.
+    async fn verify_object(&self, obj: &BatchObject) -> Result<()> {
+        if let Some(verify_action) = obj.verify_action() {
+            let verify_body = serde_json::json!({
+                "oid": obj.oid,
+                "size": obj.size
+            });
+            let req = self
+                .action_request(reqwest::Method::POST, self.push_url(), obj, verify_action)
+                .header("Content-Type", LFS_MEDIA_TYPE)
+                .body(verify_body.to_string());
+            self.send(req).await?;
+        }
+        Ok(())
+    }
+
+    /// Download multiple objects using batch requests.
+    ///
+    /// Fails with the first per-object error; use
+    /// [`download_batch_report`](Self::download_batch_report) to see every
+    /// object's outcome.
+    ///
+    /// Returns the content of each object in the same order as input.
+    pub async fn download_batch(&self, pointers: &[&Pointer]) -> Result<Vec<Vec<u8>>> {
+        let mut contents = HashMap::new();
+        for (oid, status) in self.download_batch_report(pointers).await? {
+            match status {
+                ObjectStatus::Done(content) => {
+                    contents.insert(oid, content);
+                }
+                ObjectStatus::AlreadyPresent => {
+                    return Err(Error::NotFound {
+                        oid: Some(oid),
+                        url: None,
+                    })
+                }
+                ObjectStatus::Failed(err) => return Err(err),
+            }
+        }
+
+        // Duplicate pointers get copies; the last one takes the original
+        let last: HashMap<_, _> = pointers
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (p.oid(), i))
+            .collect();
+        Ok(pointers
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                if last[p.oid()] == i {
+                    contents.remove(p.oid())
+                } else {
+                    contents.get(p.oid()).cloned()
+                }
+                .expect("every distinct object has a status")
+            })
+            .collect())
+    }
+
+    /// Download multiple objects, reporting the outcome for each one.
+    ///
+    /// Behaves like [`LfsClient::download_batch_report`](crate::LfsClient::download_batch_report),
+    /// running up to [`concurrency`](Self::concurrency) downloads at once.
+    pub async fn download_batch_report(
+        &self,
+        pointers: &[&Pointer],
+    ) -> Result<Vec<(Oid, ObjectStatus<Vec<u8>>)>> {
+        let pointers = unique_pointers(pointers.iter().copied());
+        let mut report = Vec::with_capacity(pointers.len());
+        for chunk in pointers.chunks(self.inner.batch_size) {
+            report.extend(self.download_chunk(chunk).await?);
+        }
+        Ok(report)
+    }
+
+    async fn download_chunk(
+        &self,
+        pointers: &[&Pointer],
+    ) -> Result<Vec<(Oid, ObjectStatus<Vec<u8>>)>> {
+        let objects = pointers
+            .iter()
+            .map(|p| BatchRequestObject::new(&p.oid().to_hex(), p.size()))
+            .collect();
+        let batch_resp = self
+            .batch(&self.batch_request(Operation::Download, objects))
+            .await?;
+        let received_at = batch_resp.received_at;
+        let obj_map: HashMap<_, _> = batch_resp
+            .objects
+            .into_iter()
+            .map(|o| (o.oid.clone(), o))
+            .collect();
+
+        // Download concurrently, keeping input order
+        Ok(stream::iter(pointers)
+            .map(|&pointer| {
+                let obj_map = &obj_map;
+                async move {
+                    let status = match obj_map.get(&pointer.oid().to_hex()) {
+                        Some(obj) => match self.download_object(pointer, obj, received_at).await {
+                            Ok(content) => ObjectStatus::Done(content),
+                            Err(e) => ObjectStatus::Failed(e.with_oid(pointer.oid())),
+                        },
+                        None => ObjectStatus::Failed(missing_from_response(pointer)),
+                    };
+                    (pointer.oid().clone(), status)
+                }
+            })
+            .buffered(self.inner.concurrency)
+            .collect()
+            .await)
+    }
+
+    /// Download one object according to its batch response entry.
+    async fn download_object(
+        &self,
+        pointer: &Pointer,
+        obj: &BatchObject,
+        received_at: SystemTime,
+    ) -> Result<Vec<u8>> {
+        let fresh = self
+            .refresh_expiring(Operation::Download, pointer, obj, received_at)
+            .await?;
+        let obj = fresh.as_ref().unwrap_or(obj);
+
+        if let Some(err) = &obj.error {
+            return Err(Error::object_error(&obj.oid, err.code, &err.message));
+        }
+        let action = obj.download_action().ok_or_else(|| Error::NotFound {
+            oid: Some(pointer.oid().clone()),
+            url: None,
+        })?;
+
+        let req = self.action_request(reqwest::Method::GET, self.lfs_url(), obj, action);
+        let mut response = self.send(req).await?;
+        let mut content = Vec::with_capacity(pointer.size() as usize);
+        let mut remaining = pointer.size().saturating_add(1);
+        while remaining > 0 {
+            let Some(chunk) = response.chunk().await? else {
+                break;
+            };
+            content.extend_from_slice(bounded(&chunk, &mut remaining));
+        }
+
+        // Verify content
+        Error::check_content(pointer, &Oid::from_content(&content), content.len() as u64)?;
+
+        Ok(content)
+    }
+
+    /// Re-request `obj` if its transfer action has expired or is about to.
+    ///
+    /// Returns the fresh batch object, or `None` if the original action is
+    /// still usable.
+    async fn refresh_expiring(
+        &self,
+        operation: Operation,
+        pointer: &Pointer,
+        obj: &BatchObject,
+        received_at: SystemTime,
+    ) -> Result<Option<BatchObject>> {
+        let action = match operation {
+            Operation::Download => obj.download_action(),
+            Operation::Upload => obj.upload_action(),
+        };
+        if !action.is_some_and(|a| a.expires_within(received_at, ACTION_EXPIRY_MARGIN)) {
+            return Ok(None);
+        }
+        self.request_object(operation, pointer).await.map(Some)
+    }
+
+    /// Send a single-object batch request and return the object's entry.
+    async fn request_object(&self, operation: Operation, pointer: &Pointer) -> Result<BatchObject> {
+        let objects = vec![BatchRequestObject::new(
+            &pointer.oid().to_hex(),
+            pointer.size(),
+        )];
+        let batch_resp = self.batch(&self.batch_request(operation, objects)).await?;
+
+        let oid = pointer.oid().to_hex();
+        batch_resp
+            .objects
+            .into_iter()
+            .find(|o| o.oid == oid)
+            .ok_or_else(|| missing_from_response(pointer))
+    }
+
+    fn batch_request(
+        &self,
+        operation: Operation,
+        objects: Vec<BatchRequestObject>,
+    ) -> BatchRequest {
+        let batch_req = match operation {
+            Operation::Download => BatchRequest::download(objects),
+            Operation::Upload => BatchRequest::upload(objects),
+        };
+        match &self.inner.ref_name {
+            Some(ref_name) => batch_req.with_ref(ref_name),
+            None => batch_req,
+        }
+    }
+
+    /// Start a request to an action's URL with the action's headers, and
+    /// the client's credentials if [`action_wants_credentials`] says so.
+    fn action_request(
+        &self,
+        method: reqwest::Method,
+        endpoint: &Url,
+        obj: &BatchObject,
+        action: &crate::Action,
+    ) -> reqwest::RequestBuilder {
+        let mut req = self.inner.http.request(method, &action.href);
+        for (key, value) in &action.header {
+            req = req.header(key, value);
+        }
+        if let Some(auth) = &self.inner.auth {
+            if action_wants_credentials(endpoint, obj, action) {
+                req = req.header("Authorization", auth.header_value());
+            }
+        }
+        req
+    }
+
+    async fn send(&self, req: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        check_status(req.send().await?).await
+    }
+}
+
+/// Cut a download body's `chunk` to the `remaining` bytes that may still be
+/// read. Downloads start with one byte more than the pointer's size, so a
+/// body that is too large fails the size check without being read in full.
+fn bounded<'a>(chunk: &'a [u8], remaining: &mut u64) -> &'a [u8] {
+    let len = chunk.len().min(usize::try_from(*remaining).unwrap_or(usize::MAX));
+    *remaining -= len as u64;
+    &chunk[..len]
+}
+
+/// Turn an HTTP error status into an error, reading the body for its message.
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let url = Some(response.url().to_string());
+    let message = response
+        .text()
+        .await
+        .unwrap_or_else(|_| "unknown error".into());
+    Err(Error::from_status(status.as_u16(), url, || message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_async_client_new() {
+        let client = AsyncLfsClient::new("https://github.com/owner/repo.git").unwrap();
+        assert_eq!(
+            client.lfs_url().as_str(),
+            "https://github.com/owner/repo.git/info/lfs/"
+        );
+        assert_eq!(client.batch_size(), DEFAULT_BATCH_SIZE);
+        assert_eq!(client.concurrency(), DEFAULT_CONCURRENCY);
+    }
+
+    #[test]
+    fn test_async_client_builders_share_known_objects() {
+        let client = AsyncLfsClient::new("https://github.com/owner/repo.git").unwrap();
+        let oid = Oid::from_content(b"hello");
+        client.mark_present(client.push_url(), &oid);
+
+        let configured = client
+            .clone()
+            .with_token("token")
+            .with_concurrency(0)
+            .with_batch_size(10);
+        assert_eq!(configured.concurrency(), 1);
+        assert_eq!(configured.batch_size(), 10);
+        assert!(configured.is_known_present(&oid));
+        assert!(configured.inner.auth.is_some());
+        assert!(client.inner.auth.is_none());
+    }
+}
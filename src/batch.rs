@@ -4,7 +4,10 @@
 //! See: https://github.com/git-lfs/git-lfs/blob/main/docs/api/batch.md
 
 use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
 use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Operation type for batch requests.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -29,6 +32,9 @@ pub struct BatchRequest {
     pub r#ref: Option<RefInfo>,
     /// The objects to operate on.
     pub objects: Vec<BatchRequestObject>,
+    /// The hash algorithm the client uses for OIDs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash_algo: Option<String>,
 }
 
 /// Reference information for a batch request.
@@ -55,10 +61,22 @@ pub struct BatchResponse {
     pub transfer: String,
     /// The objects with their actions.
     pub objects: Vec<BatchObject>,
+    /// The hash algorithm the server chose (sha256 if absent).
+    #[serde(default)]
+    pub hash_algo: Option<String>,
+    /// When the response was received; `expires_in` counts from here.
+    #[serde(skip, default = "SystemTime::now")]
+    pub received_at: SystemTime,
 }
 
+/// The transfer adapter every client and server supports.
+pub const DEFAULT_TRANSFER: &str = "basic";
+
+/// The hash algorithm assumed when none is given.
+pub const DEFAULT_HASH_ALGO: &str = "sha256";
+
 fn default_transfer() -> String {
-    "basic".to_string()
+    DEFAULT_TRANSFER.to_string()
 }
 
 /// An object in a batch response.
@@ -68,7 +86,9 @@ pub struct BatchObject {
     pub oid: String,
     /// The size of the object in bytes.
     pub size: u64,
-    /// Whether the object was authenticated.
+    /// Whether the object's actions are already authenticated. Unless
+    /// true, transfers to the LFS endpoint's own origin carry the client's
+    /// credentials.
     #[serde(default)]
     pub authenticated: Option<bool>,
     /// Actions available for this object.
@@ -109,9 +129,10 @@ impl BatchRequest {
     pub fn download(objects: Vec<BatchRequestObject>) -> Self {
         BatchRequest {
             operation: Operation::Download,
-            transfers: Some(vec!["basic".to_string()]),
+            transfers: Some(vec![DEFAULT_TRANSFER.to_string()]),
             r#ref: None,
             objects,
+            hash_algo: Some(DEFAULT_HASH_ALGO.to_string()),
         }
     }
 
@@ -119,9 +140,10 @@ impl BatchRequest {
     pub fn upload(objects: Vec<BatchRequestObject>) -> Self {
         BatchRequest {
             operation: Operation::Upload,
-            transfers: Some(vec!["basic".to_string()]),
+            transfers: Some(vec![DEFAULT_TRANSFER.to_string()]),
             r#ref: None,
             objects,
+            hash_algo: Some(DEFAULT_HASH_ALGO.to_string()),
         }
     }
 
@@ -132,6 +154,46 @@ impl BatchRequest {
         });
         self
     }
+
+    /// Set the transfer adapters to offer, in order of preference.
+    pub fn with_transfers<I, S>(mut self, transfers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.transfers = Some(transfers.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the hash algorithm used for the request's OIDs.
+    pub fn with_hash_algo(mut self, hash_algo: &str) -> Self {
+        self.hash_algo = Some(hash_algo.to_string());
+        self
+    }
+
+    /// Check that `response` picked a transfer adapter and hash algorithm
+    /// offered by this request.
+    ///
+    /// A request without `transfers` offers only `basic`, and one without
+    /// `hash_algo` offers only `sha256`.
+    pub fn check_response(&self, response: &BatchResponse) -> Result<()> {
+        let offered = self.transfers.as_deref().unwrap_or_default();
+        let accepted = if offered.is_empty() {
+            response.transfer == DEFAULT_TRANSFER
+        } else {
+            offered.contains(&response.transfer)
+        };
+        if !accepted {
+            return Err(Error::UnsupportedTransfer(response.transfer.clone()));
+        }
+
+        let requested = self.hash_algo.as_deref().unwrap_or(DEFAULT_HASH_ALGO);
+        let chosen = response.hash_algo.as_deref().unwrap_or(DEFAULT_HASH_ALGO);
+        if chosen != requested {
+            return Err(Error::UnsupportedHashAlgo(chosen.to_string()));
+        }
+        Ok(())
+    }
 }
 
 impl BatchRequestObject {
@@ -144,6 +206,26 @@ impl BatchRequestObject {
     }
 }
 
+impl Action {
+    /// Get the time this action expires, given when its response was received.
+    ///
+    /// A non-zero `expires_in` takes precedence over `expires_at`, as the
+    /// batch API specifies. Returns `None` if the action doesn't expire or
+    /// `expires_at` is not a valid RFC 3339 timestamp.
+    pub fn expiry(&self, received_at: SystemTime) -> Option<SystemTime> {
+        match self.expires_in {
+            Some(secs) if secs > 0 => received_at.checked_add(Duration::from_secs(secs)),
+            _ => self.expires_at.as_deref().and_then(parse_rfc3339),
+        }
+    }
+
+    /// Check whether the action has expired or will expire within `margin`.
+    pub fn expires_within(&self, received_at: SystemTime, margin: Duration) -> bool {
+        self.expiry(received_at)
+            .is_some_and(|expiry| expiry <= SystemTime::now() + margin)
+    }
+}
+
 impl BatchObject {
     /// Get the download action if available.
     pub fn download_action(&self) -> Option<&Action> {
@@ -166,6 +248,103 @@ impl BatchObject {
     }
 }
 
+/// Parse an RFC 3339 timestamp such as `2016-11-10T15:29:07Z` or
+/// `2016-11-10T15:29:07.5-08:00`.
+pub(crate) fn parse_rfc3339(value: &str) -> Option<SystemTime> {
+    let bytes = value.as_bytes();
+    if bytes.len() < 20
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || !matches!(bytes[10], b'T' | b't')
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return None;
+    }
+    let number = |start: usize, end: usize| -> Option<i64> {
+        let digits = value.get(start..end)?;
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok()
+    };
+
+    let (year, month, day) = (number(0, 4)?, number(5, 7)?, number(8, 10)?);
+    let (hour, minute, second) = (number(11, 13)?, number(14, 16)?, number(17, 19)?);
+    if !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    let mut rest = &value[19..];
+    let mut nanos = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return None;
+        }
+        // Keep nanosecond precision, padding shorter fractions
+        let mut scaled = fraction[..digits.min(9)].to_string();
+        while scaled.len() < 9 {
+            scaled.push('0');
+        }
+        nanos = scaled.parse().ok()?;
+        rest = &fraction[digits..];
+    }
+
+    let offset = match rest.as_bytes() {
+        [b'Z' | b'z'] => 0,
+        [sign @ (b'+' | b'-'), _, _, b':', _, _] => {
+            let hours = number(value.len() - 5, value.len() - 3)?;
+            let minutes = number(value.len() - 2, value.len())?;
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            let offset = hours * 3600 + minutes * 60;
+            if *sign == b'-' {
+                -offset
+            } else {
+                offset
+            }
+        }
+        _ => return None,
+    };
+
+    let secs =
+        days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset;
+    let since_epoch = Duration::new(secs.unsigned_abs(), 0);
+    let whole = if secs >= 0 {
+        UNIX_EPOCH.checked_add(since_epoch)?
+    } else {
+        UNIX_EPOCH.checked_sub(since_epoch)?
+    };
+    whole.checked_add(Duration::from_nanos(nanos))
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -230,4 +409,114 @@ mod tests {
         assert!(response.objects[0].has_error());
         assert_eq!(response.objects[0].error.as_ref().unwrap().code, 404);
     }
+
+    #[test]
+    fn test_parse_rfc3339() {
+        let at = |secs: u64| UNIX_EPOCH + Duration::from_secs(secs);
+        assert_eq!(parse_rfc3339("1970-01-01T00:00:00Z"), Some(at(0)));
+        assert_eq!(parse_rfc3339("2016-11-10T15:29:07Z"), Some(at(1478791747)));
+        assert_eq!(
+            parse_rfc3339("2016-11-10t07:29:07-08:00"),
+            Some(at(1478791747))
+        );
+        assert_eq!(
+            parse_rfc3339("2016-11-10T20:59:07+05:30"),
+            Some(at(1478791747))
+        );
+        assert_eq!(parse_rfc3339("2024-02-29T00:00:00Z"), Some(at(1709164800)));
+        assert_eq!(
+            parse_rfc3339("2016-11-10T15:29:07.25Z"),
+            Some(at(1478791747) + Duration::from_millis(250))
+        );
+        assert_eq!(
+            parse_rfc3339("1969-12-31T23:59:59Z"),
+            UNIX_EPOCH.checked_sub(Duration::from_secs(1))
+        );
+
+        for invalid in [
+            "",
+            "2016-11-10",
+            "2016-11-10T15:29:07",
+            "2016-11-10 15:29:07Z",
+            "2016-13-10T15:29:07Z",
+            "2023-02-29T15:29:07Z",
+            "2016-11-10T24:00:00Z",
+            "2016-11-10T15:29:07.Z",
+            "2016-11-10T15:29:07+0800",
+        ] {
+            assert_eq!(parse_rfc3339(invalid), None, "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_action_expiry() {
+        let action = |expires_in: Option<u64>, expires_at: Option<&str>| Action {
+            href: "https://example.com".into(),
+            header: HashMap::new(),
+            expires_in,
+            expires_at: expires_at.map(String::from),
+        };
+        let now = SystemTime::now();
+        let margin = Duration::from_secs(5);
+
+        assert!(!action(None, None).expires_within(now, margin));
+        assert!(!action(Some(3600), None).expires_within(now, margin));
+        assert!(action(Some(3600), None).expires_within(now - Duration::from_secs(3600), margin));
+        assert!(action(Some(3), None).expires_within(now, margin));
+        assert!(action(None, Some("2000-01-01T00:00:00Z")).expires_within(now, margin));
+        assert!(!action(None, Some("2999-01-01T00:00:00Z")).expires_within(now, margin));
+
+        // expires_in wins over expires_at, but zero means unset
+        assert!(!action(Some(3600), Some("2000-01-01T00:00:00Z")).expires_within(now, margin));
+        assert!(action(Some(0), Some("2000-01-01T00:00:00Z")).expires_within(now, margin));
+    }
+
+    #[test]
+    fn test_batch_request_negotiation_fields() {
+        let request = BatchRequest::download(vec![]);
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["transfers"], serde_json::json!(["basic"]));
+        assert_eq!(json["hash_algo"], "sha256");
+
+        let request = request
+            .with_transfers(["lfs-standalone-file", "basic"])
+            .with_hash_algo("sha512");
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            json["transfers"],
+            serde_json::json!(["lfs-standalone-file", "basic"])
+        );
+        assert_eq!(json["hash_algo"], "sha512");
+    }
+
+    #[test]
+    fn test_check_response() {
+        let response = |json: &str| -> BatchResponse { serde_json::from_str(json).unwrap() };
+        let request = BatchRequest::download(vec![]);
+
+        // Omitted fields mean basic and sha256
+        assert!(request
+            .check_response(&response(r#"{"objects": []}"#))
+            .is_ok());
+        assert!(request
+            .check_response(&response(
+                r#"{"transfer": "basic", "hash_algo": "sha256", "objects": []}"#
+            ))
+            .is_ok());
+
+        let err = request
+            .check_response(&response(r#"{"transfer": "tus", "objects": []}"#))
+            .unwrap_err();
+        assert!(matches!(err, Error::UnsupportedTransfer(ref t) if t == "tus"));
+
+        let err = request
+            .check_response(&response(r#"{"hash_algo": "sha512", "objects": []}"#))
+            .unwrap_err();
+        assert!(matches!(err, Error::UnsupportedHashAlgo(ref h) if h == "sha512"));
+
+        let request = request.with_transfers(["tus", "basic"]);
+        assert!(request
+            .check_response(&response(r#"{"transfer": "tus", "objects": []}"#))
+            .is_ok());
+    }
 }
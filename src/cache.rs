@@ -3,53 +3,205 @@
 //! Stores LFS objects in `.git/lfs/objects/` to avoid re-downloading
 //! and enable offline access.
 
-use std::fs::{self, File};
-use std::io::{self, Write};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::{Oid, Pointer, Result};
+use crate::index::CacheIndex;
+use crate::oid::HashingWriter;
+use crate::{IndexEntry, ObjectSource, Oid, Pointer, Result};
+
+/// How [`ObjectCache::checkout`] materializes a working-tree file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CheckoutMode {
+    /// Stream a copy of the cached object.
+    #[default]
+    Copy,
+    /// Copy-on-write clone (`FICLONE` on Linux), falling back to a copy.
+    ///
+    /// The clone shares disk blocks with the cache until either side is
+    /// modified, so edits can't affect the cached object.
+    Reflink,
+    /// Hard link to the cached object, falling back to a copy.
+    ///
+    /// The cached object is made read-only first, so editors that write in
+    /// place fail instead of corrupting the cache. Files checked out this way
+    /// are read-only and don't keep their previous permissions. Objects
+    /// found only in an alternate are never linked; they are checked out
+    /// like [`Reflink`](Self::Reflink).
+    Hardlink,
+}
 
 /// Local cache for LFS objects.
 ///
 /// Objects are stored in the git-lfs standard layout:
 /// `.git/lfs/objects/<oid[0:2]>/<oid[2:4]>/<oid>`
+///
+/// An optional maximum size can be set with [`ObjectCache::with_max_size`].
+/// Reads record an access time in the index, and once a write pushes the
+/// cache over the limit the least-recently-used objects are evicted.
+/// Pinned objects, and cleaned objects not yet marked as pushed, are never
+/// evicted.
+///
+/// An on-disk index records each object's size, insertion and access times,
+/// source and push state, so [`ObjectCache::size`], [`ObjectCache::count`]
+/// and queries like [`ObjectCache::unpushed`] don't walk the directory.
+/// Objects added or removed by other tools are only picked up by
+/// [`ObjectCache::rebuild_index`].
+///
+/// Read-only alternate caches can be added with [`ObjectCache::with_alternate`].
+/// Lookups fall back to them when an object is missing from the primary
+/// cache; writes, eviction and pruning only ever touch the primary cache.
+///
+/// Writes are safe across threads and processes sharing the directory: each
+/// writer streams into its own temp file, holds an advisory lock on the OID
+/// while writing, and atomically renames the result into place.
+#[derive(Debug, Clone)]
 pub struct ObjectCache {
     base_path: PathBuf,
+    /// Directory for in-progress writes and lock files.
+    tmp_path: PathBuf,
+    /// Maximum total size of cached objects in bytes.
+    max_size: Option<u64>,
+    /// Read-only object directories searched after `base_path`.
+    alternates: Vec<PathBuf>,
+    index: CacheIndex,
 }
 
 impl ObjectCache {
     /// Create a new object cache at the given base path.
     ///
     /// Typically this is `.git/lfs/objects` within a repository.
+    ///
+    /// In that standard layout temp files go to the sibling `tmp` directory
+    /// (`.git/lfs/tmp`), as with git-lfs. For any other base path they go to
+    /// `<base_path>/tmp`. Use [`ObjectCache::with_tmp_dir`] to override.
     pub fn new<P: AsRef<Path>>(base_path: P) -> Self {
+        let base_path = base_path.as_ref().to_path_buf();
+        let tmp_path = match base_path.parent() {
+            Some(parent) if base_path.file_name() == Some("objects".as_ref()) => parent.join("tmp"),
+            _ => base_path.join("tmp"),
+        };
+
         ObjectCache {
-            base_path: base_path.as_ref().to_path_buf(),
+            index: CacheIndex::new(&base_path),
+            base_path,
+            tmp_path,
+            max_size: None,
+            alternates: Vec::new(),
         }
     }
 
     /// Create a cache for a repository's `.git/lfs/objects` directory.
     pub fn for_repo<P: AsRef<Path>>(git_dir: P) -> Self {
-        let base_path = git_dir.as_ref().join("lfs").join("objects");
-        ObjectCache { base_path }
+        Self::new(git_dir.as_ref().join("lfs").join("objects"))
+    }
+
+    /// Create a cache by reading storage configuration from a git repository.
+    ///
+    /// - `lfs.storage` overrides the storage directory; objects live in its
+    ///   `objects` subdirectory. Relative paths are resolved against the git
+    ///   directory, as in git-lfs.
+    /// - Each git alternate object directory (`objects/info/alternates` and
+    ///   `GIT_ALTERNATE_OBJECT_DIRECTORIES`) contributes its sibling
+    ///   `lfs/objects` as a read-only alternate cache.
+    #[cfg(feature = "git2-integration")]
+    pub fn from_repo(repo: &git2::Repository) -> Result<Self> {
+        let config = repo.config()?;
+        let git_dir = repo.path();
+
+        let mut cache = match config.get_path("lfs.storage") {
+            Ok(storage) => Self::new(git_dir.join(storage).join("objects")),
+            Err(_) => Self::for_repo(git_dir),
+        };
+
+        for alternate in lfs_alternates(&git_dir.join("objects")) {
+            if alternate != cache.base_path {
+                cache = cache.with_alternate(alternate);
+            }
+        }
+
+        Ok(cache)
+    }
+
+    /// Limit the total size of the cache in bytes.
+    ///
+    /// After each `put()` or `CacheWriter::finish()`, least-recently-used
+    /// objects are removed until the cache fits. The object just written,
+    /// pinned objects, and cleaned objects not yet marked as pushed are
+    /// never evicted.
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Get the configured maximum cache size, if any.
+    pub fn max_size(&self) -> Option<u64> {
+        self.max_size
+    }
+
+    /// Add a read-only alternate object directory.
+    ///
+    /// Alternates use the same `<oid[0:2]>/<oid[2:4]>/<oid>` layout and are
+    /// searched in the order they were added.
+    pub fn with_alternate<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.alternates.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Set the directory for temp files and lock files.
+    ///
+    /// It must be on the same filesystem as the object directory so
+    /// finished objects can be renamed into place.
+    pub fn with_tmp_dir<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.tmp_path = path.as_ref().to_path_buf();
+        self
+    }
+
+    /// Get the directory used for temp files and lock files.
+    pub fn tmp_dir(&self) -> &Path {
+        &self.tmp_path
+    }
+
+    /// Get the primary object directory.
+    pub fn path(&self) -> &Path {
+        &self.base_path
+    }
+
+    /// Get the alternate object directories.
+    pub fn alternates(&self) -> &[PathBuf] {
+        &self.alternates
     }
 
     /// Get the path where an object with the given OID would be stored.
     pub fn object_path(&self, oid: &Oid) -> PathBuf {
-        let hex = oid.to_hex();
-        self.base_path
-            .join(&hex[0..2])
-            .join(&hex[2..4])
-            .join(&hex)
+        shard_path(&self.base_path, oid)
+    }
+
+    /// Find an object in the primary cache or one of the alternates.
+    ///
+    /// Returns the path of the first copy found.
+    pub fn locate(&self, oid: &Oid) -> Option<PathBuf> {
+        std::iter::once(&self.base_path)
+            .chain(&self.alternates)
+            .map(|dir| shard_path(dir, oid))
+            .find(|path| path.is_file())
     }
 
-    /// Check if an object exists in the cache.
+    /// Check if an object exists in the cache or an alternate.
     pub fn contains(&self, oid: &Oid) -> bool {
-        self.object_path(oid).exists()
+        self.locate(oid).is_some()
     }
 
     /// Check if an object exists and has the correct size.
     pub fn contains_valid(&self, pointer: &Pointer) -> bool {
-        let path = self.object_path(pointer.oid());
+        let path = match self.locate(pointer.oid()) {
+            Some(path) => path,
+            None => return false,
+        };
         match fs::metadata(&path) {
             Ok(meta) => meta.len() == pointer.size(),
             Err(_) => false,
@@ -60,8 +212,10 @@ impl ObjectCache {
     ///
     /// Returns `None` if the object is not cached.
     pub fn get(&self, oid: &Oid) -> Option<Vec<u8>> {
-        let path = self.object_path(oid);
-        fs::read(&path).ok()
+        let mut file = self.open(oid)?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content).ok()?;
+        Some(content)
     }
 
     /// Get an object and verify its hash.
@@ -86,75 +240,188 @@ impl ObjectCache {
 
     /// Store an object in the cache.
     ///
-    /// The object is stored atomically using a temp file + rename.
+    /// The object is written to a unique temp file under the OID's lock and
+    /// then renamed into place, so concurrent writers never see partial data.
     pub fn put(&self, oid: &Oid, content: &[u8]) -> Result<()> {
-        let path = self.object_path(oid);
-
-        // Create parent directories
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent).map_err(crate::Error::Io)?;
-        }
-
-        // Write to temp file first (atomic)
-        let temp_path = path.with_extension("tmp");
-        {
-            let mut file = File::create(&temp_path).map_err(crate::Error::Io)?;
-            file.write_all(content).map_err(crate::Error::Io)?;
-            file.sync_all().map_err(crate::Error::Io)?;
-        }
-
-        // Rename to final path
-        fs::rename(&temp_path, &path).map_err(crate::Error::Io)?;
+        self.put_from(oid, content, ObjectSource::Unknown)
+    }
 
-        Ok(())
+    /// Store an object, recording where it came from in the index.
+    pub fn put_from(&self, oid: &Oid, content: &[u8], source: ObjectSource) -> Result<()> {
+        let mut writer = self.writer(oid)?.with_source(source);
+        writer.write_all(content).map_err(crate::Error::Io)?;
+        writer.finish()
     }
 
     /// Store an object and verify the hash matches.
     pub fn put_verified(&self, pointer: &Pointer, content: &[u8]) -> Result<()> {
+        self.put_verified_from(pointer, content, ObjectSource::Unknown)
+    }
+
+    /// Store an object after verifying the hash, recording its source.
+    pub fn put_verified_from(
+        &self,
+        pointer: &Pointer,
+        content: &[u8],
+        source: ObjectSource,
+    ) -> Result<()> {
         // Verify content matches pointer
-        let computed = Pointer::from_content(content);
-        if computed.oid() != pointer.oid() {
-            return Err(crate::Error::InvalidPointer(
-                "content hash does not match pointer".into(),
-            ));
-        }
-        if computed.size() != pointer.size() {
-            return Err(crate::Error::InvalidPointer(
-                "content size does not match pointer".into(),
-            ));
-        }
+        let computed = Oid::from_content(content);
+        crate::Error::check_content(pointer, &computed, content.len() as u64)?;
 
-        self.put(pointer.oid(), content)
+        self.put_from(pointer.oid(), content, source)
     }
 
     /// Remove an object from the cache.
     pub fn remove(&self, oid: &Oid) -> Result<bool> {
         let path = self.object_path(oid);
-        match fs::remove_file(&path) {
-            Ok(()) => Ok(true),
-            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
-            Err(e) => Err(crate::Error::Io(e)),
-        }
+        let removed = match fs::remove_file(&path) {
+            Ok(()) => true,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+            Err(e) => return Err(crate::Error::Io(e)),
+        };
+        self.index.remove(oid).map_err(crate::Error::Io)?;
+        Ok(removed)
     }
 
     /// Get the total size of cached objects in bytes.
     pub fn size(&self) -> u64 {
-        self.iter_objects()
-            .filter_map(|path| fs::metadata(&path).ok())
-            .map(|meta| meta.len())
-            .sum()
+        self.index.total_size()
     }
 
     /// Get the number of cached objects.
     pub fn count(&self) -> usize {
-        self.iter_objects().count()
+        self.index.len()
+    }
+
+    /// Get the index entry for an object in the primary cache.
+    pub fn entry(&self, oid: &Oid) -> Option<IndexEntry> {
+        self.index.get(oid)
+    }
+
+    /// Get objects that are not known to be on the server.
+    ///
+    /// This includes cleaned objects not yet marked as pushed, and objects
+    /// of unknown source. Least recently used first.
+    pub fn unpushed(&self) -> Vec<(Oid, IndexEntry)> {
+        self.index.entries(|entry| !entry.pushed)
+    }
+
+    /// Get objects larger than `size` bytes, least recently used first.
+    pub fn larger_than(&self, size: u64) -> Vec<(Oid, IndexEntry)> {
+        self.index.entries(|entry| entry.size > size)
+    }
+
+    /// Record that an object has been uploaded to the server.
+    ///
+    /// Cleaned objects become eligible for eviction once pushed.
+    pub fn mark_pushed(&self, oid: &Oid) -> Result<()> {
+        self.index.mark_pushed(oid).map_err(crate::Error::Io)
+    }
+
+    /// Rebuild the index from the objects on disk.
+    ///
+    /// Use this after other tools have added or removed objects. Sources
+    /// and push state are lost; every object is recorded as
+    /// [`ObjectSource::Unknown`].
+    pub fn rebuild_index(&self) -> Result<()> {
+        self.index.rebuild().map_err(crate::Error::Io)
     }
 
     /// Iterate over all cached object paths.
+    ///
+    /// Only files at `<oid[0:2]>/<oid[2:4]>/<oid>` with a valid lowercase hex
+    /// OID are returned, so temp files, lock files and pin markers never
+    /// count as objects.
     fn iter_objects(&self) -> impl Iterator<Item = PathBuf> {
         let base = self.base_path.clone();
 
-        walkdir(base)
+        walkdir(base).filter(|path| is_object_path(path))
+    }
+
+    /// Remove temp files left behind by crashed or killed writers.
+    ///
+    /// Only files older than `older_than` are removed, so writes in progress
+    /// in other processes are left alone. Also removes stale lock files and
+    /// `<oid>.tmp` files left in the object directory by older versions.
+    ///
+    /// Returns the number of files removed.
+    pub fn sweep_temp_files(&self, older_than: Duration) -> Result<usize> {
+        let cutoff = SystemTime::now()
+            .checked_sub(older_than)
+            .unwrap_or(UNIX_EPOCH);
+        let is_stale = |path: &Path| {
+            fs::metadata(path)
+                .and_then(|meta| meta.modified())
+                .map(|modified| modified < cutoff)
+                .unwrap_or(false)
+        };
+
+        let mut removed = 0;
+
+        if let Ok(entries) = fs::read_dir(&self.tmp_path) {
+            for path in entries.flatten().map(|e| e.path()) {
+                let is_lock = path.extension() == Some("lock".as_ref());
+                let is_temp = path.extension() == Some("tmp".as_ref());
+                if !(is_lock || is_temp) || !is_stale(&path) {
+                    continue;
+                }
+                if is_lock {
+                    // Only remove a lock file nobody holds
+                    if let Some(lock) = ObjectLock::try_acquire(&path)? {
+                        drop(lock);
+                        removed += 1;
+                    }
+                } else if fs::remove_file(&path).is_ok() {
+                    removed += 1;
+                }
+            }
+        }
+
+        let legacy = walkdir(self.base_path.clone())
+            .filter(|path| path.extension() == Some("tmp".as_ref()) && is_stale(path));
+        for path in legacy {
+            if fs::remove_file(&path).is_ok() {
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Take the advisory write lock for an object, blocking until available.
+    ///
+    /// The lock is held until the returned guard is dropped.
+    fn lock(&self, oid: &Oid) -> Result<ObjectLock> {
+        fs::create_dir_all(&self.tmp_path).map_err(crate::Error::Io)?;
+        ObjectLock::acquire(&self.tmp_path.join(format!("{}.lock", oid.to_hex())))
+            .map_err(crate::Error::Io)
+    }
+
+    /// Create a uniquely named temp file for writing an object.
+    fn temp_file(&self, oid: &Oid) -> Result<(File, PathBuf)> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        fs::create_dir_all(&self.tmp_path).map_err(crate::Error::Io)?;
+        loop {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.subsec_nanos())
+                .unwrap_or(0);
+            let name = format!(
+                "{}-{}-{}-{}.tmp",
+                oid.to_hex(),
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed),
+                nanos
+            );
+            let path = self.tmp_path.join(name);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => return Ok((file, path)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(crate::Error::Io(e)),
+            }
+        }
     }
 
     /// Prune objects not referenced by any pointer.
@@ -171,6 +438,9 @@ impl ObjectCache {
                         removed += meta.len();
                         let _ = fs::remove_file(&path);
                     }
+                    if let Ok(oid) = Oid::from_hex(filename) {
+                        self.index.remove(&oid).map_err(crate::Error::Io)?;
+                    }
                 }
             }
         }
@@ -179,15 +449,198 @@ impl ObjectCache {
     }
 
     /// Open a cached object for streaming read.
+    ///
+    /// Falls back to the alternates if the object is not in the primary cache.
     pub fn open(&self, oid: &Oid) -> Option<File> {
-        let path = self.object_path(oid);
-        File::open(&path).ok()
+        if let Ok(file) = File::open(self.object_path(oid)) {
+            if let Ok(meta) = file.metadata() {
+                let _ = self.index.access(oid, meta.len());
+            }
+            return Some(file);
+        }
+
+        // Alternates are read-only, so no access time is recorded there
+        self.alternates
+            .iter()
+            .find_map(|dir| File::open(shard_path(dir, oid)).ok())
+    }
+
+    /// Materialize a cached object as a working-tree file.
+    ///
+    /// The object is verified against the pointer, then written to `dest`
+    /// using `mode`, falling back to a streamed copy if linking isn't
+    /// possible (e.g. across filesystems). `dest` is replaced atomically, so
+    /// an existing file, or an existing link to the cache, is never written
+    /// through.
+    ///
+    /// Returns the mode that was actually used.
+    pub fn checkout(
+        &self,
+        pointer: &Pointer,
+        dest: &Path,
+        mode: CheckoutMode,
+    ) -> Result<CheckoutMode> {
+        let src = self
+            .locate(pointer.oid())
+            .ok_or_else(|| crate::Error::NotFound {
+                oid: Some(pointer.oid().clone()),
+                url: None,
+            })?;
+        let temp_path = checkout_temp_path(dest);
+
+        let used = match mode {
+            CheckoutMode::Copy => None,
+            CheckoutMode::Reflink => {
+                verify_object(&src, pointer)?;
+                reflink(&src, &temp_path)
+                    .ok()
+                    .map(|()| CheckoutMode::Reflink)
+            }
+            CheckoutMode::Hardlink if src == self.object_path(pointer.oid()) => {
+                verify_object(&src, pointer)?;
+                make_readonly(&src)
+                    .and_then(|()| fs::hard_link(&src, &temp_path))
+                    .ok()
+                    .map(|()| CheckoutMode::Hardlink)
+            }
+            // Alternates are shared and read-only, so they are never
+            // chmodded or linked into a working tree
+            CheckoutMode::Hardlink => {
+                verify_object(&src, pointer)?;
+                reflink(&src, &temp_path)
+                    .ok()
+                    .map(|()| CheckoutMode::Reflink)
+            }
+        };
+
+        let used = match used {
+            Some(used) => used,
+            None => {
+                if let Err(e) = copy_verified(&src, &temp_path, pointer) {
+                    let _ = fs::remove_file(&temp_path);
+                    return Err(e);
+                }
+                CheckoutMode::Copy
+            }
+        };
+
+        // Links share permissions with the cache; copies keep the old file's mode
+        if used != CheckoutMode::Hardlink {
+            if let Ok(meta) = fs::metadata(dest) {
+                let _ = fs::set_permissions(&temp_path, meta.permissions());
+            }
+        }
+
+        if let Err(e) = fs::rename(&temp_path, dest) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(crate::Error::Io(e));
+        }
+
+        Ok(used)
+    }
+
+    /// Pin an object so it is never evicted.
+    ///
+    /// Use this for objects that only exist locally, such as content that
+    /// has been cleaned but not yet uploaded.
+    pub fn pin(&self, oid: &Oid) -> Result<()> {
+        let pin_dir = self.pin_dir();
+        fs::create_dir_all(&pin_dir).map_err(crate::Error::Io)?;
+        File::create(pin_dir.join(oid.to_hex())).map_err(crate::Error::Io)?;
+        Ok(())
+    }
+
+    /// Unpin an object, making it eligible for eviction again.
+    ///
+    /// Returns `false` if the object was not pinned.
+    pub fn unpin(&self, oid: &Oid) -> Result<bool> {
+        match fs::remove_file(self.pin_dir().join(oid.to_hex())) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(crate::Error::Io(e)),
+        }
+    }
+
+    /// Check if an object is pinned.
+    pub fn is_pinned(&self, oid: &Oid) -> bool {
+        self.pin_dir().join(oid.to_hex()).exists()
+    }
+
+    /// Check if a corrupt object may be deleted so it can be fetched again.
+    ///
+    /// Copies in the alternates are never touched. Pinned objects and
+    /// objects not known to be on the server may exist nowhere else.
+    #[cfg(feature = "git2-integration")]
+    pub(crate) fn is_discardable(&self, oid: &Oid) -> bool {
+        if !self.object_path(oid).is_file() {
+            return true;
+        }
+        !self.is_pinned(oid) && self.index.get(oid).is_some_and(|entry| entry.pushed)
+    }
+
+    /// Directory holding pin markers, one empty file per pinned OID.
+    fn pin_dir(&self) -> PathBuf {
+        self.base_path.join("pinned")
+    }
+
+    /// Evict least-recently-used objects until the cache fits its maximum size.
+    ///
+    /// Returns the number of bytes removed. Does nothing if no maximum size
+    /// is configured.
+    pub fn evict(&self) -> Result<u64> {
+        self.evict_except(None)
+    }
+
+    fn evict_except(&self, keep: Option<&Oid>) -> Result<u64> {
+        let max_size = match self.max_size {
+            Some(max_size) => max_size,
+            None => return Ok(0),
+        };
+
+        let mut total = self.index.total_size();
+        if total <= max_size {
+            return Ok(0);
+        }
+
+        let pinned: HashSet<String> = match fs::read_dir(self.pin_dir()) {
+            Ok(entries) => entries
+                .flatten()
+                .filter_map(|e| e.file_name().into_string().ok())
+                .collect(),
+            Err(_) => HashSet::new(),
+        };
+
+        // Least recently used first
+        let candidates = self
+            .index
+            .entries(|entry| entry.pushed || entry.source != ObjectSource::Clean);
+
+        let mut removed = 0u64;
+        for (oid, entry) in candidates {
+            if total <= max_size {
+                break;
+            }
+            if Some(&oid) == keep || pinned.contains(&oid.to_hex()) {
+                continue;
+            }
+            match fs::remove_file(self.object_path(&oid)) {
+                Ok(()) => removed += entry.size,
+                // Already gone; only the index was out of date
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(_) => continue,
+            }
+            self.index.remove(&oid).map_err(crate::Error::Io)?;
+            total -= entry.size;
+        }
+
+        Ok(removed)
     }
 
     /// Create a writer for storing an object.
     ///
     /// Returns a `CacheWriter` that will atomically store the object
-    /// when finished.
+    /// when finished. The writer holds the OID's lock until it is finished
+    /// or dropped, so other writers of the same object wait for it.
     pub fn writer(&self, oid: &Oid) -> Result<CacheWriter> {
         let final_path = self.object_path(oid);
 
@@ -196,32 +649,57 @@ impl ObjectCache {
             fs::create_dir_all(parent).map_err(crate::Error::Io)?;
         }
 
-        let temp_path = final_path.with_extension("tmp");
-        let file = File::create(&temp_path).map_err(crate::Error::Io)?;
+        let lock = self.lock(oid)?;
+        let (file, temp_path) = self.temp_file(oid)?;
 
         Ok(CacheWriter {
+            cache: self.clone(),
+            oid: oid.clone(),
             file,
             temp_path,
             final_path,
+            source: ObjectSource::Unknown,
             finished: false,
+            _lock: lock,
         })
     }
 }
 
 /// Writer for streaming content into the cache.
 pub struct CacheWriter {
+    cache: ObjectCache,
+    oid: Oid,
     file: File,
     temp_path: PathBuf,
     final_path: PathBuf,
+    source: ObjectSource,
     finished: bool,
+    // Declared last so it is released after the temp file is cleaned up
+    _lock: ObjectLock,
 }
 
 impl CacheWriter {
+    /// Record where the object came from in the cache index.
+    ///
+    /// Defaults to [`ObjectSource::Unknown`].
+    pub fn with_source(mut self, source: ObjectSource) -> Self {
+        self.source = source;
+        self
+    }
+
     /// Finish writing and atomically move to final location.
+    ///
+    /// Evicts older objects afterwards if the cache has a maximum size.
     pub fn finish(mut self) -> Result<()> {
         self.file.sync_all().map_err(crate::Error::Io)?;
+        let size = self.file.metadata().map_err(crate::Error::Io)?.len();
         fs::rename(&self.temp_path, &self.final_path).map_err(crate::Error::Io)?;
         self.finished = true;
+        self.cache
+            .index
+            .add(&self.oid, size, self.source)
+            .map_err(crate::Error::Io)?;
+        self.cache.evict_except(Some(&self.oid))?;
         Ok(())
     }
 }
@@ -245,8 +723,228 @@ impl Drop for CacheWriter {
     }
 }
 
+/// Advisory lock on a lock file, removed again on release.
+///
+/// The file is unlinked while the lock is still held. A waiter that opened
+/// the old file before it was unlinked notices the path now refers to a
+/// different file (or none) and retries, so two holders can never coexist.
+#[derive(Debug)]
+struct ObjectLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl ObjectLock {
+    fn acquire(path: &Path) -> io::Result<Self> {
+        loop {
+            let file = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(path)?;
+            file.lock()?;
+            if is_same_file(&file, path) {
+                return Ok(ObjectLock {
+                    file,
+                    path: path.to_path_buf(),
+                });
+            }
+        }
+    }
+
+    /// Like `acquire`, but returns `None` if the lock is held elsewhere.
+    fn try_acquire(path: &Path) -> Result<Option<Self>> {
+        let file = match OpenOptions::new().write(true).open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(crate::Error::Io(e)),
+        };
+        match file.try_lock() {
+            Ok(()) if is_same_file(&file, path) => Ok(Some(ObjectLock {
+                file,
+                path: path.to_path_buf(),
+            })),
+            Ok(()) | Err(fs::TryLockError::WouldBlock) => Ok(None),
+            Err(fs::TryLockError::Error(e)) => Err(crate::Error::Io(e)),
+        }
+    }
+}
+
+impl Drop for ObjectLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        let _ = self.file.unlock();
+    }
+}
+
+/// Check that `path` still refers to the open `file`.
+#[cfg(unix)]
+fn is_same_file(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+/// Open files can't be unlinked on Windows, so the path can't have changed.
+#[cfg(not(unix))]
+fn is_same_file(_file: &File, path: &Path) -> bool {
+    path.exists()
+}
+
+/// Check that a path is a valid object location: `<aa>/<bb>/<aabb...>`.
+pub(crate) fn is_object_path(path: &Path) -> bool {
+    let name = match path.file_name().and_then(|s| s.to_str()) {
+        Some(name) => name,
+        None => return false,
+    };
+    if name.len() != 64 || !name.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return false;
+    }
+
+    let parent = path.parent();
+    let grandparent = parent.and_then(Path::parent);
+    parent.and_then(Path::file_name) == Some(name[2..4].as_ref())
+        && grandparent.and_then(Path::file_name) == Some(name[0..2].as_ref())
+}
+
+/// Path of an object within an object directory.
+fn shard_path(dir: &Path, oid: &Oid) -> PathBuf {
+    let hex = oid.to_hex();
+    dir.join(&hex[0..2]).join(&hex[2..4]).join(&hex)
+}
+
+/// LFS object directories of a git directory's alternates.
+///
+/// git-lfs keeps objects for an alternate `<dir>/objects` in `<dir>/lfs/objects`.
+#[cfg(feature = "git2-integration")]
+fn lfs_alternates(objects_dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+
+    if let Ok(content) = fs::read_to_string(objects_dir.join("info").join("alternates")) {
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // Relative entries are relative to the objects directory
+            dirs.push(objects_dir.join(line));
+        }
+    }
+
+    if let Some(env) = std::env::var_os("GIT_ALTERNATE_OBJECT_DIRECTORIES") {
+        dirs.extend(std::env::split_paths(&env).filter(|p| !p.as_os_str().is_empty()));
+    }
+
+    dirs.iter()
+        .filter_map(|dir| dir.parent())
+        .map(|parent| parent.join("lfs").join("objects"))
+        .collect()
+}
+
+/// Temporary path next to `dest`, so the final rename stays on one filesystem.
+///
+/// Unique per call, so concurrent checkouts of the same path don't collide.
+fn checkout_temp_path(dest: &Path) -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let name = dest
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    dest.with_file_name(format!(
+        ".{}.lfs-{}-{}.tmp",
+        name,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Replace a working-tree file with new content via a temp file and rename.
+///
+/// Never writes through the existing file, which may be a hard link into
+/// the cache.
+#[cfg(feature = "git2-integration")]
+pub(crate) fn replace_file(dest: &Path, content: &[u8]) -> io::Result<()> {
+    let temp_path = checkout_temp_path(dest);
+    if let Err(e) = fs::write(&temp_path, content) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+    if let Ok(meta) = fs::metadata(dest) {
+        if !meta.permissions().readonly() {
+            let _ = fs::set_permissions(&temp_path, meta.permissions());
+        }
+    }
+    fs::rename(&temp_path, dest).inspect_err(|_| {
+        let _ = fs::remove_file(&temp_path);
+    })
+}
+
+/// Check that a stored object hashes to the pointer's OID and size.
+fn verify_object(path: &Path, pointer: &Pointer) -> Result<()> {
+    let file = File::open(path).map_err(crate::Error::Io)?;
+    let (oid, size) = Oid::from_reader(file).map_err(crate::Error::Io)?;
+    crate::Error::check_content(pointer, &oid, size)
+}
+
+/// Stream `src` to a new file at `dest`, verifying the content on the way.
+fn copy_verified(src: &Path, dest: &Path, pointer: &Pointer) -> Result<()> {
+    let mut reader = File::open(src).map_err(crate::Error::Io)?;
+    let file = File::create(dest).map_err(crate::Error::Io)?;
+    let mut writer = HashingWriter::new(file);
+    io::copy(&mut reader, &mut writer).map_err(crate::Error::Io)?;
+
+    let (oid, size, _) = writer.finish();
+    crate::Error::check_content(pointer, &oid, size)
+}
+
+/// Clone `src` into a new file at `dest` using a copy-on-write reflink.
+#[cfg(target_os = "linux")]
+fn reflink(src: &Path, dest: &Path) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let src_file = File::open(src)?;
+    let dest_file = File::create(dest)?;
+    // SAFETY: both descriptors are valid for the duration of the call
+    let ret = unsafe {
+        libc::ioctl(
+            dest_file.as_raw_fd(),
+            libc::FICLONE as _,
+            src_file.as_raw_fd(),
+        )
+    };
+    if ret == -1 {
+        let err = io::Error::last_os_error();
+        drop(dest_file);
+        let _ = fs::remove_file(dest);
+        return Err(err);
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_src: &Path, _dest: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "reflinks are not supported on this platform",
+    ))
+}
+
+/// Make a cached object read-only before sharing its inode with a hard link.
+fn make_readonly(path: &Path) -> io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    if !permissions.readonly() {
+        permissions.set_readonly(true);
+        fs::set_permissions(path, permissions)?;
+    }
+    Ok(())
+}
+
 /// Walk a directory tree and return all file paths.
-fn walkdir(base: PathBuf) -> impl Iterator<Item = PathBuf> {
+pub(crate) fn walkdir(base: PathBuf) -> impl Iterator<Item = PathBuf> {
     let mut stack = vec![base];
 
     std::iter::from_fn(move || {
@@ -357,4 +1055,492 @@ mod tests {
         assert_eq!(cache.count(), 2);
         assert_eq!(cache.size(), (content1.len() + content2.len()) as u64);
     }
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let td = TempDir::new().unwrap();
+        let cache = ObjectCache::new(td.path()).with_max_size(45);
+
+        let first = b"first object, 20 byte";
+        let second = b"second object 20 byte";
+        let third = b"third object, 20 byte";
+        let (p1, p2, p3) = (
+            Pointer::from_content(first),
+            Pointer::from_content(second),
+            Pointer::from_content(third),
+        );
+
+        cache.put(p1.oid(), first).unwrap();
+        cache.put(p2.oid(), second).unwrap();
+
+        // Reading the first object makes the second one least recently used
+        assert!(cache.get(p1.oid()).is_some());
+
+        cache.put(p3.oid(), third).unwrap();
+
+        assert!(cache.contains(p1.oid()));
+        assert!(!cache.contains(p2.oid()));
+        assert!(cache.contains(p3.oid()));
+        assert!(cache.size() <= 45);
+    }
+
+    #[test]
+    fn test_evict_skips_pinned() {
+        let td = TempDir::new().unwrap();
+        let cache = ObjectCache::new(td.path()).with_max_size(10);
+
+        let pinned = b"pinned, not yet pushed";
+        let other = b"ordinary cached object";
+        let p1 = Pointer::from_content(pinned);
+        let p2 = Pointer::from_content(other);
+
+        cache.put(p1.oid(), pinned).unwrap();
+        cache.pin(p1.oid()).unwrap();
+        assert!(cache.is_pinned(p1.oid()));
+
+        // Over the limit, but the pinned object and the new object both stay
+        cache.put(p2.oid(), other).unwrap();
+        assert!(cache.contains(p1.oid()));
+        assert!(cache.contains(p2.oid()));
+
+        // Pin markers are not counted as objects
+        assert_eq!(cache.count(), 2);
+
+        // Once unpinned, the old object can go
+        assert!(cache.unpin(p1.oid()).unwrap());
+        cache.evict().unwrap();
+        assert!(!cache.contains(p1.oid()));
+        assert!(!cache.unpin(p1.oid()).unwrap());
+    }
+
+    #[test]
+    fn test_no_eviction_without_limit() {
+        let td = TempDir::new().unwrap();
+        let cache = ObjectCache::new(td.path());
+
+        let content = b"kept forever";
+        let pointer = Pointer::from_content(content);
+        cache.put(pointer.oid(), content).unwrap();
+
+        assert_eq!(cache.evict().unwrap(), 0);
+        assert!(cache.contains(pointer.oid()));
+    }
+
+    #[test]
+    fn test_alternate_lookup() {
+        let primary_dir = TempDir::new().unwrap();
+        let shared_dir = TempDir::new().unwrap();
+
+        let content = b"base image layer";
+        let pointer = Pointer::from_content(content);
+        ObjectCache::new(shared_dir.path())
+            .put(pointer.oid(), content)
+            .unwrap();
+
+        let cache = ObjectCache::new(primary_dir.path()).with_alternate(shared_dir.path());
+
+        assert!(cache.contains(pointer.oid()));
+        assert!(cache.contains_valid(&pointer));
+        assert_eq!(cache.get_verified(&pointer).unwrap(), content);
+        assert!(cache
+            .locate(pointer.oid())
+            .unwrap()
+            .starts_with(shared_dir.path()));
+
+        // Alternates are not part of the primary cache
+        assert_eq!(cache.count(), 0);
+        assert!(!cache.object_path(pointer.oid()).exists());
+        assert!(!cache.remove(pointer.oid()).unwrap());
+        assert!(cache.contains(pointer.oid()));
+    }
+
+    #[test]
+    #[cfg(feature = "git2-integration")]
+    fn test_from_repo_storage_and_alternates() {
+        let temp = TempDir::new().unwrap();
+        let shared = TempDir::new().unwrap();
+        let repo = git2::Repository::init(temp.path()).unwrap();
+
+        // A relative lfs.storage is resolved against the git directory
+        repo.config()
+            .unwrap()
+            .set_str("lfs.storage", "shared-lfs")
+            .unwrap();
+
+        // Alternate object directory with an LFS cache next to it
+        let alt_objects = shared.path().join("objects");
+        fs::create_dir_all(&alt_objects).unwrap();
+        let info = repo.path().join("objects").join("info");
+        fs::create_dir_all(&info).unwrap();
+        fs::write(
+            info.join("alternates"),
+            format!("{}\n", alt_objects.display()),
+        )
+        .unwrap();
+
+        let cache = ObjectCache::from_repo(&repo).unwrap();
+        assert_eq!(cache.path(), repo.path().join("shared-lfs").join("objects"));
+        assert_eq!(
+            cache.alternates(),
+            &[shared.path().join("lfs").join("objects")]
+        );
+    }
+
+    #[test]
+    fn test_checkout_copy() {
+        let td = TempDir::new().unwrap();
+        let cache = ObjectCache::new(td.path().join("objects"));
+
+        let content = b"dataset shard";
+        let pointer = Pointer::from_content(content);
+        cache.put(pointer.oid(), content).unwrap();
+
+        // Destination currently holds the pointer file
+        let dest = td.path().join("shard.bin");
+        fs::write(&dest, pointer.encode_bytes()).unwrap();
+
+        let used = cache.checkout(&pointer, &dest, CheckoutMode::Copy).unwrap();
+        assert_eq!(used, CheckoutMode::Copy);
+        assert_eq!(fs::read(&dest).unwrap(), content);
+    }
+
+    #[test]
+    fn test_checkout_reflink_falls_back_to_copy() {
+        let td = TempDir::new().unwrap();
+        let cache = ObjectCache::new(td.path().join("objects"));
+
+        let content = b"reflink me if you can";
+        let pointer = Pointer::from_content(content);
+        cache.put(pointer.oid(), content).unwrap();
+
+        // Whether the filesystem supports reflinks or not, the content arrives
+        let dest = td.path().join("data.bin");
+        let used = cache
+            .checkout(&pointer, &dest, CheckoutMode::Reflink)
+            .unwrap();
+        assert!(used == CheckoutMode::Reflink || used == CheckoutMode::Copy);
+        assert_eq!(fs::read(&dest).unwrap(), content);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_checkout_hardlink_protects_cache() {
+        use std::os::unix::fs::MetadataExt;
+
+        let td = TempDir::new().unwrap();
+        let cache = ObjectCache::new(td.path().join("objects"));
+
+        let content = b"linked content";
+        let pointer = Pointer::from_content(content);
+        cache.put(pointer.oid(), content).unwrap();
+
+        let dest = td.path().join("linked.bin");
+        let used = cache
+            .checkout(&pointer, &dest, CheckoutMode::Hardlink)
+            .unwrap();
+        assert_eq!(used, CheckoutMode::Hardlink);
+
+        let object = cache.object_path(pointer.oid());
+        assert_eq!(
+            fs::metadata(&object).unwrap().ino(),
+            fs::metadata(&dest).unwrap().ino()
+        );
+        assert!(fs::metadata(&object).unwrap().permissions().readonly());
+
+        // Checking out again replaces the link instead of writing through it
+        let used = cache.checkout(&pointer, &dest, CheckoutMode::Copy).unwrap();
+        assert_eq!(used, CheckoutMode::Copy);
+        assert_ne!(
+            fs::metadata(&object).unwrap().ino(),
+            fs::metadata(&dest).unwrap().ino()
+        );
+        assert_eq!(cache.get_verified(&pointer).unwrap(), content);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_checkout_never_links_alternates() {
+        use std::os::unix::fs::MetadataExt;
+
+        let td = TempDir::new().unwrap();
+        let shared = TempDir::new().unwrap();
+        let content = b"shared across the host";
+        let pointer = Pointer::from_content(content);
+        let alternate = ObjectCache::new(shared.path());
+        alternate.put(pointer.oid(), content).unwrap();
+        let object = alternate.object_path(pointer.oid());
+        let permissions = fs::metadata(&object).unwrap().permissions();
+
+        let cache = ObjectCache::new(td.path().join("objects")).with_alternate(shared.path());
+        let dest = td.path().join("shared.bin");
+        let used = cache
+            .checkout(&pointer, &dest, CheckoutMode::Hardlink)
+            .unwrap();
+        assert!(used == CheckoutMode::Reflink || used == CheckoutMode::Copy);
+        assert_eq!(fs::read(&dest).unwrap(), content);
+        assert_ne!(
+            fs::metadata(&object).unwrap().ino(),
+            fs::metadata(&dest).unwrap().ino()
+        );
+        assert_eq!(fs::metadata(&object).unwrap().permissions(), permissions);
+    }
+
+    #[test]
+    fn test_checkout_rejects_corrupt_object() {
+        let td = TempDir::new().unwrap();
+        let cache = ObjectCache::new(td.path().join("objects"));
+
+        let pointer = Pointer::from_content(b"expected content");
+        cache.put(pointer.oid(), b"corrupted content").unwrap();
+
+        let dest = td.path().join("out.bin");
+        for mode in [
+            CheckoutMode::Copy,
+            CheckoutMode::Reflink,
+            CheckoutMode::Hardlink,
+        ] {
+            assert!(cache.checkout(&pointer, &dest, mode).is_err());
+            assert!(!dest.exists());
+        }
+    }
+
+    #[test]
+    fn test_tmp_dir_layout() {
+        let td = TempDir::new().unwrap();
+
+        let cache = ObjectCache::for_repo(td.path());
+        assert_eq!(cache.tmp_dir(), td.path().join("lfs").join("tmp"));
+
+        let cache = ObjectCache::new(td.path().join("cache"));
+        assert_eq!(cache.tmp_dir(), td.path().join("cache").join("tmp"));
+
+        let cache = cache.with_tmp_dir(td.path().join("scratch"));
+        assert_eq!(cache.tmp_dir(), td.path().join("scratch"));
+    }
+
+    #[test]
+    fn test_concurrent_put_same_object() {
+        let td = TempDir::new().unwrap();
+        let cache = ObjectCache::new(td.path().join("objects"));
+
+        let content = vec![7u8; 64 * 1024];
+        let oid = Oid::from_content(&content);
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let cache = cache.clone();
+                let oid = oid.clone();
+                let content = content.clone();
+                std::thread::spawn(move || cache.put(&oid, &content))
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap().unwrap();
+        }
+
+        assert_eq!(cache.get(&oid).unwrap(), content);
+        assert_eq!(cache.count(), 1);
+        // No temp or lock files are left behind
+        assert_eq!(fs::read_dir(cache.tmp_dir()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_stray_files_are_not_objects() {
+        let td = TempDir::new().unwrap();
+        let cache = ObjectCache::new(td.path());
+
+        let oid = Oid::from_content(b"real object");
+        cache.put(&oid, b"real object").unwrap();
+
+        let shard = cache.object_path(&oid).parent().unwrap().to_path_buf();
+        fs::write(shard.join(format!("{}.tmp", oid.to_hex())), b"partial").unwrap();
+        fs::write(shard.join(".DS_Store"), b"junk").unwrap();
+        fs::write(td.path().join("README"), b"junk").unwrap();
+        let misplaced = Oid::from_content(b"misplaced");
+        fs::write(shard.join(misplaced.to_hex()), b"misplaced").unwrap();
+        cache.pin(&oid).unwrap();
+
+        assert_eq!(cache.count(), 1);
+        assert_eq!(cache.size(), 11);
+    }
+
+    #[test]
+    fn test_sweep_temp_files() {
+        let td = TempDir::new().unwrap();
+        let cache = ObjectCache::new(td.path().join("objects"));
+        let oid = Oid::from_content(b"content");
+        cache.put(&oid, b"content").unwrap();
+
+        fs::create_dir_all(cache.tmp_dir()).unwrap();
+        let stale = cache.tmp_dir().join("stale-1-2-3.tmp");
+        fs::write(&stale, b"partial").unwrap();
+        let legacy = cache.object_path(&oid).with_extension("tmp");
+        fs::write(&legacy, b"partial").unwrap();
+
+        // Recent files are left alone
+        assert_eq!(
+            cache.sweep_temp_files(Duration::from_secs(3600)).unwrap(),
+            0
+        );
+        assert!(stale.exists());
+
+        // A held lock is never removed, even when old
+        let held = cache.lock(&oid).unwrap();
+        assert_eq!(cache.sweep_temp_files(Duration::ZERO).unwrap(), 2);
+        assert!(!stale.exists());
+        assert!(!legacy.exists());
+        assert!(cache
+            .tmp_dir()
+            .join(format!("{}.lock", oid.to_hex()))
+            .exists());
+        drop(held);
+
+        assert_eq!(cache.get(&oid).unwrap(), b"content");
+    }
+
+    #[test]
+    fn test_index_queries() {
+        let td = TempDir::new().unwrap();
+        let cache = ObjectCache::new(td.path());
+
+        let small = Pointer::from_content(b"small");
+        let large = Pointer::from_content(b"a rather larger object");
+        cache
+            .put_verified_from(&small, b"small", ObjectSource::Download)
+            .unwrap();
+        cache
+            .put_verified_from(&large, b"a rather larger object", ObjectSource::Clean)
+            .unwrap();
+
+        let entry = cache.entry(large.oid()).unwrap();
+        assert_eq!(entry.size, 22);
+        assert_eq!(entry.source, ObjectSource::Clean);
+        assert!(!entry.pushed);
+        assert!(entry.inserted <= entry.last_access);
+        assert!(cache.entry(small.oid()).unwrap().pushed);
+
+        let unpushed: Vec<_> = cache.unpushed().into_iter().map(|e| e.0).collect();
+        assert_eq!(unpushed, vec![large.oid().clone()]);
+        let larger: Vec<_> = cache.larger_than(10).into_iter().map(|e| e.0).collect();
+        assert_eq!(larger, vec![large.oid().clone()]);
+
+        cache.mark_pushed(large.oid()).unwrap();
+        assert!(cache.unpushed().is_empty());
+
+        // A fresh handle sees the same metadata
+        let reopened = ObjectCache::new(td.path());
+        assert_eq!(reopened.count(), 2);
+        assert_eq!(
+            reopened.entry(large.oid()).unwrap(),
+            cache.entry(large.oid()).unwrap()
+        );
+
+        cache.remove(small.oid()).unwrap();
+        assert_eq!(reopened.count(), 1);
+        assert_eq!(reopened.size(), 22);
+    }
+
+    #[test]
+    fn test_index_rebuilt_from_disk() {
+        let td = TempDir::new().unwrap();
+        let cache = ObjectCache::new(td.path());
+        let oid = Oid::from_content(b"content");
+        cache
+            .put_from(&oid, b"content", ObjectSource::Clean)
+            .unwrap();
+
+        // Objects written by other tools are found once the index is rebuilt
+        let other = Oid::from_content(b"from git-lfs");
+        let path = cache.object_path(&other);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, b"from git-lfs").unwrap();
+        assert_eq!(cache.count(), 1);
+        cache.rebuild_index().unwrap();
+        assert_eq!(cache.count(), 2);
+        assert_eq!(cache.entry(&oid).unwrap().source, ObjectSource::Unknown);
+
+        // A missing index is rebuilt on first use
+        fs::remove_file(td.path().join("index.jsonl")).unwrap();
+        let reopened = ObjectCache::new(td.path());
+        assert_eq!(reopened.count(), 2);
+        assert_eq!(reopened.size(), 19);
+    }
+
+    #[test]
+    fn test_evict_skips_unpushed() {
+        let td = TempDir::new().unwrap();
+        let cache = ObjectCache::new(td.path()).with_max_size(10);
+
+        let cleaned = Pointer::from_content(b"cleaned, not yet pushed");
+        let other = Pointer::from_content(b"ordinary cached object");
+        cache
+            .put_verified_from(&cleaned, b"cleaned, not yet pushed", ObjectSource::Clean)
+            .unwrap();
+        cache
+            .put_verified_from(&other, b"ordinary cached object", ObjectSource::Download)
+            .unwrap();
+        assert!(cache.contains(cleaned.oid()));
+
+        cache.mark_pushed(cleaned.oid()).unwrap();
+        cache.evict().unwrap();
+        assert!(!cache.contains(cleaned.oid()));
+        assert!(cache.entry(cleaned.oid()).is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "git2-integration")]
+    fn test_is_discardable_keeps_local_objects() {
+        let td = TempDir::new().unwrap();
+        let cache = ObjectCache::new(td.path());
+
+        let downloaded = Oid::from_content(b"downloaded");
+        let cleaned = Oid::from_content(b"cleaned");
+        let pinned = Oid::from_content(b"pinned");
+        cache
+            .put_from(&downloaded, b"downloaded", ObjectSource::Download)
+            .unwrap();
+        cache
+            .put_from(&cleaned, b"cleaned", ObjectSource::Clean)
+            .unwrap();
+        cache
+            .put_from(&pinned, b"pinned", ObjectSource::Download)
+            .unwrap();
+        cache.pin(&pinned).unwrap();
+
+        assert!(cache.is_discardable(&downloaded));
+        assert!(!cache.is_discardable(&cleaned));
+        assert!(!cache.is_discardable(&pinned));
+        assert!(cache.is_discardable(&Oid::from_content(b"not cached")));
+
+        cache.mark_pushed(&cleaned).unwrap();
+        assert!(cache.is_discardable(&cleaned));
+    }
+
+    #[test]
+    fn test_put_verified_mismatch_errors() {
+        let td = TempDir::new().unwrap();
+        let cache = ObjectCache::new(td.path());
+        let pointer = Pointer::from_content(b"expected");
+
+        match cache.put_verified(&pointer, b"short") {
+            Err(crate::Error::SizeMismatch {
+                oid,
+                expected,
+                actual,
+            }) => {
+                assert_eq!(&oid, pointer.oid());
+                assert_eq!((expected, actual), (8, 5));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        match cache.put_verified(&pointer, b"EXPECTED") {
+            Err(crate::Error::HashMismatch { expected, actual }) => {
+                assert_eq!(&expected, pointer.oid());
+                assert_eq!(actual, Oid::from_content(b"EXPECTED"));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(!cache.contains(pointer.oid()));
+    }
 }
@@ -0,0 +1,408 @@
+//! Strict validation of the canonical pointer format.
+//!
+//! [`Pointer::parse`](crate::Pointer::parse) is lenient, like git-lfs when
+//! smudging. Pointers committed to git should also be in the canonical form
+//! from the spec, which this module checks:
+//!
+//! - `version` first, then the remaining keys sorted (`ext-*`, `oid`, `size`)
+//! - each line is exactly `key value\n`: no blank lines, CRs or extra spaces
+//! - a final newline
+//! - lowercase hex OIDs and sizes without leading zeros
+//! - no unknown or repeated keys, and no two extensions with one priority
+
+use std::fmt;
+
+use crate::pointer::{PointerExtension, LFS_SPEC_V1, MAX_POINTER_SIZE};
+
+/// One reason a blob is not a canonical pointer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PointerDiagnostic {
+    line: Option<usize>,
+    kind: DiagnosticKind,
+}
+
+/// What is wrong with a pointer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// Larger than [`MAX_POINTER_SIZE`](crate::pointer::MAX_POINTER_SIZE).
+    TooLarge,
+    /// Not valid UTF-8.
+    InvalidUtf8,
+    /// The last line has no trailing `\n`.
+    MissingFinalNewline,
+    /// Line ends with `\r\n`.
+    CarriageReturn,
+    /// Empty line.
+    BlankLine,
+    /// Whitespace before the key.
+    LeadingWhitespace,
+    /// Whitespace after the value.
+    TrailingWhitespace,
+    /// Not `key value`, or more than one space between them.
+    MalformedLine,
+    /// A key the spec doesn't define.
+    UnknownKey(String),
+    /// A key that already appeared.
+    DuplicateKey(String),
+    /// `version` is not the first line.
+    VersionNotFirst,
+    /// A key sorts before the one on the previous line.
+    OutOfOrder {
+        /// The misplaced key.
+        key: String,
+        /// The key on the previous line, which should come after it.
+        previous: String,
+    },
+    /// A required key is missing.
+    MissingKey(&'static str),
+    /// `version` is not the current spec URL.
+    UnsupportedVersion(String),
+    /// `oid` is not `sha256:` followed by 64 hex digits.
+    InvalidOid(String),
+    /// The OID contains uppercase hex digits.
+    UppercaseOid,
+    /// `size` is not a decimal integer.
+    InvalidSize(String),
+    /// `size` has leading zeros.
+    SizeLeadingZeros,
+    /// An `ext-*` line is malformed.
+    InvalidExtension(String),
+    /// An extension has the same priority as an earlier one.
+    DuplicatePriority(u8),
+}
+
+impl PointerDiagnostic {
+    fn new(line: Option<usize>, kind: DiagnosticKind) -> Self {
+        PointerDiagnostic { line, kind }
+    }
+
+    /// The 1-based line number, or `None` for problems with the whole blob.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    /// What is wrong.
+    pub fn kind(&self) -> &DiagnosticKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiagnosticKind::TooLarge => {
+                write!(f, "larger than {} bytes", MAX_POINTER_SIZE)
+            }
+            DiagnosticKind::InvalidUtf8 => write!(f, "not valid UTF-8"),
+            DiagnosticKind::MissingFinalNewline => write!(f, "missing final newline"),
+            DiagnosticKind::CarriageReturn => write!(f, "CRLF line ending"),
+            DiagnosticKind::BlankLine => write!(f, "blank line"),
+            DiagnosticKind::LeadingWhitespace => write!(f, "leading whitespace"),
+            DiagnosticKind::TrailingWhitespace => write!(f, "trailing whitespace"),
+            DiagnosticKind::MalformedLine => write!(f, "expected `key value`"),
+            DiagnosticKind::UnknownKey(key) => write!(f, "unknown key {:?}", key),
+            DiagnosticKind::DuplicateKey(key) => write!(f, "duplicate key {:?}", key),
+            DiagnosticKind::VersionNotFirst => write!(f, "version must be the first line"),
+            DiagnosticKind::OutOfOrder { key, previous } => {
+                write!(f, "key {:?} must come before {:?}", key, previous)
+            }
+            DiagnosticKind::MissingKey(key) => write!(f, "missing {}", key),
+            DiagnosticKind::UnsupportedVersion(version) => {
+                write!(f, "unsupported version {:?}", version)
+            }
+            DiagnosticKind::InvalidOid(oid) => write!(f, "invalid oid {:?}", oid),
+            DiagnosticKind::UppercaseOid => write!(f, "oid must be lowercase hex"),
+            DiagnosticKind::InvalidSize(size) => write!(f, "invalid size {:?}", size),
+            DiagnosticKind::SizeLeadingZeros => write!(f, "size has leading zeros"),
+            DiagnosticKind::InvalidExtension(message) => write!(f, "{}", message),
+            DiagnosticKind::DuplicatePriority(priority) => {
+                write!(f, "duplicate extension priority {}", priority)
+            }
+        }
+    }
+}
+
+impl fmt::Display for PointerDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.kind),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+/// Check a blob against the canonical pointer format.
+///
+/// Returns every problem found, in line order; an empty list means the blob
+/// is a canonical pointer.
+pub(crate) fn validate(content: &[u8]) -> Vec<PointerDiagnostic> {
+    use DiagnosticKind::*;
+
+    let mut diagnostics = Vec::new();
+    let mut report =
+        |line: Option<usize>, kind| diagnostics.push(PointerDiagnostic::new(line, kind));
+
+    if content.len() > MAX_POINTER_SIZE {
+        report(None, TooLarge);
+        return diagnostics;
+    }
+    let text = match std::str::from_utf8(content) {
+        Ok(text) => text,
+        Err(_) => {
+            report(None, InvalidUtf8);
+            return diagnostics;
+        }
+    };
+
+    let mut lines: Vec<&str> = text.split('\n').collect();
+    if text.ends_with('\n') {
+        lines.pop();
+    } else {
+        report(Some(lines.len()), MissingFinalNewline);
+    }
+
+    let mut seen: Vec<String> = Vec::new();
+    let mut priorities: Vec<u8> = Vec::new();
+    let mut previous: Option<String> = None;
+
+    for (i, raw) in lines.iter().enumerate() {
+        let n = Some(i + 1);
+
+        let line = match raw.strip_suffix('\r') {
+            Some(line) => {
+                report(n, CarriageReturn);
+                line
+            }
+            None => raw,
+        };
+        if line.trim().is_empty() {
+            report(n, BlankLine);
+            continue;
+        }
+        if line.starts_with(char::is_whitespace) {
+            report(n, LeadingWhitespace);
+        }
+        if line.ends_with(char::is_whitespace) {
+            report(n, TrailingWhitespace);
+        }
+
+        let (key, value) = match line.trim().split_once(' ') {
+            Some((key, value)) => {
+                if value.starts_with(char::is_whitespace) {
+                    report(n, MalformedLine);
+                }
+                (key, value.trim_start())
+            }
+            None => {
+                report(n, MalformedLine);
+                continue;
+            }
+        };
+
+        if seen.iter().any(|k| k == key) {
+            report(n, DuplicateKey(key.to_string()));
+            continue;
+        }
+        seen.push(key.to_string());
+
+        if key == "version" {
+            if i != 0 {
+                report(n, VersionNotFirst);
+            }
+            if value != LFS_SPEC_V1 {
+                report(n, UnsupportedVersion(value.to_string()));
+            }
+            continue;
+        }
+
+        if let Some(prev) = previous.as_deref() {
+            if key < prev {
+                report(
+                    n,
+                    OutOfOrder {
+                        key: key.to_string(),
+                        previous: prev.to_string(),
+                    },
+                );
+            }
+        }
+        previous = Some(key.to_string());
+
+        match key {
+            "oid" => {
+                if let Some(kind) = check_oid(value) {
+                    report(n, kind);
+                }
+            }
+            "size" => {
+                if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                    report(n, InvalidSize(value.to_string()));
+                } else if value.len() > 1 && value.starts_with('0') {
+                    report(n, SizeLeadingZeros);
+                } else if value.parse::<u64>().is_err() {
+                    report(n, InvalidSize(value.to_string()));
+                }
+            }
+            _ if key.starts_with("ext-") => {
+                match PointerExtension::parse(key, value) {
+                    Err(e) => report(n, InvalidExtension(e.to_string())),
+                    Ok(ext) => {
+                        if priorities.contains(&ext.priority()) {
+                            report(n, DuplicatePriority(ext.priority()));
+                        }
+                        priorities.push(ext.priority());
+                        if let Some(kind) = check_oid(value) {
+                            report(n, kind);
+                        }
+                    }
+                }
+            }
+            _ => report(n, UnknownKey(key.to_string())),
+        }
+    }
+
+    for key in ["version", "oid", "size"] {
+        if !seen.iter().any(|k| k == key) {
+            report(None, MissingKey(key));
+        }
+    }
+
+    diagnostics
+}
+
+/// Check a `sha256:<hex>` value, allowing only lowercase hex.
+fn check_oid(value: &str) -> Option<DiagnosticKind> {
+    let hex = match value.strip_prefix("sha256:") {
+        Some(hex) if hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit()) => hex,
+        _ => return Some(DiagnosticKind::InvalidOid(value.to_string())),
+    };
+    if hex.bytes().any(|b| b.is_ascii_uppercase()) {
+        return Some(DiagnosticKind::UppercaseOid);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DiagnosticKind::*;
+
+    const OID: &str = "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393";
+
+    fn kinds(content: &str) -> Vec<(Option<usize>, DiagnosticKind)> {
+        validate(content.as_bytes())
+            .into_iter()
+            .map(|d| (d.line(), d.kind().clone()))
+            .collect()
+    }
+
+    fn canonical() -> String {
+        format!("version {}\noid sha256:{}\nsize 12345\n", LFS_SPEC_V1, OID)
+    }
+
+    #[test]
+    fn test_canonical_pointer() {
+        assert!(kinds(&canonical()).is_empty());
+
+        let with_ext = format!(
+            "version {}\next-0-foo sha256:{}\noid sha256:{}\nsize 0\n",
+            LFS_SPEC_V1, OID, OID
+        );
+        assert!(kinds(&with_ext).is_empty());
+    }
+
+    #[test]
+    fn test_whitespace_and_line_endings() {
+        assert_eq!(
+            kinds(canonical().trim_end()),
+            [(Some(3), MissingFinalNewline)]
+        );
+        assert_eq!(
+            kinds(&canonical().replace('\n', "\r\n")),
+            [
+                (Some(1), CarriageReturn),
+                (Some(2), CarriageReturn),
+                (Some(3), CarriageReturn)
+            ]
+        );
+        assert_eq!(
+            kinds(&canonical().replace("size 12345", "size 12345 ")),
+            [(Some(3), TrailingWhitespace)]
+        );
+        assert_eq!(
+            kinds(&canonical().replace("\nsize", "\n\nsize")),
+            [(Some(3), BlankLine)]
+        );
+        assert_eq!(
+            kinds(&canonical().replace("size 12345", "size  12345")),
+            [(Some(3), MalformedLine)]
+        );
+    }
+
+    #[test]
+    fn test_order_and_keys() {
+        let swapped = format!("version {}\nsize 12345\noid sha256:{}\n", LFS_SPEC_V1, OID);
+        assert_eq!(
+            kinds(&swapped),
+            [(
+                Some(3),
+                OutOfOrder {
+                    key: "oid".into(),
+                    previous: "size".into()
+                }
+            )]
+        );
+
+        let version_last = format!("oid sha256:{}\nsize 12345\nversion {}\n", OID, LFS_SPEC_V1);
+        assert_eq!(kinds(&version_last), [(Some(3), VersionNotFirst)]);
+
+        let extra = canonical().replace("size 12345\n", "size 12345\nsize 1\nzzz 1\n");
+        assert_eq!(
+            kinds(&extra),
+            [
+                (Some(4), DuplicateKey("size".into())),
+                (Some(5), UnknownKey("zzz".into()))
+            ]
+        );
+
+        // Pointer::parse requires strictly ascending priorities
+        let same_priority = format!(
+            "version {}\next-0-bar sha256:{}\next-0-foo sha256:{}\noid sha256:{}\nsize 0\n",
+            LFS_SPEC_V1, OID, OID, OID
+        );
+        assert_eq!(kinds(&same_priority), [(Some(3), DuplicatePriority(0))]);
+
+        let missing = format!("version {}\nsize 1\n", LFS_SPEC_V1);
+        assert_eq!(kinds(&missing), [(None, MissingKey("oid"))]);
+    }
+
+    #[test]
+    fn test_values() {
+        assert_eq!(
+            kinds(&canonical().replace("size 12345", "size 012345")),
+            [(Some(3), SizeLeadingZeros)]
+        );
+        assert_eq!(
+            kinds(&canonical().replace("size 12345", "size -1")),
+            [(Some(3), InvalidSize("-1".into()))]
+        );
+        assert_eq!(
+            kinds(&canonical().replace(OID, &OID.to_uppercase())),
+            [(Some(2), UppercaseOid)]
+        );
+        assert_eq!(
+            kinds(&canonical().replace(LFS_SPEC_V1, "https://hawser.github.com/spec/v1")),
+            [(
+                Some(1),
+                UnsupportedVersion("https://hawser.github.com/spec/v1".into())
+            )]
+        );
+
+        let diagnostics = validate(canonical().replace("sha256:", "md5:").as_bytes());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line(), Some(2));
+        assert!(diagnostics[0]
+            .to_string()
+            .starts_with("line 2: invalid oid"));
+    }
+}
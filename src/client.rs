@@ -1,42 +1,178 @@
 //! LFS HTTP client for upload/download operations.
 
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{self, Read, Write};
+use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 use url::Url;
 
-use crate::batch::{BatchRequest, BatchRequestObject, BatchResponse};
+use crate::batch::{
+    Action, BatchObject, BatchRequest, BatchRequestObject, BatchResponse, Operation,
+};
+use crate::credentials::{self, CredentialSource};
 use crate::oid::HashingWriter;
-use crate::{Error, Pointer, Result};
+use crate::throttle::{Throttle, Throttled};
+use crate::transport::{
+    decode_body, HttpMethod, HttpRequest, HttpResponse, HttpTransport, RedirectPolicy, RequestBody,
+    UreqTransport, ACCEPT_ENCODING,
+};
+use crate::{Error, Oid, Pointer, Result};
 
 /// LFS client for communicating with an LFS server.
 ///
 /// This type is cheaply cloneable - multiple clones share the same underlying
-/// HTTP agent and configuration.
+/// HTTP transport and configuration. Requests go through a [`UreqTransport`]
+/// unless another [`HttpTransport`] is set with
+/// [`with_transport`](Self::with_transport).
 #[derive(Clone)]
 pub struct LfsClient {
     inner: Arc<LfsClientInner>,
 }
 
+/// Default number of objects sent in a single batch API request.
+pub const DEFAULT_BATCH_SIZE: usize = 100;
+
+/// How close to expiry an action may be before it is re-requested rather
+/// than used for a transfer.
+pub(crate) const ACTION_EXPIRY_MARGIN: Duration = Duration::from_secs(5);
+
+/// Outcome of a batch operation for a single object.
+#[derive(Debug)]
+pub enum ObjectStatus<T> {
+    /// The object was transferred. Downloads carry the verified content.
+    Done(T),
+    /// The server already has the object, so nothing was uploaded.
+    AlreadyPresent,
+    /// The server reported an error for this object, or its transfer failed.
+    Failed(Error),
+}
+
+impl<T> ObjectStatus<T> {
+    /// Convert into a `Result`, treating `AlreadyPresent` as `None`.
+    pub fn into_result(self) -> Result<Option<T>> {
+        match self {
+            ObjectStatus::Done(value) => Ok(Some(value)),
+            ObjectStatus::AlreadyPresent => Ok(None),
+            ObjectStatus::Failed(err) => Err(err),
+        }
+    }
+}
+
 /// Authentication method for LFS requests.
 #[derive(Clone)]
-enum Auth {
+pub(crate) enum Auth {
     /// Bearer token (OAuth/PAT)
     Bearer(String),
     /// Basic auth (username, password)
     Basic(String, String),
 }
 
-struct LfsClientInner {
+/// Credentials and where they came from.
+type FoundCredentials = (Auth, CredentialSource);
+
+impl std::fmt::Debug for Auth {
+    // Keeps secrets out of logs
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Auth::Bearer(_) => f.write_str("Bearer(..)"),
+            Auth::Basic(username, _) => write!(f, "Basic({:?}, ..)", username),
+        }
+    }
+}
+
+impl Auth {
+    /// Value for the `Authorization` header.
+    pub(crate) fn header_value(&self) -> String {
+        match self {
+            Auth::Bearer(token) => format!("Bearer {}", token),
+            Auth::Basic(username, password) => {
+                let credentials = format!("{}:{}", username, password);
+                let encoded = base64::Engine::encode(
+                    &base64::engine::general_purpose::STANDARD,
+                    credentials.as_bytes(),
+                );
+                format!("Basic {}", encoded)
+            }
+        }
+    }
+}
+
+/// Authentication an LFS endpoint expects, from `lfs.<url>.access`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessMode {
+    /// The endpoint needs no credentials; none are sent to it.
+    None,
+    /// The endpoint expects HTTP basic authentication.
+    Basic,
+    /// The endpoint expects Kerberos/SPNEGO negotiation, which this client
+    /// can't perform; configured credentials are sent as usual.
+    Negotiate,
+}
+
+impl AccessMode {
+    /// Parse a git-lfs access mode name, case-insensitively. The legacy
+    /// `private` mode means basic authentication.
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode.to_ascii_lowercase().as_str() {
+            "none" => Some(AccessMode::None),
+            "basic" | "private" => Some(AccessMode::Basic),
+            "negotiate" => Some(AccessMode::Negotiate),
+            _ => None,
+        }
+    }
+}
+
+/// Settings for one endpoint, resolved by
+/// [`LfsClientBuilder`](crate::LfsClientBuilder) from explicit settings and
+/// `http.<url>.*` / `lfs.<url>.*` config.
+#[derive(Clone, Default)]
+pub(crate) struct EndpointConfig {
+    /// Headers sent with batch API requests (`http.extraHeader`).
+    pub(crate) extra_headers: Vec<(String, String)>,
+    /// Authentication the endpoint expects, if configured.
+    pub(crate) access: Option<AccessMode>,
+    /// Transport with the endpoint's own proxy and TLS settings, if they
+    /// differ from the main endpoint's.
+    pub(crate) transport: Option<Arc<dyn HttpTransport>>,
+}
+
+#[derive(Clone)]
+pub(crate) struct LfsClientInner {
     /// The LFS API endpoint URL.
-    lfs_url: Url,
+    pub(crate) lfs_url: Url,
+    /// Endpoint for uploads, if different from `lfs_url`.
+    pub(crate) push_url: Option<Url>,
+    /// Endpoints tried in order when a download from `lfs_url` fails.
+    pub(crate) fallback_urls: Vec<Url>,
     /// Optional authentication.
-    auth: Option<Auth>,
-    /// HTTP agent for making requests.
-    agent: ureq::Agent,
+    pub(crate) auth: Option<Auth>,
+    /// HTTP transport for making requests, shared by clones.
+    pub(crate) transport: Arc<dyn HttpTransport>,
     /// Optional ref name for batch requests (e.g., "refs/heads/main").
-    ref_name: Option<String>,
+    pub(crate) ref_name: Option<String>,
+    /// Maximum number of objects per batch request.
+    pub(crate) batch_size: usize,
+    /// Settings for the endpoints configured through the builder, keyed by
+    /// endpoint. Other endpoints get none.
+    pub(crate) endpoints: HashMap<Url, EndpointConfig>,
+    /// How redirects are followed.
+    pub(crate) redirect_policy: RedirectPolicy,
+    /// Whether to look for credentials outside the client's configuration.
+    pub(crate) ambient_credentials: bool,
+    /// Whether downloads offer compressed encodings.
+    pub(crate) transfer_compression: bool,
+    /// Limit on transfer bandwidth, shared by clones.
+    pub(crate) throttle: Option<Arc<Throttle>>,
+    /// Ambient credentials found for each endpoint, shared by clones.
+    found_credentials: Arc<Mutex<HashMap<Url, Option<FoundCredentials>>>>,
+    /// Endpoints and OIDs this process has seen present, shared by clones.
+    known_objects: Arc<Mutex<HashSet<(Url, Oid)>>>,
+    /// Endpoints whose last batch request failed to get through, shared by
+    /// clones.
+    unhealthy_endpoints: Arc<Mutex<HashSet<Url>>>,
 }
 
 impl LfsClient {
@@ -49,9 +185,20 @@ impl LfsClient {
         Ok(LfsClient {
             inner: Arc::new(LfsClientInner {
                 lfs_url,
+                push_url: None,
+                fallback_urls: Vec::new(),
                 auth: None,
-                agent: ureq::Agent::new(),
+                transport: Arc::new(UreqTransport::new()),
                 ref_name: None,
+                batch_size: DEFAULT_BATCH_SIZE,
+                endpoints: HashMap::new(),
+                redirect_policy: RedirectPolicy::default(),
+                ambient_credentials: false,
+                transfer_compression: true,
+                throttle: None,
+                found_credentials: Arc::default(),
+                known_objects: Arc::default(),
+                unhealthy_endpoints: Arc::default(),
             }),
         })
     }
@@ -61,9 +208,20 @@ impl LfsClient {
         LfsClient {
             inner: Arc::new(LfsClientInner {
                 lfs_url,
+                push_url: None,
+                fallback_urls: Vec::new(),
                 auth: None,
-                agent: ureq::Agent::new(),
+                transport: Arc::new(UreqTransport::new()),
                 ref_name: None,
+                batch_size: DEFAULT_BATCH_SIZE,
+                endpoints: HashMap::new(),
+                redirect_policy: RedirectPolicy::default(),
+                ambient_credentials: false,
+                transfer_compression: true,
+                throttle: None,
+                found_credentials: Arc::default(),
+                known_objects: Arc::default(),
+                unhealthy_endpoints: Arc::default(),
             }),
         }
     }
@@ -81,100 +239,90 @@ impl LfsClient {
     /// 1. `remote.lfsdefault` config if set
     /// 2. The only remote if there's exactly one
     /// 3. "origin" as fallback
+    ///
+    /// Uploads use a separate push endpoint, found the same way except that
+    /// `lfs.pushurl` comes before `lfs.url`, `remote.<name>.lfspushurl`
+    /// before `remote.<name>.lfsurl`, and `remote.<name>.pushurl` before
+    /// the remote URL. The push remote is `remote.lfspushdefault`, then
+    /// `remote.pushDefault`, then the remote used for downloads.
+    ///
+    /// HTTP settings such as `http.proxy` and `http.sslCAInfo` are applied
+    /// too; see [`LfsClientBuilder::from_repo`](crate::LfsClientBuilder::from_repo).
     #[cfg(feature = "git2-integration")]
     pub fn from_repo(repo: &git2::Repository) -> Result<Self> {
-        let config = repo.config().map_err(|e| Error::Git(e.to_string()))?;
-
-        // Check for explicit lfs.url in git config first (highest priority)
-        if let Ok(url) = config.get_string("lfs.url") {
-            return Self::from_lfs_url(&url);
-        }
+        crate::LfsClientBuilder::from_repo(repo)?.build()
+    }
 
-        // Check .lfsconfig file in repo root
-        if let Some(workdir) = repo.workdir() {
-            let lfsconfig_path = workdir.join(".lfsconfig");
-            if lfsconfig_path.exists() {
-                if let Ok(lfsconfig) = git2::Config::open(&lfsconfig_path) {
-                    if let Ok(url) = lfsconfig.get_string("lfs.url") {
-                        return Self::from_lfs_url(&url);
-                    }
-                }
-            }
-        }
+    /// Send uploads to a different LFS endpoint, e.g. a primary server
+    /// when downloads come from a mirror.
+    pub fn with_push_url(self, push_url: Url) -> Self {
+        self.configure(|inner| inner.push_url = Some(push_url))
+    }
 
-        // Determine which remote to use
-        let remote_name = config
-            .get_string("remote.lfsdefault")
-            .ok()
-            .or_else(|| {
-                // If exactly one remote, use it
-                let remotes = repo.remotes().ok()?;
-                if remotes.len() == 1 {
-                    remotes.get(0).map(|s| s.to_string())
-                } else {
-                    None
-                }
-            })
-            .unwrap_or_else(|| "origin".to_string());
-
-        // Check for remote-specific lfsurl
-        let remote_lfsurl_key = format!("remote.{}.lfsurl", remote_name);
-        if let Ok(url) = config.get_string(&remote_lfsurl_key) {
-            return Self::from_lfs_url(&url);
-        }
-
-        // Fall back to deriving from remote URL
-        let remote = repo
-            .find_remote(&remote_name)
-            .map_err(|e| Error::Git(e.to_string()))?;
-        let remote_url = remote
-            .url()
-            .ok_or_else(|| Error::Git("remote has no URL".into()))?;
-
-        LfsClient::new(remote_url)
-    }
-
-    /// Create a client from an LFS URL string, normalizing the path.
-    fn from_lfs_url(url: &str) -> Result<Self> {
-        let lfs_url = Url::parse(url).map_err(|e| Error::InvalidUrl(e.to_string()))?;
-        // Ensure URL ends with /info/lfs/ for batch API
-        let lfs_url = if lfs_url.path().ends_with("/info/lfs/") {
-            lfs_url
-        } else if lfs_url.path().ends_with("/info/lfs") {
-            let mut url = lfs_url;
-            url.set_path(&format!("{}/", url.path()));
-            url
-        } else {
-            let mut url = lfs_url;
-            let path = url.path().trim_end_matches('/');
-            url.set_path(&format!("{}/info/lfs/", path));
-            url
-        };
-        Ok(LfsClient::with_url(lfs_url))
+    /// Add an endpoint to download from when the main one fails.
+    ///
+    /// Fallbacks, such as a read-only mirror or an archive server, are tried
+    /// in the order added when an object is missing or the previous endpoint
+    /// is unreachable or overloaded. Endpoints that recently failed to
+    /// respond are tried after the others. Credentials set with
+    /// [`with_auth`](Self::with_auth) or [`with_token`](Self::with_token)
+    /// are not sent to fallbacks on another origin.
+    pub fn with_fallback_url(self, url: Url) -> Self {
+        self.configure(|inner| inner.fallback_urls.push(url))
     }
 
     /// Set basic authentication credentials.
     pub fn with_auth(self, username: &str, password: &str) -> Self {
-        LfsClient {
-            inner: Arc::new(LfsClientInner {
-                lfs_url: self.inner.lfs_url.clone(),
-                auth: Some(Auth::Basic(username.to_string(), password.to_string())),
-                agent: ureq::Agent::new(),
-                ref_name: self.inner.ref_name.clone(),
-            }),
-        }
+        self.configure(|inner| {
+            inner.auth = Some(Auth::Basic(username.to_string(), password.to_string()))
+        })
     }
 
     /// Set authentication from a bearer token (OAuth/PAT).
     pub fn with_token(self, token: &str) -> Self {
-        LfsClient {
-            inner: Arc::new(LfsClientInner {
-                lfs_url: self.inner.lfs_url.clone(),
-                auth: Some(Auth::Bearer(token.to_string())),
-                agent: ureq::Agent::new(),
-                ref_name: self.inner.ref_name.clone(),
-            }),
-        }
+        self.configure(|inner| inner.auth = Some(Auth::Bearer(token.to_string())))
+    }
+
+    /// Look for credentials outside the client's configuration when none
+    /// are set with [`with_auth`](Self::with_auth) or
+    /// [`with_token`](Self::with_token).
+    ///
+    /// Credentials come from the environment, then the netrc file, then an
+    /// askpass program once the server asks for them; see
+    /// [`CredentialSource`] for the details. Answers are remembered per
+    /// endpoint for the client's lifetime. Off by default, except for
+    /// clients created with [`from_repo`](Self::from_repo).
+    pub fn with_ambient_credentials(self, enabled: bool) -> Self {
+        self.configure(|inner| inner.ambient_credentials = enabled)
+    }
+
+    /// Set how redirects are followed; see [`RedirectPolicy`].
+    pub fn with_redirect_policy(self, policy: RedirectPolicy) -> Self {
+        self.configure(|inner| inner.redirect_policy = policy)
+    }
+
+    /// Limit uploads and downloads to `bytes_per_second`, combined across
+    /// all transfers made by this client and its clones. Zero removes the
+    /// limit.
+    pub fn with_bandwidth_limit(self, bytes_per_second: u64) -> Self {
+        let throttle = (bytes_per_second > 0).then(|| Arc::new(Throttle::new(bytes_per_second)));
+        self.configure(|inner| inner.throttle = throttle)
+    }
+
+    /// Get the bandwidth limit in bytes per second, if one is set.
+    pub fn bandwidth_limit(&self) -> Option<u64> {
+        self.inner.throttle.as_ref().map(|t| t.bytes_per_second())
+    }
+
+    /// Offer compressed encodings (`Accept-Encoding: gzip, zstd`) when
+    /// downloading objects. On by default.
+    ///
+    /// Compressed downloads are decoded before their content is verified,
+    /// so callers always get the object itself. Turn this off for storage
+    /// backends that mishandle compression; downloads then ask for
+    /// `identity`.
+    pub fn with_transfer_compression(self, enabled: bool) -> Self {
+        self.configure(|inner| inner.transfer_compression = enabled)
     }
 
     /// Set the ref name for batch requests.
@@ -182,139 +330,284 @@ impl LfsClient {
     /// The ref name is sent with batch requests to help servers with
     /// access control and locking decisions (e.g., "refs/heads/main").
     pub fn with_ref(self, ref_name: &str) -> Self {
+        self.configure(|inner| inner.ref_name = Some(ref_name.to_string()))
+    }
+
+    /// Send requests through `transport` instead of the default
+    /// [`UreqTransport`].
+    ///
+    /// Pass an `Arc` to share one transport between several clients. The
+    /// transport is used for every endpoint.
+    pub fn with_transport(self, transport: impl HttpTransport + 'static) -> Self {
+        let transport: Arc<dyn HttpTransport> = Arc::new(transport);
+        self.configure(|inner| {
+            inner.transport = transport;
+            for config in inner.endpoints.values_mut() {
+                config.transport = None;
+            }
+        })
+    }
+
+    /// Set the maximum number of objects sent in one batch request.
+    ///
+    /// Multi-object operations are split into chunks of this size. Defaults
+    /// to [`DEFAULT_BATCH_SIZE`]; a size of zero is treated as one.
+    pub fn with_batch_size(self, batch_size: usize) -> Self {
+        self.configure(|inner| inner.batch_size = batch_size.max(1))
+    }
+
+    /// Check whether the [push endpoint](Self::push_url) is known to have
+    /// `oid`.
+    ///
+    /// Objects become known when this client (or a clone of it) uploads
+    /// them or finds them already present. Objects seen by `check_exists`
+    /// are only known to be on the download endpoint.
+    pub fn is_known_present(&self, oid: &Oid) -> bool {
+        self.is_known_at(self.push_url(), oid)
+    }
+
+    fn is_known_at(&self, endpoint: &Url, oid: &Oid) -> bool {
+        self.inner
+            .known_objects
+            .lock()
+            .unwrap()
+            .contains(&(endpoint.clone(), oid.clone()))
+    }
+
+    fn mark_present(&self, endpoint: &Url, oid: &Oid) {
+        self.inner
+            .known_objects
+            .lock()
+            .unwrap()
+            .insert((endpoint.clone(), oid.clone()));
+    }
+
+    /// Wrap a transfer's reader or writer in the bandwidth limit, if any.
+    fn throttled<T>(&self, inner: T) -> Throttled<T> {
+        Throttled::new(inner, self.inner.throttle.clone())
+    }
+
+    /// Body of a download response for `pointer`, throttled as it arrives
+    /// and then decoded. Reading stops one byte past the pointer's size, so
+    /// a body that decodes to more than that fails the size check instead
+    /// of filling memory or disk.
+    fn download_body(
+        &self,
+        pointer: &Pointer,
+        response: HttpResponse,
+    ) -> Result<Box<dyn Read + Send>> {
+        let encoding = response.header("Content-Encoding").map(str::to_string);
+        let body = decode_body(encoding.as_deref(), Box::new(self.throttled(response.body)))?;
+        Ok(Box::new(body.take(pointer.size().saturating_add(1))))
+    }
+
+    /// Copy the configuration, apply `f`, and return a client using it.
+    pub(crate) fn configure(self, f: impl FnOnce(&mut LfsClientInner)) -> Self {
+        let mut inner = (*self.inner).clone();
+        f(&mut inner);
         LfsClient {
-            inner: Arc::new(LfsClientInner {
-                lfs_url: self.inner.lfs_url.clone(),
-                auth: self.inner.auth.clone(),
-                agent: ureq::Agent::new(),
-                ref_name: Some(ref_name.to_string()),
-            }),
+            inner: Arc::new(inner),
         }
     }
 
-    /// Get the LFS endpoint URL.
+    /// Get the LFS endpoint URL, used for downloads and, unless a push URL
+    /// is set, uploads.
     pub fn lfs_url(&self) -> &Url {
         &self.inner.lfs_url
     }
 
-    /// Send a batch request to the LFS server.
-    pub fn batch(&self, request: &BatchRequest) -> Result<BatchResponse> {
-        let url = self.inner.lfs_url.join("objects/batch")?;
-
-        let mut req = self
-            .inner
-            .agent
-            .post(url.as_str())
-            .set("Accept", "application/vnd.git-lfs+json")
-            .set("Content-Type", "application/vnd.git-lfs+json")
-            .set("User-Agent", "git2-lfs/0.1");
-
-        if let Some(auth) = &self.inner.auth {
-            req = match auth {
-                Auth::Bearer(token) => req.set("Authorization", &format!("Bearer {}", token)),
-                Auth::Basic(username, password) => {
-                    let credentials = format!("{}:{}", username, password);
-                    let encoded = base64::Engine::encode(
-                        &base64::engine::general_purpose::STANDARD,
-                        credentials.as_bytes(),
-                    );
-                    req.set("Authorization", &format!("Basic {}", encoded))
-                }
-            };
-        }
+    /// Get the LFS endpoint URL used for uploads.
+    pub fn push_url(&self) -> &Url {
+        self.inner.push_url.as_ref().unwrap_or(&self.inner.lfs_url)
+    }
 
-        let response = req.send_json(request)?;
-        let batch_response: BatchResponse = response.into_json()?;
-        Ok(batch_response)
+    /// Get the fallback endpoints for downloads, in the order added.
+    pub fn fallback_urls(&self) -> &[Url] {
+        &self.inner.fallback_urls
     }
 
-    /// Upload content to the LFS server.
+    /// Check whether an endpoint is considered healthy.
     ///
-    /// Returns the pointer for the uploaded content.
-    pub fn upload(&self, pointer: &Pointer, content: &[u8]) -> Result<()> {
-        // Verify content matches pointer
-        let computed = Pointer::from_content(content);
-        if computed.oid() != pointer.oid() || computed.size() != pointer.size() {
-            return Err(Error::InvalidPointer(
-                "content does not match pointer".into(),
-            ));
-        }
+    /// An endpoint becomes unhealthy when a batch request to it fails with
+    /// a retryable error, and healthy again once one succeeds. The state is
+    /// shared by clones and kept for the client's lifetime.
+    pub fn is_endpoint_healthy(&self, endpoint: &Url) -> bool {
+        !self
+            .inner
+            .unhealthy_endpoints
+            .lock()
+            .unwrap()
+            .contains(endpoint)
+    }
 
-        // Request upload URL
-        let mut batch_req = BatchRequest::upload(vec![BatchRequestObject::new(
-            &pointer.oid().to_hex(),
-            pointer.size(),
-        )]);
-        if let Some(ref_name) = &self.inner.ref_name {
-            batch_req = batch_req.with_ref(ref_name);
+    fn set_endpoint_health(&self, endpoint: &Url, healthy: bool) {
+        let mut unhealthy = self.inner.unhealthy_endpoints.lock().unwrap();
+        if healthy {
+            unhealthy.remove(endpoint);
+        } else {
+            unhealthy.insert(endpoint.clone());
         }
+    }
 
-        let batch_resp = self.batch(&batch_req)?;
+    /// Download endpoints in the order to try them: the main endpoint, then
+    /// the fallbacks, with unhealthy endpoints moved to the end.
+    fn download_endpoints(&self) -> Vec<Url> {
+        let mut endpoints: Vec<_> = std::iter::once(&self.inner.lfs_url)
+            .chain(&self.inner.fallback_urls)
+            .cloned()
+            .collect();
+        endpoints.sort_by_key(|endpoint| !self.is_endpoint_healthy(endpoint));
+        endpoints
+    }
 
-        if batch_resp.objects.is_empty() {
-            return Err(Error::ServerError {
-                code: 500,
-                message: "no objects in batch response".into(),
-            });
-        }
+    /// Get the maximum number of objects sent in one batch request.
+    pub fn batch_size(&self) -> usize {
+        self.inner.batch_size
+    }
 
-        let obj = &batch_resp.objects[0];
+    /// Get the authentication the endpoint expects, if configured.
+    pub fn access(&self) -> Option<AccessMode> {
+        self.access_for(self.lfs_url())
+    }
 
-        // Check for errors
-        if let Some(err) = &obj.error {
-            return Err(Error::ServerError {
-                code: err.code,
-                message: err.message.clone(),
-            });
-        }
+    fn access_for(&self, endpoint: &Url) -> Option<AccessMode> {
+        self.inner
+            .endpoints
+            .get(endpoint)
+            .and_then(|config| config.access)
+    }
 
-        // Get upload action
-        let upload_action = obj.upload_action().ok_or_else(|| {
-            // No upload action means object already exists
-            Error::Http("object already exists on server".into())
-        });
+    /// Get how redirects are followed.
+    pub fn redirect_policy(&self) -> RedirectPolicy {
+        self.inner.redirect_policy
+    }
 
-        // If no upload action, object already exists - that's OK
-        let action = match upload_action {
-            Ok(a) => a,
-            Err(_) => return Ok(()), // Already exists
+    /// Send a batch request to the LFS server.
+    ///
+    /// Upload requests go to the [push URL](Self::push_url). Fails with
+    /// [`Error::UnsupportedTransfer`] or [`Error::UnsupportedHashAlgo`] if
+    /// the server picks a transfer adapter or hash algorithm the request
+    /// didn't offer.
+    pub fn batch(&self, request: &BatchRequest) -> Result<BatchResponse> {
+        let endpoint = match request.operation {
+            Operation::Download => self.lfs_url(),
+            Operation::Upload => self.push_url(),
         };
+        self.batch_at(endpoint, request)
+    }
 
-        // Upload the content
-        let mut req = self.inner.agent.put(&action.href);
-
-        // Add headers from action
-        for (key, value) in &action.header {
-            req = req.set(key, value);
+    /// Send a batch request to a specific endpoint, recording whether the
+    /// endpoint is reachable.
+    fn batch_at(&self, endpoint: &Url, request: &BatchRequest) -> Result<BatchResponse> {
+        let result = self.send_batch(endpoint, request);
+        match &result {
+            Ok(_) => self.set_endpoint_health(endpoint, true),
+            Err(e) if e.is_retryable() => self.set_endpoint_health(endpoint, false),
+            Err(_) => {}
         }
+        result
+    }
 
-        req = req.set("Content-Type", "application/octet-stream");
-        req = req.set("Content-Length", &content.len().to_string());
+    fn send_batch(&self, endpoint: &Url, request: &BatchRequest) -> Result<BatchResponse> {
+        let url = endpoint.join("objects/batch")?;
 
-        req.send_bytes(content)?;
+        let body = serde_json::to_vec(request)?;
 
-        // Verify if required
-        if let Some(verify_action) = obj.verify_action() {
-            let verify_body = serde_json::json!({
-                "oid": pointer.oid().to_hex(),
-                "size": pointer.size()
-            });
+        let send = |auth: Option<&Auth>| {
+            let mut req = HttpRequest::new(HttpMethod::Post, url.as_str())
+                .with_header("Accept", "application/vnd.git-lfs+json")
+                .with_header("Content-Type", "application/vnd.git-lfs+json")
+                .with_header("User-Agent", "git2-lfs/0.1")
+                .with_body(RequestBody::Bytes(&body));
 
-            let mut req = self.inner.agent.post(&verify_action.href);
-            for (key, value) in &verify_action.header {
-                req = req.set(key, value);
+            let extra_headers = self.inner.endpoints.get(endpoint).map(|c| &c.extra_headers);
+            for (name, value) in extra_headers.into_iter().flatten() {
+                req = req.with_header(name, value);
+            }
+            if let Some(auth) = auth {
+                req = req.with_header("Authorization", &auth.header_value());
+            }
+            self.send(endpoint, req)
+        };
+
+        let credentials = self.credentials_for(endpoint);
+        let mut source = credentials.as_ref().map(|(_, source)| *source);
+        let mut result = send(credentials.as_ref().map(|(auth, _)| auth));
+
+        // Without other credentials, ask for some once the server wants them
+        let unauthorized = matches!(&result, Err(Error::AuthRequired { code: 401, .. }));
+        if unauthorized && credentials.is_none() && self.inner.ambient_credentials {
+            match credentials::askpass(endpoint) {
+                Ok(Some(auth)) => {
+                    source = Some(CredentialSource::Askpass);
+                    result = send(Some(&auth));
+                    if result.is_ok() {
+                        self.inner
+                            .found_credentials
+                            .lock()
+                            .unwrap()
+                            .insert(endpoint.clone(), Some((auth, CredentialSource::Askpass)));
+                    }
+                }
+                Ok(None) => {}
+                // The server's answer matters more than why nobody could
+                // supply credentials for it
+                Err(e) => result = result.map_err(|err| err.caused_by(e)),
             }
-            req = req.set("Content-Type", "application/vnd.git-lfs+json");
-            req.send_json(&verify_body)?;
         }
 
-        Ok(())
+        let response = result.map_err(|e| e.with_credentials(source))?;
+        let batch_response: BatchResponse = serde_json::from_reader(response.body)?;
+        request.check_response(&batch_response)?;
+        Ok(batch_response)
     }
 
-    /// Download content from the LFS server.
-    pub fn download(&self, pointer: &Pointer) -> Result<Vec<u8>> {
-        // Request download URL
-        let mut batch_req = BatchRequest::download(vec![BatchRequestObject::new(
+    /// Credentials to send to `endpoint`, and where they came from.
+    ///
+    /// Explicit and environment credentials are meant for the client's own
+    /// servers, so they are only sent to the origins of the download and
+    /// push endpoints. Fallbacks elsewhere only get credentials found for
+    /// their own host.
+    fn credentials_for(&self, endpoint: &Url) -> Option<FoundCredentials> {
+        if self.access_for(endpoint) == Some(AccessMode::None) {
+            return None;
+        }
+        let own = [self.lfs_url(), self.push_url()]
+            .iter()
+            .any(|url| url.origin() == endpoint.origin());
+        if own {
+            if let Some(auth) = &self.inner.auth {
+                return Some((auth.clone(), CredentialSource::Explicit));
+            }
+        }
+        if !self.inner.ambient_credentials {
+            return None;
+        }
+        self.inner
+            .found_credentials
+            .lock()
+            .unwrap()
+            .entry(endpoint.clone())
+            .or_insert_with(|| match own {
+                true => credentials::ambient(endpoint),
+                false => credentials::netrc(endpoint),
+            })
+            .clone()
+    }
+
+    /// Upload content to the LFS server.
+    ///
+    /// Does nothing if the object is already known to be on the server.
+    pub fn upload(&self, pointer: &Pointer, content: &[u8]) -> Result<()> {
+        // Verify content matches pointer
+        Error::check_content(pointer, &Oid::from_content(content), content.len() as u64)?;
+
+        if self.is_known_present(pointer.oid()) {
+            return Ok(());
+        }
+
+        // Request upload URL
+        let mut batch_req = BatchRequest::upload(vec![BatchRequestObject::new(
             &pointer.oid().to_hex(),
             pointer.size(),
         )]);
@@ -325,44 +618,45 @@ impl LfsClient {
         let batch_resp = self.batch(&batch_req)?;
 
         if batch_resp.objects.is_empty() {
-            return Err(Error::NotFound(pointer.oid().to_hex()));
+            return Err(Error::Protocol("no objects in batch response".into()));
         }
 
         let obj = &batch_resp.objects[0];
 
         // Check for errors
         if let Some(err) = &obj.error {
-            return Err(Error::ServerError {
-                code: err.code,
-                message: err.message.clone(),
-            });
+            return Err(Error::object_error(&obj.oid, err.code, &err.message));
         }
 
-        // Get download action
-        let action = obj
-            .download_action()
-            .ok_or_else(|| Error::NotFound(pointer.oid().to_hex()))?;
+        // Get upload action (no action means already exists)
+        let action = match obj.upload_action() {
+            Some(a) => a,
+            None => {
+                self.mark_present(self.push_url(), pointer.oid());
+                return Ok(()); // Already exists
+            }
+        };
 
-        // Download the content
-        let mut req = self.inner.agent.get(&action.href);
+        // Upload the content, verifying it if required
+        self.send_object(content, obj, action)
+            .map_err(|e| e.with_oid(pointer.oid()))?;
 
-        // Add headers from action
-        for (key, value) in &action.header {
-            req = req.set(key, value);
-        }
+        self.mark_present(self.push_url(), pointer.oid());
+        Ok(())
+    }
 
-        let response = req.call()?;
+    /// Download content from the LFS server.
+    ///
+    /// Fallback endpoints are tried in turn if the object can't be fetched
+    /// from the main one; see [`with_fallback_url`](Self::with_fallback_url).
+    pub fn download(&self, pointer: &Pointer) -> Result<Vec<u8>> {
+        let response = self.download_response(pointer)?;
 
         let mut content = Vec::with_capacity(pointer.size() as usize);
-        response.into_reader().read_to_end(&mut content)?;
+        self.download_body(pointer, response)?.read_to_end(&mut content)?;
 
         // Verify content
-        let computed = Pointer::from_content(&content);
-        if computed.oid() != pointer.oid() {
-            return Err(Error::InvalidPointer(
-                "downloaded content hash mismatch".into(),
-            ));
-        }
+        Error::check_content(pointer, &Oid::from_content(&content), content.len() as u64)?;
 
         Ok(content)
     }
@@ -371,67 +665,26 @@ impl LfsClient {
     ///
     /// This is more memory-efficient than `download()` for large files.
     /// Streams directly to a temp file while hashing, then renames atomically.
+    /// Uses fallback endpoints like `download()`.
     pub fn download_to_file<P: AsRef<Path>>(&self, pointer: &Pointer, dest: P) -> Result<()> {
         let dest = dest.as_ref();
-
-        // Request download URL
-        let mut batch_req = BatchRequest::download(vec![BatchRequestObject::new(
-            &pointer.oid().to_hex(),
-            pointer.size(),
-        )]);
-        if let Some(ref_name) = &self.inner.ref_name {
-            batch_req = batch_req.with_ref(ref_name);
-        }
-
-        let batch_resp = self.batch(&batch_req)?;
-
-        if batch_resp.objects.is_empty() {
-            return Err(Error::NotFound(pointer.oid().to_hex()));
-        }
-
-        let obj = &batch_resp.objects[0];
-
-        if let Some(err) = &obj.error {
-            return Err(Error::ServerError {
-                code: err.code,
-                message: err.message.clone(),
-            });
-        }
-
-        let action = obj
-            .download_action()
-            .ok_or_else(|| Error::NotFound(pointer.oid().to_hex()))?;
-
-        // Download the content
-        let mut req = self.inner.agent.get(&action.href);
-        for (key, value) in &action.header {
-            req = req.set(key, value);
-        }
-
-        let response = req.call()?;
+        let response = self.download_response(pointer)?;
+        let mut body = self.download_body(pointer, response)?;
 
         // Stream to temp file while hashing
         let temp_path = dest.with_extension("tmp");
         let temp_file = File::create(&temp_path).map_err(Error::Io)?;
         let mut hashing_writer = HashingWriter::new(temp_file);
 
-        io::copy(&mut response.into_reader(), &mut hashing_writer).map_err(Error::Io)?;
+        io::copy(&mut body, &mut hashing_writer).map_err(Error::Io)?;
 
         let (computed_oid, size, file) = hashing_writer.finish();
         drop(file); // Close before rename
 
         // Verify hash and size
-        if &computed_oid != pointer.oid() {
-            let _ = std::fs::remove_file(&temp_path);
-            return Err(Error::InvalidPointer(
-                "downloaded content hash mismatch".into(),
-            ));
-        }
-        if size != pointer.size() {
+        if let Err(e) = Error::check_content(pointer, &computed_oid, size) {
             let _ = std::fs::remove_file(&temp_path);
-            return Err(Error::InvalidPointer(
-                "downloaded content size mismatch".into(),
-            ));
+            return Err(e);
         }
 
         // Atomic rename
@@ -442,12 +695,45 @@ impl LfsClient {
 
     /// Download content to a writer, streaming and verifying hash.
     ///
+    /// Uses fallback endpoints like `download()`, but only until the
+    /// transfer starts: a failure after bytes reach the writer is returned.
     /// Returns the number of bytes written.
     pub fn download_to_writer<W: Write>(
         &self,
         pointer: &Pointer,
         writer: W,
     ) -> Result<u64> {
+        let response = self.download_response(pointer)?;
+        let mut body = self.download_body(pointer, response)?;
+
+        // Stream to writer while hashing
+        let mut hashing_writer = HashingWriter::new(writer);
+        io::copy(&mut body, &mut hashing_writer).map_err(Error::Io)?;
+
+        let (computed_oid, size, _) = hashing_writer.finish();
+
+        // Verify hash and size
+        Error::check_content(pointer, &computed_oid, size)?;
+
+        Ok(size)
+    }
+
+    /// Start downloading `pointer`'s content, trying each download endpoint
+    /// until one returns the object.
+    fn download_response(&self, pointer: &Pointer) -> Result<HttpResponse> {
+        let endpoints = self.download_endpoints();
+        let mut result = Err(missing_from_response(pointer));
+        for (i, endpoint) in endpoints.iter().enumerate() {
+            result = self.download_response_at(endpoint, pointer);
+            match &result {
+                Err(e) if i + 1 < endpoints.len() && falls_back(e) => continue,
+                _ => break,
+            }
+        }
+        result
+    }
+
+    fn download_response_at(&self, endpoint: &Url, pointer: &Pointer) -> Result<HttpResponse> {
         // Request download URL
         let mut batch_req = BatchRequest::download(vec![BatchRequestObject::new(
             &pointer.oid().to_hex(),
@@ -457,51 +743,33 @@ impl LfsClient {
             batch_req = batch_req.with_ref(ref_name);
         }
 
-        let batch_resp = self.batch(&batch_req)?;
+        let batch_resp = self.batch_at(endpoint, &batch_req)?;
 
         if batch_resp.objects.is_empty() {
-            return Err(Error::NotFound(pointer.oid().to_hex()));
+            return Err(Error::NotFound {
+                oid: Some(pointer.oid().clone()),
+                url: None,
+            });
         }
 
         let obj = &batch_resp.objects[0];
 
+        // Check for errors
         if let Some(err) = &obj.error {
-            return Err(Error::ServerError {
-                code: err.code,
-                message: err.message.clone(),
-            });
+            return Err(Error::object_error(&obj.oid, err.code, &err.message));
         }
 
+        // Get download action
         let action = obj
             .download_action()
-            .ok_or_else(|| Error::NotFound(pointer.oid().to_hex()))?;
-
-        let mut req = self.inner.agent.get(&action.href);
-        for (key, value) in &action.header {
-            req = req.set(key, value);
-        }
-
-        let response = req.call()?;
-
-        // Stream to writer while hashing
-        let mut hashing_writer = HashingWriter::new(writer);
-        io::copy(&mut response.into_reader(), &mut hashing_writer).map_err(Error::Io)?;
-
-        let (computed_oid, size, _) = hashing_writer.finish();
-
-        // Verify hash
-        if &computed_oid != pointer.oid() {
-            return Err(Error::InvalidPointer(
-                "downloaded content hash mismatch".into(),
-            ));
-        }
-        if size != pointer.size() {
-            return Err(Error::InvalidPointer(
-                "downloaded content size mismatch".into(),
-            ));
-        }
+            .ok_or_else(|| Error::NotFound {
+                oid: Some(pointer.oid().clone()),
+                url: None,
+            })?;
 
-        Ok(size)
+        let (req, credentials) = self.download_request(endpoint, obj, action);
+        self.send(endpoint, req)
+            .map_err(|e| e.with_credentials(credentials).with_oid(pointer.oid()))
     }
 
     /// Upload content from a file, streaming.
@@ -524,14 +792,22 @@ impl LfsClient {
 
     /// Upload content from a reader.
     ///
-    /// The pointer must match the content that will be read.
-    /// The size must be known in advance for Content-Length header.
-    pub fn upload_reader<R: Read>(
+    /// The pointer must match the content that will be read, starting at
+    /// the reader's current position. Nothing is read if the object is
+    /// already known to be on the server. The size must be known in
+    /// advance for Content-Length header. If the upload is redirected with
+    /// a 307 or 308, the reader is rewound to where it started and read
+    /// again.
+    pub fn upload_reader<R: Read + Seek>(
         &self,
         pointer: &Pointer,
-        reader: R,
+        mut reader: R,
         size: u64,
     ) -> Result<()> {
+        if self.is_known_present(pointer.oid()) {
+            return Ok(());
+        }
+
         // Request upload URL
         let mut batch_req = BatchRequest::upload(vec![BatchRequestObject::new(
             &pointer.oid().to_hex(),
@@ -544,183 +820,459 @@ impl LfsClient {
         let batch_resp = self.batch(&batch_req)?;
 
         if batch_resp.objects.is_empty() {
-            return Err(Error::ServerError {
-                code: 500,
-                message: "no objects in batch response".into(),
-            });
+            return Err(Error::Protocol("no objects in batch response".into()));
         }
 
         let obj = &batch_resp.objects[0];
 
         if let Some(err) = &obj.error {
-            return Err(Error::ServerError {
-                code: err.code,
-                message: err.message.clone(),
-            });
+            return Err(Error::object_error(&obj.oid, err.code, &err.message));
         }
 
         // Get upload action (no action means already exists)
         let action = match obj.upload_action() {
             Some(a) => a,
-            None => return Ok(()), // Already exists
-        };
-
-        // Upload the content
-        let mut req = self.inner.agent.put(&action.href);
-        for (key, value) in &action.header {
-            req = req.set(key, value);
-        }
-        req = req.set("Content-Type", "application/octet-stream");
-        req = req.set("Content-Length", &size.to_string());
-
-        // Send with reader
-        req.send(reader)?;
-
-        // Verify if required
-        if let Some(verify_action) = obj.verify_action() {
-            let verify_body = serde_json::json!({
-                "oid": pointer.oid().to_hex(),
-                "size": pointer.size()
-            });
-
-            let mut req = self.inner.agent.post(&verify_action.href);
-            for (key, value) in &verify_action.header {
-                req = req.set(key, value);
+            None => {
+                self.mark_present(self.push_url(), pointer.oid());
+                return Ok(()); // Already exists
             }
-            req = req.set("Content-Type", "application/vnd.git-lfs+json");
-            req.send_json(&verify_body)?;
-        }
+        };
 
+        // Upload the content, streaming from the reader
+        let start = reader.stream_position()?;
+        let reader = RefCell::new(reader);
+        let body = || -> Result<RequestBody<'_>> {
+            reader.borrow_mut().seek(SeekFrom::Start(start))?;
+            let shared = SharedReader(&reader);
+            Ok(RequestBody::Reader(Box::new(self.throttled(shared))))
+        };
+        let (req, credentials) = self.action_request(HttpMethod::Put, self.push_url(), obj, action);
+        let req = req
+            .with_header("Content-Type", "application/octet-stream")
+            .with_header("Content-Length", &size.to_string())
+            .with_body(body()?);
+        self.send_replayable(self.push_url(), req, Some(&body))
+            .map_err(|e| e.with_credentials(credentials))
+            .and_then(|_| self.verify_object(obj))
+            .map_err(|e| e.with_oid(pointer.oid()))?;
+
+        self.mark_present(self.push_url(), pointer.oid());
         Ok(())
     }
 
     /// Check if objects exist on the server.
     ///
-    /// Returns a list of OIDs that exist.
+    /// Objects already known to be present are not queried again; the rest
+    /// are queried in chunks of [`batch_size`](Self::batch_size). Returns
+    /// the distinct OIDs that exist.
     pub fn check_exists(&self, pointers: &[&Pointer]) -> Result<Vec<String>> {
-        if pointers.is_empty() {
-            return Ok(vec![]);
-        }
-
-        let objects: Vec<_> = pointers
+        let pointers = unique_pointers(pointers.iter().copied());
+        let unknown: Vec<_> = pointers
             .iter()
-            .map(|p| BatchRequestObject::new(&p.oid().to_hex(), p.size()))
+            .copied()
+            .filter(|p| !self.is_known_at(self.lfs_url(), p.oid()))
             .collect();
 
-        let mut batch_req = BatchRequest::download(objects);
-        if let Some(ref_name) = &self.inner.ref_name {
-            batch_req = batch_req.with_ref(ref_name);
-        }
-        let batch_resp = self.batch(&batch_req)?;
+        for chunk in unknown.chunks(self.inner.batch_size) {
+            let objects: Vec<_> = chunk
+                .iter()
+                .map(|p| BatchRequestObject::new(&p.oid().to_hex(), p.size()))
+                .collect();
 
-        let existing: Vec<_> = batch_resp
-            .objects
-            .into_iter()
-            .filter(|obj| obj.download_action().is_some())
-            .map(|obj| obj.oid)
-            .collect();
+            let mut batch_req = BatchRequest::download(objects);
+            if let Some(ref_name) = &self.inner.ref_name {
+                batch_req = batch_req.with_ref(ref_name);
+            }
+            let batch_resp = self.batch(&batch_req)?;
+
+            for obj in &batch_resp.objects {
+                if obj.download_action().is_some() {
+                    if let Ok(oid) = Oid::from_hex(&obj.oid) {
+                        self.mark_present(self.lfs_url(), &oid);
+                    }
+                }
+            }
+        }
 
-        Ok(existing)
+        Ok(pointers
+            .iter()
+            .filter(|p| self.is_known_at(self.lfs_url(), p.oid()))
+            .map(|p| p.oid().to_hex())
+            .collect())
     }
 
-    /// Upload multiple objects in a single batch request.
+    /// Upload multiple objects using batch requests.
     ///
     /// More efficient than calling `upload()` multiple times as it uses
-    /// a single batch request to get all upload URLs.
+    /// one batch request per chunk of [`batch_size`](Self::batch_size)
+    /// objects. Fails with the first per-object error; use
+    /// [`upload_batch_report`](Self::upload_batch_report) to see every
+    /// object's outcome.
     pub fn upload_batch(&self, items: &[(&Pointer, &[u8])]) -> Result<()> {
-        if items.is_empty() {
-            return Ok(());
-        }
-
-        // Verify all content matches pointers
-        for (pointer, content) in items {
-            let computed = Pointer::from_content(content);
-            if computed.oid() != pointer.oid() || computed.size() != pointer.size() {
-                return Err(Error::InvalidPointer(format!(
-                    "content does not match pointer for oid {}",
-                    pointer.oid().to_hex()
-                )));
-            }
+        for (_, status) in self.upload_batch_report(items)? {
+            status.into_result()?;
         }
+        Ok(())
+    }
 
-        // Request upload URLs for all objects
-        let objects: Vec<_> = items
+    /// Upload multiple objects, reporting the outcome for each one.
+    ///
+    /// Returns one `(oid, status)` entry per distinct OID, in order of first
+    /// appearance. Objects already known to be on the server are reported
+    /// as `AlreadyPresent` without asking it again. A per-object server
+    /// error or failed transfer does not stop the other uploads. Errors
+    /// affecting a whole batch request, such as rejected credentials, are
+    /// returned as `Err`.
+    pub fn upload_batch_report(
+        &self,
+        items: &[(&Pointer, &[u8])],
+    ) -> Result<Vec<(Oid, ObjectStatus<()>)>> {
+        let mut seen = HashSet::new();
+        let items: Vec<_> = items
+            .iter()
+            .filter(|(p, _)| seen.insert(p.oid()))
+            .copied()
+            .collect();
+        let pending: Vec<_> = items
             .iter()
-            .map(|(p, _)| BatchRequestObject::new(&p.oid().to_hex(), p.size()))
+            .filter(|(p, _)| !self.is_known_present(p.oid()))
+            .copied()
             .collect();
 
-        let mut batch_req = BatchRequest::upload(objects);
-        if let Some(ref_name) = &self.inner.ref_name {
-            batch_req = batch_req.with_ref(ref_name);
+        let mut statuses = HashMap::with_capacity(pending.len());
+        for chunk in pending.chunks(self.inner.batch_size) {
+            statuses.extend(self.upload_chunk(chunk)?);
         }
-        let batch_resp = self.batch(&batch_req)?;
 
-        // Create a map of oid -> content for lookup
-        let content_map: std::collections::HashMap<_, _> = items
+        Ok(items
             .iter()
-            .map(|(p, c)| (p.oid().to_hex(), *c))
+            .map(|(p, _)| {
+                let status = statuses
+                    .remove(p.oid())
+                    .unwrap_or(ObjectStatus::AlreadyPresent);
+                (p.oid().clone(), status)
+            })
+            .collect())
+    }
+
+    fn upload_chunk(&self, items: &[(&Pointer, &[u8])]) -> Result<Vec<(Oid, ObjectStatus<()>)>> {
+        // Content that doesn't match its pointer is never sent
+        let mut statuses: Vec<_> = items
+            .iter()
+            .map(|(pointer, content)| {
+                Error::check_content(pointer, &Oid::from_content(content), content.len() as u64)
+                    .err()
+                    .map(ObjectStatus::Failed)
+            })
             .collect();
 
-        // Upload each object that has an upload action
-        for obj in &batch_resp.objects {
-            // Check for errors
-            if let Some(err) = &obj.error {
-                return Err(Error::ServerError {
-                    code: err.code,
-                    message: err.message.clone(),
-                });
+        let objects: Vec<_> = items
+            .iter()
+            .zip(&statuses)
+            .filter(|(_, status)| status.is_none())
+            .map(|((p, _), _)| BatchRequestObject::new(&p.oid().to_hex(), p.size()))
+            .collect();
+
+        if !objects.is_empty() {
+            let mut batch_req = BatchRequest::upload(objects);
+            if let Some(ref_name) = &self.inner.ref_name {
+                batch_req = batch_req.with_ref(ref_name);
             }
+            let batch_resp = self.batch(&batch_req)?;
+            let received_at = batch_resp.received_at;
+
+            let obj_map: HashMap<_, _> = batch_resp
+                .objects
+                .into_iter()
+                .map(|o| (o.oid.clone(), o))
+                .collect();
+
+            for ((pointer, content), status) in items.iter().zip(&mut statuses) {
+                if status.is_none() {
+                    *status = Some(match obj_map.get(&pointer.oid().to_hex()) {
+                        Some(obj) => self.upload_object(pointer, content, obj, received_at),
+                        None => ObjectStatus::Failed(missing_from_response(pointer)),
+                    });
+                }
+            }
+        }
+
+        Ok(items
+            .iter()
+            .zip(statuses)
+            .map(|((pointer, _), status)| {
+                (
+                    pointer.oid().clone(),
+                    status.expect("every object has a status"),
+                )
+            })
+            .collect())
+    }
 
-            // Get upload action (no action means already exists)
-            let action = match obj.upload_action() {
-                Some(a) => a,
-                None => continue, // Already exists on server
+    /// Upload one object according to its batch response entry.
+    fn upload_object(
+        &self,
+        pointer: &Pointer,
+        content: &[u8],
+        obj: &BatchObject,
+        received_at: SystemTime,
+    ) -> ObjectStatus<()> {
+        let push_url = self.push_url();
+        let fresh =
+            match self.refresh_expiring(push_url, Operation::Upload, pointer, obj, received_at) {
+                Ok(fresh) => fresh,
+                Err(e) => return ObjectStatus::Failed(e.with_oid(pointer.oid())),
             };
+        let obj = fresh.as_ref().unwrap_or(obj);
 
-            // Get content for this object
-            let content = content_map.get(&obj.oid).ok_or_else(|| {
-                Error::InvalidPointer(format!("no content for oid {}", obj.oid))
-            })?;
+        if let Some(err) = &obj.error {
+            return ObjectStatus::Failed(Error::object_error(&obj.oid, err.code, &err.message));
+        }
 
-            // Upload the content
-            let mut req = self.inner.agent.put(&action.href);
-            for (key, value) in &action.header {
-                req = req.set(key, value);
+        // No upload action means the server already has it
+        let action = match obj.upload_action() {
+            Some(a) => a,
+            None => {
+                self.mark_present(self.push_url(), pointer.oid());
+                return ObjectStatus::AlreadyPresent;
             }
-            req = req.set("Content-Type", "application/octet-stream");
-            req = req.set("Content-Length", &content.len().to_string());
-            req.send_bytes(content)?;
-
-            // Verify if required
-            if let Some(verify_action) = obj.verify_action() {
-                let verify_body = serde_json::json!({
-                    "oid": obj.oid,
-                    "size": obj.size
-                });
-
-                let mut req = self.inner.agent.post(&verify_action.href);
-                for (key, value) in &verify_action.header {
-                    req = req.set(key, value);
-                }
-                req = req.set("Content-Type", "application/vnd.git-lfs+json");
-                req.send_json(&verify_body)?;
+        };
+
+        match self.send_object(content, obj, action) {
+            Ok(()) => {
+                self.mark_present(self.push_url(), pointer.oid());
+                ObjectStatus::Done(())
             }
+            Err(e) => ObjectStatus::Failed(e.with_oid(pointer.oid())),
         }
+    }
 
+    /// Send an object's content to its upload action and verify it if the
+    /// server asked for verification.
+    fn send_object(&self, content: &[u8], obj: &BatchObject, action: &Action) -> Result<()> {
+        // A throttled body is streamed, so redirects stream it from the
+        // start again
+        let body = || -> Result<RequestBody<'_>> {
+            Ok(match self.inner.throttle {
+                Some(_) => RequestBody::Reader(Box::new(self.throttled(content))),
+                None => RequestBody::Bytes(content),
+            })
+        };
+        let (req, credentials) = self.action_request(HttpMethod::Put, self.push_url(), obj, action);
+        let req = req
+            .with_header("Content-Type", "application/octet-stream")
+            .with_header("Content-Length", &content.len().to_string())
+            .with_body(body()?);
+        self.send_replayable(self.push_url(), req, Some(&body))
+            .map_err(|e| e.with_credentials(credentials))?;
+        self.verify_object(obj)
+    }
+
+    /// Call the object's verify action, if the server gave one.
+    fn verify_object(&self, obj: &BatchObject) -> Result<()> {
+        if let Some(verify_action) = obj.verify_action() {
+            let verify_body = serde_json::to_vec(&serde_json::json!({
+                "oid": obj.oid,
+                "size": obj.size
+            }))?;
+
+            let (req, credentials) =
+                self.action_request(HttpMethod::Post, self.push_url(), obj, verify_action);
+            let req = req
+                .with_header("Content-Type", "application/vnd.git-lfs+json")
+                .with_body(RequestBody::Bytes(&verify_body));
+            self.send(self.push_url(), req)
+                .map_err(|e| e.with_credentials(credentials))?;
+        }
         Ok(())
     }
 
-    /// Download multiple objects in a single batch request.
+    /// Request for a transfer action, carrying the action's headers, plus
+    /// the client's credentials when
+    /// [`action_wants_credentials`] says so. Also returns where those
+    /// credentials came from.
+    fn action_request<'a>(
+        &self,
+        method: HttpMethod,
+        endpoint: &Url,
+        obj: &BatchObject,
+        action: &'a Action,
+    ) -> (HttpRequest<'a>, Option<CredentialSource>) {
+        let mut req = HttpRequest::new(method, &action.href);
+        for (key, value) in &action.header {
+            req = req.with_header(key, value);
+        }
+        if !action_wants_credentials(endpoint, obj, action) {
+            return (req, None);
+        }
+        match self.credentials_for(endpoint) {
+            Some((auth, source)) => (
+                req.with_header("Authorization", &auth.header_value()),
+                Some(source),
+            ),
+            None => (req, None),
+        }
+    }
+
+    /// Request for a download action, offering compressed encodings unless
+    /// transfer compression is off or the action sets `Accept-Encoding`.
+    fn download_request<'a>(
+        &self,
+        endpoint: &Url,
+        obj: &BatchObject,
+        action: &'a Action,
+    ) -> (HttpRequest<'a>, Option<CredentialSource>) {
+        let (req, credentials) = self.action_request(HttpMethod::Get, endpoint, obj, action);
+        if req.header("Accept-Encoding").is_some() {
+            return (req, credentials);
+        }
+        // Asking for identity also stops ureq from offering gzip itself
+        let accept = match self.inner.transfer_compression {
+            true => ACCEPT_ENCODING,
+            false => "identity",
+        };
+        (req.with_header("Accept-Encoding", accept), credentials)
+    }
+
+    /// Send a request for `endpoint` through its transport, following
+    /// redirects and turning error statuses into errors.
+    ///
+    /// Transfer actions go through the transport of the endpoint that
+    /// returned them.
+    fn send(&self, endpoint: &Url, request: HttpRequest<'_>) -> Result<HttpResponse> {
+        self.send_replayable(endpoint, request, None)
+    }
+
+    /// Like [`send`](Self::send), calling `replay` for a streamed body that
+    /// a redirect asks to be sent again.
+    fn send_replayable<'a>(
+        &self,
+        endpoint: &Url,
+        request: HttpRequest<'a>,
+        replay: Option<&dyn Fn() -> Result<RequestBody<'a>>>,
+    ) -> Result<HttpResponse> {
+        let transport = self
+            .inner
+            .endpoints
+            .get(endpoint)
+            .and_then(|config| config.transport.as_deref())
+            .unwrap_or(&*self.inner.transport);
+        self.inner
+            .redirect_policy
+            .send(transport, request, replay)?
+            .error_for_status()
+    }
+
+    /// Download multiple objects using batch requests.
     ///
     /// More efficient than calling `download()` multiple times as it uses
-    /// a single batch request to get all download URLs.
+    /// one batch request per chunk of [`batch_size`](Self::batch_size)
+    /// objects. Fails with the first per-object error; use
+    /// [`download_batch_report`](Self::download_batch_report) to see every
+    /// object's outcome.
     ///
-    /// Returns a vector of (pointer, content) pairs in the same order as input.
+    /// Returns the content of each object in the same order as input.
     pub fn download_batch(&self, pointers: &[&Pointer]) -> Result<Vec<Vec<u8>>> {
-        if pointers.is_empty() {
-            return Ok(vec![]);
+        let mut contents = HashMap::new();
+        for (oid, status) in self.download_batch_report(pointers)? {
+            match status {
+                ObjectStatus::Done(content) => {
+                    contents.insert(oid, content);
+                }
+                ObjectStatus::AlreadyPresent => {
+                    return Err(Error::NotFound {
+                        oid: Some(oid),
+                        url: None,
+                    })
+                }
+                ObjectStatus::Failed(err) => return Err(err),
+            }
         }
 
+        // Duplicate pointers get copies; the last one takes the original
+        let last: HashMap<_, _> = pointers
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (p.oid(), i))
+            .collect();
+        Ok(pointers
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                if last[p.oid()] == i {
+                    contents.remove(p.oid())
+                } else {
+                    contents.get(p.oid()).cloned()
+                }
+                .expect("every distinct object has a status")
+            })
+            .collect())
+    }
+
+    /// Download multiple objects, reporting the outcome for each one.
+    ///
+    /// Returns one `(oid, status)` entry per distinct OID, in order of first
+    /// appearance, with verified content for each successful download. Objects the server
+    /// doesn't have are reported as `Failed` with [`Error::NotFound`], so
+    /// callers can decide whether a missing object should fail the whole
+    /// operation. Errors affecting a whole batch request are returned as
+    /// `Err`.
+    pub fn download_batch_report(
+        &self,
+        pointers: &[&Pointer],
+    ) -> Result<Vec<(Oid, ObjectStatus<Vec<u8>>)>> {
+        let pointers = unique_pointers(pointers.iter().copied());
+        let mut report = Vec::with_capacity(pointers.len());
+        for chunk in pointers.chunks(self.inner.batch_size) {
+            report.extend(self.download_chunk(chunk)?);
+        }
+        Ok(report)
+    }
+
+    /// Download a chunk, retrying objects that failed at one endpoint
+    /// against the next.
+    fn download_chunk(&self, pointers: &[&Pointer]) -> Result<Vec<(Oid, ObjectStatus<Vec<u8>>)>> {
+        let endpoints = self.download_endpoints();
+        let mut statuses = HashMap::with_capacity(pointers.len());
+        let mut pending = pointers.to_vec();
+
+        for (i, endpoint) in endpoints.iter().enumerate() {
+            let last = i + 1 == endpoints.len();
+            let report = match self.download_chunk_at(endpoint, &pending) {
+                Ok(report) => report,
+                Err(e) if !last && falls_back(&e) => continue,
+                // Objects that failed at an earlier endpoint keep that status
+                Err(_) if !statuses.is_empty() => break,
+                Err(e) => return Err(e),
+            };
+
+            let mut retry = Vec::new();
+            for (pointer, (oid, status)) in pending.iter().zip(report) {
+                if matches!(&status, ObjectStatus::Failed(e) if falls_back(e)) {
+                    retry.push(*pointer);
+                }
+                statuses.insert(oid, status);
+            }
+            pending = retry;
+            if pending.is_empty() {
+                break;
+            }
+        }
+
+        Ok(pointers
+            .iter()
+            .map(|p| {
+                let status = statuses.remove(p.oid()).expect("every object has a status");
+                (p.oid().clone(), status)
+            })
+            .collect())
+    }
+
+    fn download_chunk_at(
+        &self,
+        endpoint: &Url,
+        pointers: &[&Pointer],
+    ) -> Result<Vec<(Oid, ObjectStatus<Vec<u8>>)>> {
         // Request download URLs for all objects
         let objects: Vec<_> = pointers
             .iter()
@@ -731,64 +1283,164 @@ impl LfsClient {
         if let Some(ref_name) = &self.inner.ref_name {
             batch_req = batch_req.with_ref(ref_name);
         }
-        let batch_resp = self.batch(&batch_req)?;
+        let batch_resp = self.batch_at(endpoint, &batch_req)?;
+        let received_at = batch_resp.received_at;
 
         // Create a map of oid -> batch object for lookup
-        let obj_map: std::collections::HashMap<_, _> = batch_resp
+        let obj_map: HashMap<_, _> = batch_resp
             .objects
             .into_iter()
             .map(|o| (o.oid.clone(), o))
             .collect();
 
         // Download each object in order
-        let mut results = Vec::with_capacity(pointers.len());
-        for pointer in pointers {
-            let oid = pointer.oid().to_hex();
-            let obj = obj_map
-                .get(&oid)
-                .ok_or_else(|| Error::NotFound(oid.clone()))?;
-
-            // Check for errors
-            if let Some(err) = &obj.error {
-                return Err(Error::ServerError {
-                    code: err.code,
-                    message: err.message.clone(),
-                });
-            }
+        Ok(pointers
+            .iter()
+            .map(|pointer| {
+                let status = match obj_map.get(&pointer.oid().to_hex()) {
+                    Some(obj) => match self.download_object(endpoint, pointer, obj, received_at) {
+                        Ok(content) => ObjectStatus::Done(content),
+                        Err(e) => ObjectStatus::Failed(e.with_oid(pointer.oid())),
+                    },
+                    None => ObjectStatus::Failed(missing_from_response(pointer)),
+                };
+                (pointer.oid().clone(), status)
+            })
+            .collect())
+    }
 
-            // Get download action
-            let action = obj
-                .download_action()
-                .ok_or_else(|| Error::NotFound(oid.clone()))?;
+    /// Re-request `obj` if its transfer action has expired or is about to.
+    ///
+    /// Actions from a batch response may sit in the queue while earlier
+    /// objects transfer. Returns the fresh batch object, or `None` if the
+    /// original action is still usable.
+    fn refresh_expiring(
+        &self,
+        endpoint: &Url,
+        operation: Operation,
+        pointer: &Pointer,
+        obj: &BatchObject,
+        received_at: SystemTime,
+    ) -> Result<Option<BatchObject>> {
+        let action = match operation {
+            Operation::Download => obj.download_action(),
+            Operation::Upload => obj.upload_action(),
+        };
+        if !action.is_some_and(|a| a.expires_within(received_at, ACTION_EXPIRY_MARGIN)) {
+            return Ok(None);
+        }
 
-            // Download the content
-            let mut req = self.inner.agent.get(&action.href);
-            for (key, value) in &action.header {
-                req = req.set(key, value);
-            }
-            let response = req.call()?;
-
-            let mut content = Vec::with_capacity(pointer.size() as usize);
-            response.into_reader().read_to_end(&mut content)?;
-
-            // Verify content
-            let computed = Pointer::from_content(&content);
-            if computed.oid() != pointer.oid() {
-                return Err(Error::InvalidPointer(format!(
-                    "downloaded content hash mismatch for oid {}",
-                    oid
-                )));
-            }
+        let objects = vec![BatchRequestObject::new(
+            &pointer.oid().to_hex(),
+            pointer.size(),
+        )];
+        let mut batch_req = match operation {
+            Operation::Download => BatchRequest::download(objects),
+            Operation::Upload => BatchRequest::upload(objects),
+        };
+        if let Some(ref_name) = &self.inner.ref_name {
+            batch_req = batch_req.with_ref(ref_name);
+        }
+        let batch_resp = self.batch_at(endpoint, &batch_req)?;
 
-            results.push(content);
+        let oid = pointer.oid().to_hex();
+        batch_resp
+            .objects
+            .into_iter()
+            .find(|o| o.oid == oid)
+            .map(Some)
+            .ok_or_else(|| missing_from_response(pointer))
+    }
+
+    /// Download one object according to its batch response entry.
+    fn download_object(
+        &self,
+        endpoint: &Url,
+        pointer: &Pointer,
+        obj: &BatchObject,
+        received_at: SystemTime,
+    ) -> Result<Vec<u8>> {
+        let fresh =
+            self.refresh_expiring(endpoint, Operation::Download, pointer, obj, received_at)?;
+        let obj = fresh.as_ref().unwrap_or(obj);
+
+        if let Some(err) = &obj.error {
+            return Err(Error::object_error(&obj.oid, err.code, &err.message));
         }
 
-        Ok(results)
+        let action = obj.download_action().ok_or_else(|| Error::NotFound {
+            oid: Some(pointer.oid().clone()),
+            url: None,
+        })?;
+
+        let (req, credentials) = self.download_request(endpoint, obj, action);
+        let response = self
+            .send(endpoint, req)
+            .map_err(|e| e.with_credentials(credentials))?;
+
+        let mut content = Vec::with_capacity(pointer.size() as usize);
+        self.download_body(pointer, response)?.read_to_end(&mut content)?;
+
+        // Verify content
+        Error::check_content(pointer, &Oid::from_content(&content), content.len() as u64)?;
+
+        Ok(content)
     }
 }
 
+/// Whether a transfer request for `action` should carry the client's
+/// credentials.
+///
+/// Per the batch API, objects whose actions need nothing more are marked
+/// `authenticated`; for the rest the client authenticates as it would to
+/// the endpoint. Credentials only go to the endpoint's own origin, never
+/// to third-party storage, and never replace an `Authorization` header the
+/// action brings.
+pub(crate) fn action_wants_credentials(endpoint: &Url, obj: &BatchObject, action: &Action) -> bool {
+    if obj.authenticated == Some(true)
+        || action
+            .header
+            .keys()
+            .any(|key| key.eq_ignore_ascii_case("authorization"))
+    {
+        return false;
+    }
+    Url::parse(&action.href).is_ok_and(|href| href.origin() == endpoint.origin())
+}
+
+/// Whether a download that failed with `err` should be tried at the next
+/// endpoint: the object is missing, or the endpoint is unreachable or
+/// overloaded.
+fn falls_back(err: &Error) -> bool {
+    err.is_not_found() || err.is_retryable()
+}
+
+/// Reader borrowed from a `RefCell`, so an upload body can be rewound
+/// and streamed again after a redirect.
+struct SharedReader<'a, R>(&'a RefCell<R>);
+
+impl<R: Read> Read for SharedReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.borrow_mut().read(buf)
+    }
+}
+
+/// Drop pointers whose OID appeared earlier, keeping the input order.
+pub(crate) fn unique_pointers<'a>(pointers: impl Iterator<Item = &'a Pointer>) -> Vec<&'a Pointer> {
+    let mut seen = HashSet::new();
+    pointers.filter(|p| seen.insert(p.oid())).collect()
+}
+
+/// Error for an object the server left out of a batch response.
+pub(crate) fn missing_from_response(pointer: &Pointer) -> Error {
+    Error::Protocol(format!(
+        "batch response is missing object {}",
+        pointer.oid()
+    ))
+}
+
 /// Derive the LFS endpoint URL from a Git remote URL.
-fn derive_lfs_url(repo_url: &str) -> Result<Url> {
+pub(crate) fn derive_lfs_url(repo_url: &str) -> Result<Url> {
     let repo_url = repo_url.trim();
 
     // Handle SSH URLs (git@github.com:owner/repo.git)
@@ -875,6 +1527,281 @@ mod tests {
         assert!(Arc::ptr_eq(&client1.inner, &client2.inner));
     }
 
+    /// Transport that serves the batch API and object storage from memory.
+    #[derive(Default)]
+    struct MemoryTransport {
+        objects: Mutex<HashMap<String, Vec<u8>>>,
+        requests: Mutex<Vec<(HttpMethod, String, Option<String>)>>,
+    }
+
+    impl HttpTransport for MemoryTransport {
+        fn send(&self, request: HttpRequest<'_>) -> Result<HttpResponse> {
+            self.requests.lock().unwrap().push((
+                request.method,
+                request.url.to_string(),
+                request.header("Authorization").map(str::to_string),
+            ));
+
+            let mut body = Vec::new();
+            match request.body {
+                RequestBody::Empty => {}
+                RequestBody::Bytes(bytes) => body.extend_from_slice(bytes),
+                RequestBody::Reader(mut reader) => {
+                    reader.read_to_end(&mut body)?;
+                }
+            }
+
+            let mut objects = self.objects.lock().unwrap();
+            if request.url.ends_with("/objects/batch") {
+                let batch: serde_json::Value = serde_json::from_slice(&body)?;
+                let upload = batch["operation"] == "upload";
+                let results: Vec<_> = batch["objects"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|obj| {
+                        let oid = obj["oid"].as_str().unwrap();
+                        let href = format!("https://lfs.test/objects/{}", oid);
+                        let mut result = obj.clone();
+                        if upload && !objects.contains_key(oid) {
+                            result["actions"] = serde_json::json!({"upload": {"href": href}});
+                        } else if !upload && objects.contains_key(oid) {
+                            result["actions"] = serde_json::json!({"download": {"href": href}});
+                        } else if !upload {
+                            result["error"] =
+                                serde_json::json!({"code": 404, "message": "not found"});
+                        }
+                        result
+                    })
+                    .collect();
+                let response = serde_json::json!({"objects": results});
+                return Ok(HttpResponse::new(200, request.url, response.to_string()));
+            }
+
+            let oid = request.url.rsplit('/').next().unwrap().to_string();
+            Ok(match request.method {
+                HttpMethod::Put => {
+                    objects.insert(oid, body);
+                    HttpResponse::new(200, request.url, "")
+                }
+                _ => match objects.get(&oid) {
+                    Some(content) => HttpResponse::new(200, request.url, content.clone()),
+                    None => HttpResponse::new(404, request.url, "not found"),
+                },
+            })
+        }
+    }
+
+    #[test]
+    fn test_client_with_memory_transport() {
+        let transport = Arc::new(MemoryTransport::default());
+        let client = LfsClient::new("https://lfs.test/repo.git")
+            .unwrap()
+            .with_transport(transport.clone())
+            .with_token("secret")
+            .with_ref("refs/heads/main");
+
+        let content = b"stored in memory";
+        let pointer = Pointer::from_content(content);
+        client.upload(&pointer, content).unwrap();
+        assert_eq!(client.download(&pointer).unwrap(), content);
+
+        let streamed = b"streamed from a reader";
+        let streamed_pointer = Pointer::from_content(streamed);
+        client
+            .upload_reader(
+                &streamed_pointer,
+                io::Cursor::new(streamed),
+                streamed.len() as u64,
+            )
+            .unwrap();
+        let mut out = Vec::new();
+        client
+            .download_to_writer(&streamed_pointer, &mut out)
+            .unwrap();
+        assert_eq!(out, streamed);
+
+        let missing = Pointer::from_content(b"never uploaded");
+        assert!(client.download(&missing).unwrap_err().is_not_found());
+
+        // The transport survives later builder calls, and batch requests
+        // carry the credentials
+        let requests = transport.requests.lock().unwrap();
+        let batches: Vec<_> = requests
+            .iter()
+            .filter(|(_, url, _)| url.ends_with("/objects/batch"))
+            .collect();
+        assert_eq!(batches.len(), 5);
+        assert!(batches
+            .iter()
+            .all(|(method, _, auth)| *method == HttpMethod::Post
+                && auth.as_deref() == Some("Bearer secret")));
+        assert_eq!(requests.len(), 9);
+    }
+
+    #[test]
+    fn test_bandwidth_limit_covers_transfers() {
+        let client = LfsClient::new("https://lfs.test/repo.git")
+            .unwrap()
+            .with_transport(MemoryTransport::default())
+            .with_bandwidth_limit(50_000);
+        assert_eq!(client.bandwidth_limit(), Some(50_000));
+
+        // Clones configured later keep sharing the limit
+        let other = client.clone().with_ref("refs/heads/main");
+        assert!(Arc::ptr_eq(
+            client.inner.throttle.as_ref().unwrap(),
+            other.inner.throttle.as_ref().unwrap()
+        ));
+
+        let content = vec![1u8; 10_000];
+        let pointer = Pointer::from_content(&content);
+        let start = std::time::Instant::now();
+        client.upload(&pointer, &content).unwrap();
+        let mut out = Vec::new();
+        other.download_to_writer(&pointer, &mut out).unwrap();
+        assert_eq!(out, content);
+        assert!(start.elapsed() >= Duration::from_millis(300));
+
+        assert_eq!(client.with_bandwidth_limit(0).bandwidth_limit(), None);
+    }
+
+    /// Transport serving one object, zstd-compressed when the request
+    /// offers it, and recording each download's `Accept-Encoding`.
+    struct Compressing {
+        content: Vec<u8>,
+        accepted: Mutex<Vec<Option<String>>>,
+    }
+
+    impl HttpTransport for Compressing {
+        fn send(&self, request: HttpRequest<'_>) -> Result<HttpResponse> {
+            if request.url.ends_with("/objects/batch") {
+                let oid = Oid::from_content(&self.content).to_hex();
+                let response = serde_json::json!({"objects": [{
+                    "oid": oid,
+                    "size": self.content.len(),
+                    "actions": {"download": {"href": format!("https://lfs.test/objects/{}", oid)}}
+                }]});
+                return Ok(HttpResponse::new(200, request.url, response.to_string()));
+            }
+
+            let accept = request.header("Accept-Encoding").map(str::to_string);
+            self.accepted.lock().unwrap().push(accept.clone());
+            Ok(match accept {
+                Some(accept) if accept.contains("zstd") => {
+                    HttpResponse::new(200, request.url, zstd::encode_all(&self.content[..], 0)?)
+                        .with_header("Content-Encoding", "zstd")
+                }
+                _ => HttpResponse::new(200, request.url, self.content.clone()),
+            })
+        }
+    }
+
+    #[test]
+    fn test_downloads_decode_compressed_content() {
+        let content = b"row,value\n".repeat(1000);
+        let pointer = Pointer::from_content(&content);
+        let transport = Arc::new(Compressing {
+            content: content.clone(),
+            accepted: Mutex::default(),
+        });
+        let client = LfsClient::new("https://lfs.test/repo.git")
+            .unwrap()
+            .with_transport(transport.clone());
+
+        // Content is verified after decoding
+        assert_eq!(client.download(&pointer).unwrap(), content);
+        let mut out = Vec::new();
+        client.download_to_writer(&pointer, &mut out).unwrap();
+        assert_eq!(out, content);
+
+        let plain = client.clone().with_transfer_compression(false);
+        assert_eq!(plain.download(&pointer).unwrap(), content);
+
+        assert_eq!(
+            *transport.accepted.lock().unwrap(),
+            [Some("gzip, zstd"), Some("gzip, zstd"), Some("identity")]
+                .map(|accept| accept.map(str::to_string))
+        );
+
+        // Decoding stops one byte past the pointer's size
+        let short = Pointer::new(pointer.oid().clone(), 10);
+        let too_large = |err: Error| {
+            matches!(err, Error::SizeMismatch { expected: 10, actual: 11, .. })
+        };
+        assert!(too_large(client.download(&short).unwrap_err()));
+        let mut out = Vec::new();
+        assert!(too_large(client.download_to_writer(&short, &mut out).unwrap_err()));
+        assert_eq!(out.len(), 11);
+    }
+
+    struct Unauthorized;
+
+    impl HttpTransport for Unauthorized {
+        fn send(&self, request: HttpRequest<'_>) -> Result<HttpResponse> {
+            Ok(HttpResponse::new(401, request.url, "who are you?"))
+        }
+    }
+
+    #[test]
+    fn test_auth_error_names_credential_source() {
+        let client = LfsClient::new("https://lfs.test/repo.git")
+            .unwrap()
+            .with_transport(Unauthorized);
+        let request = BatchRequest::download(vec![BatchRequestObject::new("abc", 1)]);
+
+        let err = client.batch(&request).unwrap_err();
+        assert!(err.is_auth());
+        assert_eq!(err.credential_source(), None);
+
+        let err = client.with_token("expired").batch(&request).unwrap_err();
+        assert_eq!(err.credential_source(), Some(CredentialSource::Explicit));
+        assert!(
+            err.to_string()
+                .contains("credentials from client configuration"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_action_wants_credentials() {
+        let endpoint = Url::parse("https://git.example.com/repo.git/info/lfs/").unwrap();
+        let object =
+            |json: serde_json::Value| -> BatchObject { serde_json::from_value(json).unwrap() };
+        let same_host = object(serde_json::json!({
+            "oid": "abc", "size": 1,
+            "actions": { "download": { "href": "https://git.example.com/lfs/objects/abc" } }
+        }));
+        let wants = |obj: &BatchObject| {
+            action_wants_credentials(&endpoint, obj, obj.download_action().unwrap())
+        };
+        assert!(wants(&same_host));
+
+        let authenticated = object(serde_json::json!({
+            "oid": "abc", "size": 1, "authenticated": true,
+            "actions": { "download": { "href": "https://git.example.com/lfs/objects/abc" } }
+        }));
+        let own_header = object(serde_json::json!({
+            "oid": "abc", "size": 1,
+            "actions": { "download": {
+                "href": "https://git.example.com/lfs/objects/abc",
+                "header": { "authorization": "RemoteAuth token" }
+            } }
+        }));
+        let other_port = object(serde_json::json!({
+            "oid": "abc", "size": 1,
+            "actions": { "download": { "href": "https://git.example.com:8443/abc" } }
+        }));
+        let storage = object(serde_json::json!({
+            "oid": "abc", "size": 1,
+            "actions": { "download": { "href": "https://bucket.s3.example.com/abc" } }
+        }));
+        for obj in [&authenticated, &own_header, &other_port, &storage] {
+            assert!(!wants(obj));
+        }
+    }
+
     #[test]
     #[cfg(feature = "git2-integration")]
     fn test_from_repo_with_lfsconfig() {
@@ -0,0 +1,336 @@
+//! URL-matched git configuration.
+//!
+//! Git lets `http.*` settings, and git-lfs lets `lfs.*` settings, be scoped
+//! to URLs with keys like `http.https://example.com/.proxy`. This module
+//! collects such entries and picks the one that best matches a URL, using
+//! git's rules: the scheme, host and port must match, `*` matches one host
+//! label, the pattern's path must be a prefix of the URL's path on a `/`
+//! boundary, and a user name in the pattern must match the URL's. Among
+//! matching entries the longest host, then the longest path, then a user
+//! match wins; the last of equally good entries wins, as with any config.
+
+use url::Url;
+
+use crate::{Error, Result};
+
+/// Config entries for some sections, keyed by optional URL.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct UrlConfig {
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    /// Lowercased section name, e.g. `http`.
+    section: String,
+    /// URL subsection, if any.
+    url: Option<String>,
+    /// Lowercased variable name, e.g. `sslverify`.
+    key: String,
+    /// Value, or `None` for a valueless boolean entry.
+    value: Option<String>,
+}
+
+/// How well a URL pattern matches: host length, path length, user matched.
+type Score = (usize, usize, bool);
+
+impl UrlConfig {
+    /// Add a config entry named like `section.key` or `section.<url>.key`.
+    pub(crate) fn push(&mut self, name: &str, value: Option<&str>) {
+        let Some((section, rest)) = name.split_once('.') else {
+            return;
+        };
+        let (url, key) = match rest.rsplit_once('.') {
+            Some((url, key)) => (Some(url.to_string()), key),
+            None => (None, rest),
+        };
+        self.entries.push(Entry {
+            section: section.to_ascii_lowercase(),
+            url,
+            key: key.to_ascii_lowercase(),
+            value: value.map(str::to_string),
+        });
+    }
+
+    /// Read every entry of the given sections from a git config.
+    #[cfg(feature = "git2-integration")]
+    pub(crate) fn from_git(config: &git2::Config, sections: &[&str]) -> Result<Self> {
+        let mut url_config = UrlConfig::default();
+        let glob = format!("^({})\\.", sections.join("|"));
+        let mut entries = config.entries(Some(&glob))?;
+        while let Some(entry) = entries.next() {
+            let entry = entry?;
+            if let Some(name) = entry.name() {
+                url_config.push(name, entry.value());
+            }
+        }
+        Ok(url_config)
+    }
+
+    /// Matching entries for `section.key`, in config order.
+    fn matching(&self, section: &str, key: &str, url: &Url) -> Vec<(Score, &Entry)> {
+        self.entries
+            .iter()
+            .filter(|e| e.section == section && e.key == key)
+            .filter_map(|e| {
+                let score = match &e.url {
+                    None => Some((0, 0, false)),
+                    Some(pattern) => match_url(pattern, url),
+                };
+                score.map(|score| (score, e))
+            })
+            .collect()
+    }
+
+    /// Value of the entry for `section.key` that best matches `url`.
+    ///
+    /// The outer `Option` is whether any entry matched; the inner one is
+    /// `None` for a valueless entry.
+    pub(crate) fn get(&self, section: &str, key: &str, url: &Url) -> Option<Option<&str>> {
+        let mut best: Option<(Score, &Entry)> = None;
+        for (score, entry) in self.matching(section, key, url) {
+            if best.is_none_or(|(best_score, _)| score >= best_score) {
+                best = Some((score, entry));
+            }
+        }
+        best.map(|(_, entry)| entry.value.as_deref())
+    }
+
+    /// Value of the string entry for `section.key` that best matches `url`.
+    pub(crate) fn get_str(&self, section: &str, key: &str, url: &Url) -> Option<&str> {
+        self.get(section, key, url).map(Option::unwrap_or_default)
+    }
+
+    /// Values of every entry for `section.key` that matches `url`, for
+    /// multi-valued settings. An empty value clears the values before it.
+    pub(crate) fn get_all(&self, section: &str, key: &str, url: &Url) -> Vec<&str> {
+        let mut values = Vec::new();
+        for (_, entry) in self.matching(section, key, url) {
+            match entry.value.as_deref() {
+                None | Some("") => values.clear(),
+                Some(value) => values.push(value),
+            }
+        }
+        values
+    }
+
+    /// Boolean entry for `section.key`, parsed the way git does.
+    pub(crate) fn get_bool(&self, section: &str, key: &str, url: &Url) -> Result<Option<bool>> {
+        let Some(value) = self.get(section, key, url) else {
+            return Ok(None);
+        };
+        let value = match value {
+            None => return Ok(Some(true)),
+            Some(value) => value.to_ascii_lowercase(),
+        };
+        match value.as_str() {
+            "true" | "yes" | "on" => Ok(Some(true)),
+            "false" | "no" | "off" | "" => Ok(Some(false)),
+            _ => value
+                .parse::<i64>()
+                .map(|n| Some(n != 0))
+                .map_err(|_| invalid(section, key, "expected a boolean")),
+        }
+    }
+
+    /// Whole-number entry for `section.key`.
+    pub(crate) fn get_u64(&self, section: &str, key: &str, url: &Url) -> Result<Option<u64>> {
+        self.get_str(section, key, url)
+            .map(|value| {
+                value
+                    .trim()
+                    .parse()
+                    .map_err(|_| invalid(section, key, "expected a whole number"))
+            })
+            .transpose()
+    }
+}
+
+/// Error for a config value that can't be used.
+pub(crate) fn invalid(section: &str, key: &str, message: &str) -> Error {
+    Error::Config {
+        key: format!("{}.{}", section, key),
+        message: message.to_string(),
+    }
+}
+
+/// Score `url` against a config URL pattern, or `None` if it doesn't match.
+fn match_url(pattern: &str, url: &Url) -> Option<Score> {
+    let (scheme, rest) = pattern.split_once("://")?;
+    if !scheme.eq_ignore_ascii_case(url.scheme()) {
+        return None;
+    }
+
+    let (authority, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, ""),
+    };
+    let (user, host_port) = match authority.rsplit_once('@') {
+        Some((userinfo, host_port)) => {
+            let user = userinfo.split_once(':').map_or(userinfo, |(user, _)| user);
+            (Some(user), host_port)
+        }
+        None => (None, authority),
+    };
+    // A colon inside an IPv6 literal's brackets is not a port separator
+    let (host, port) = match host_port.rfind(':') {
+        Some(i) if !host_port[i..].contains(']') => (
+            &host_port[..i],
+            Some(host_port[i + 1..].parse::<u16>().ok()?),
+        ),
+        _ => (host_port, None),
+    };
+
+    // Host labels must match, with `*` standing for any one label
+    let url_host = url.host_str()?;
+    let pattern_labels: Vec<_> = host.split('.').collect();
+    let url_labels: Vec<_> = url_host.split('.').collect();
+    if pattern_labels.len() != url_labels.len()
+        || !pattern_labels
+            .iter()
+            .zip(&url_labels)
+            .all(|(p, u)| *p == "*" || p.eq_ignore_ascii_case(u))
+    {
+        return None;
+    }
+
+    let default_port = default_port(&scheme.to_ascii_lowercase());
+    if port.or(default_port) != url.port_or_known_default() {
+        return None;
+    }
+
+    // The pattern's path must be a whole-segment prefix of the URL's
+    let pattern_path = path.trim_end_matches('/');
+    let url_path = url.path();
+    if !pattern_path.is_empty()
+        && url_path != pattern_path
+        && !url_path
+            .strip_prefix(pattern_path)
+            .is_some_and(|rest| rest.starts_with('/'))
+    {
+        return None;
+    }
+
+    let user_matched = match user {
+        Some(user) if user != url.username() => return None,
+        Some(_) => true,
+        None => false,
+    };
+
+    Some((host.len(), pattern_path.len(), user_matched))
+}
+
+fn default_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "http" => Some(80),
+        "https" => Some(443),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_match_url() {
+        let target = url("https://alice@git.example.com/team/repo.git/info/lfs");
+
+        assert!(match_url("https://git.example.com", &target).is_some());
+        assert!(match_url("https://git.example.com:443/team", &target).is_some());
+        assert!(match_url("https://*.example.com/", &target).is_some());
+        assert!(match_url("https://alice@git.example.com", &target).is_some());
+
+        assert!(match_url("http://git.example.com", &target).is_none());
+        assert!(match_url("https://example.com", &target).is_none());
+        assert!(match_url("https://*.git.example.com", &target).is_none());
+        assert!(match_url("https://git.example.com:8443", &target).is_none());
+        assert!(match_url("https://git.example.com/tea", &target).is_none());
+        assert!(match_url("https://bob@git.example.com", &target).is_none());
+    }
+
+    #[test]
+    fn test_best_match_wins() {
+        let mut config = UrlConfig::default();
+        config.push(
+            "http.https://git.example.com/team/repo.git.proxy",
+            Some("repo"),
+        );
+        config.push("http.proxy", Some("global"));
+        config.push("http.https://git.example.com.proxy", Some("host"));
+        config.push("http.https://*.example.com/team.proxy", Some("wildcard"));
+        config.push("http.https://other.example.com.proxy", Some("other"));
+
+        assert_eq!(
+            config.get_str(
+                "http",
+                "proxy",
+                &url("https://git.example.com/team/repo.git/info/lfs")
+            ),
+            Some("repo")
+        );
+        assert_eq!(
+            config.get_str("http", "proxy", &url("https://git.example.com/elsewhere")),
+            Some("host")
+        );
+        assert_eq!(
+            config.get_str("http", "proxy", &url("https://ci.example.com/team/x")),
+            Some("wildcard")
+        );
+        assert_eq!(
+            config.get_str("http", "proxy", &url("https://example.org/")),
+            Some("global")
+        );
+        assert_eq!(
+            config.get_str("http", "cainfo", &url("https://example.org/")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_typed_values() {
+        let target = url("https://git.example.com/repo");
+        let mut config = UrlConfig::default();
+        config.push("http.sslVerify", Some("yes"));
+        config.push("http.https://git.example.com.sslverify", Some("false"));
+        config.push("lfs.dialtimeout", Some("10"));
+        config.push("lfs.activitytimeout", Some("soon"));
+        config.push("http.extraheader", Some("X-One: 1"));
+        config.push("http.https://git.example.com.extraHeader", Some("X-Two: 2"));
+        config.push(
+            "http.https://other.example.com.extraHeader",
+            Some("X-Three: 3"),
+        );
+
+        assert_eq!(
+            config.get_bool("http", "sslverify", &target).unwrap(),
+            Some(false)
+        );
+        assert_eq!(
+            config
+                .get_bool("http", "sslverify", &url("https://other.example.com"))
+                .unwrap(),
+            Some(true)
+        );
+        assert_eq!(
+            config.get_u64("lfs", "dialtimeout", &target).unwrap(),
+            Some(10)
+        );
+        assert!(matches!(
+            config.get_u64("lfs", "activitytimeout", &target),
+            Err(Error::Config { .. })
+        ));
+        assert_eq!(
+            config.get_all("http", "extraheader", &target),
+            ["X-One: 1", "X-Two: 2"]
+        );
+
+        // An empty value resets the list
+        config.push("http.extraheader", Some(""));
+        assert!(config.get_all("http", "extraheader", &target).is_empty());
+    }
+}
@@ -0,0 +1,332 @@
+//! Credentials found outside the client's own configuration.
+//!
+//! With [`LfsClient::with_ambient_credentials`](crate::LfsClient::with_ambient_credentials),
+//! batch requests that have no explicit credentials look for them in turn in:
+//!
+//! 1. The environment: `GIT_LFS_TOKEN` for a bearer token, or
+//!    `GIT_LFS_USERNAME` and `GIT_LFS_PASSWORD` for basic authentication.
+//!    Like explicit credentials, these are only sent to the origins of the
+//!    client's download and push endpoints, never to fallbacks elsewhere.
+//! 2. The netrc file (`$NETRC`, or `~/.netrc`; `_netrc` on Windows), by the
+//!    endpoint's host, falling back to its `default` entry.
+//! 3. An askpass program (`GIT_ASKPASS`, then `SSH_ASKPASS`), run with
+//!    git's prompts only after the server answers 401 to a request without
+//!    credentials.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use url::Url;
+
+use crate::client::Auth;
+use crate::{Error, Result};
+
+/// Where the credentials sent with a request came from.
+///
+/// Reported by [`Error::credential_source`] when the server rejects them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CredentialSource {
+    /// Set on the client with `with_auth()` or `with_token()`.
+    Explicit,
+    /// `GIT_LFS_TOKEN`, or `GIT_LFS_USERNAME` and `GIT_LFS_PASSWORD`.
+    Environment,
+    /// A matching entry in the netrc file.
+    Netrc,
+    /// Answers from the `GIT_ASKPASS` or `SSH_ASKPASS` program.
+    Askpass,
+}
+
+impl fmt::Display for CredentialSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CredentialSource::Explicit => "client configuration",
+            CredentialSource::Environment => "environment",
+            CredentialSource::Netrc => "netrc",
+            CredentialSource::Askpass => "askpass",
+        })
+    }
+}
+
+/// Credentials for `url` from the environment or the netrc file, in that
+/// order.
+pub(crate) fn ambient(url: &Url) -> Option<(Auth, CredentialSource)> {
+    let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    if let Some(auth) = from_env(env) {
+        return Some((auth, CredentialSource::Environment));
+    }
+    netrc(url)
+}
+
+/// Credentials for `url` from the netrc file.
+pub(crate) fn netrc(url: &Url) -> Option<(Auth, CredentialSource)> {
+    let contents = std::fs::read_to_string(netrc_path()?).ok()?;
+    let auth = Netrc::parse(&contents).lookup(url)?;
+    Some((auth, CredentialSource::Netrc))
+}
+
+/// Credentials from environment variables, looked up with `var`.
+fn from_env(var: impl Fn(&str) -> Option<String>) -> Option<Auth> {
+    if let Some(token) = var("GIT_LFS_TOKEN") {
+        return Some(Auth::Bearer(token));
+    }
+    Some(Auth::Basic(
+        var("GIT_LFS_USERNAME")?,
+        var("GIT_LFS_PASSWORD")?,
+    ))
+}
+
+fn netrc_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("NETRC") {
+        return Some(path.into());
+    }
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    let name = if cfg!(windows) { "_netrc" } else { ".netrc" };
+    Some(PathBuf::from(home).join(name))
+}
+
+/// Parsed netrc file.
+#[derive(Debug, Default)]
+struct Netrc {
+    /// `machine` entries, in file order.
+    machines: Vec<(String, NetrcEntry)>,
+    /// The `default` entry.
+    default: Option<NetrcEntry>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct NetrcEntry {
+    login: Option<String>,
+    password: Option<String>,
+}
+
+impl Netrc {
+    /// Parse netrc contents, skipping `macdef` bodies and unknown tokens.
+    fn parse(contents: &str) -> Self {
+        let mut netrc = Netrc::default();
+        // Host of the entry being read, `None` for `default`
+        let mut current: Option<(Option<String>, NetrcEntry)> = None;
+
+        let mut lines = contents.lines();
+        while let Some(line) = lines.next() {
+            let mut tokens = line.split_whitespace();
+            while let Some(token) = tokens.next() {
+                match token {
+                    "machine" | "default" => {
+                        netrc.finish(current.take());
+                        let host = match token {
+                            "machine" => Some(tokens.next().unwrap_or_default().to_string()),
+                            _ => None,
+                        };
+                        current = Some((host, NetrcEntry::default()));
+                    }
+                    "login" | "password" => {
+                        let value = tokens.next().map(str::to_string);
+                        if let Some((_, entry)) = &mut current {
+                            match token {
+                                "login" => entry.login = value,
+                                _ => entry.password = value,
+                            }
+                        }
+                    }
+                    "account" => {
+                        tokens.next();
+                    }
+                    "macdef" => {
+                        // A macro runs to the next blank line
+                        for line in lines.by_ref() {
+                            if line.trim().is_empty() {
+                                break;
+                            }
+                        }
+                        break;
+                    }
+                    _ if token.starts_with('#') => break,
+                    _ => {}
+                }
+            }
+        }
+        netrc.finish(current);
+        netrc
+    }
+
+    fn finish(&mut self, entry: Option<(Option<String>, NetrcEntry)>) {
+        match entry {
+            Some((Some(host), entry)) => self.machines.push((host, entry)),
+            Some((None, entry)) => self.default = Some(entry),
+            None => {}
+        }
+    }
+
+    /// Basic credentials for `url`'s host. A user name in the URL selects
+    /// the entry with that login.
+    fn lookup(&self, url: &Url) -> Option<Auth> {
+        let host = url.host_str()?;
+        let user = Some(url.username()).filter(|user| !user.is_empty());
+        let matches = |entry: &&NetrcEntry| user.is_none() || entry.login.as_deref() == user;
+
+        let entry = self
+            .machines
+            .iter()
+            .filter(|(machine, _)| machine.eq_ignore_ascii_case(host))
+            .map(|(_, entry)| entry)
+            .find(matches)
+            .or(self.default.as_ref().filter(matches))?;
+        Some(Auth::Basic(
+            entry.login.clone()?,
+            entry.password.clone().unwrap_or_default(),
+        ))
+    }
+}
+
+/// Ask the askpass program for credentials for `url`, or `None` if no
+/// program is configured.
+pub(crate) fn askpass(url: &Url) -> Result<Option<Auth>> {
+    let program = ["GIT_ASKPASS", "SSH_ASKPASS"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .find(|program| !program.is_empty());
+    match program {
+        Some(program) => ask(Path::new(&program), url).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Prompt for a user name and password the way git does.
+fn ask(program: &Path, url: &Url) -> Result<Auth> {
+    let origin = url.origin().ascii_serialization();
+    let username = match url.username() {
+        "" => run_askpass(program, &format!("Username for '{}': ", origin))?,
+        user => user.to_string(),
+    };
+
+    let mut user_url = Url::parse(&origin)?;
+    let _ = user_url.set_username(&username);
+    let user_origin = user_url.as_str().trim_end_matches('/');
+    let password = run_askpass(program, &format!("Password for '{}': ", user_origin))?;
+    Ok(Auth::Basic(username, password))
+}
+
+fn run_askpass(program: &Path, prompt: &str) -> Result<String> {
+    let askpass_error = |message: String| Error::Config {
+        key: "askpass".into(),
+        message: format!("{}: {}", program.display(), message),
+    };
+
+    let output = Command::new(program)
+        .arg(prompt)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| askpass_error(e.to_string()))?;
+    if !output.status.success() {
+        return Err(askpass_error(format!("exited with {}", output.status)));
+    }
+
+    let answer = String::from_utf8(output.stdout)
+        .map_err(|_| askpass_error("answer is not valid UTF-8".into()))?;
+    Ok(answer.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    fn basic(auth: Option<Auth>) -> Option<(String, String)> {
+        match auth {
+            Some(Auth::Basic(user, password)) => Some((user, password)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_env_credentials() {
+        let env = |vars: &'static [(&str, &str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+
+        assert!(matches!(
+            from_env(env(&[("GIT_LFS_TOKEN", "t"), ("GIT_LFS_USERNAME", "u")])),
+            Some(Auth::Bearer(token)) if token == "t"
+        ));
+        assert_eq!(
+            basic(from_env(env(&[
+                ("GIT_LFS_USERNAME", "u"),
+                ("GIT_LFS_PASSWORD", "p")
+            ]))),
+            Some(("u".into(), "p".into()))
+        );
+        assert!(from_env(env(&[("GIT_LFS_USERNAME", "u")])).is_none());
+    }
+
+    #[test]
+    fn test_netrc_lookup() {
+        let netrc = Netrc::parse(
+            "# CI credentials\n\
+             machine git.example.com login alice password one\n\
+             machine git.example.com\n  login bob\n  password two\n\
+             macdef init\nmachine evil.example.com login mallory\n\n\
+             machine other.example.com login carol password three account x\n\
+             default login anonymous password guest\n",
+        );
+
+        assert_eq!(
+            basic(netrc.lookup(&url("https://git.example.com/repo.git/info/lfs"))),
+            Some(("alice".into(), "one".into()))
+        );
+        assert_eq!(
+            basic(netrc.lookup(&url("https://bob@git.example.com/repo"))),
+            Some(("bob".into(), "two".into()))
+        );
+        assert_eq!(
+            basic(netrc.lookup(&url("https://OTHER.example.com/"))),
+            Some(("carol".into(), "three".into()))
+        );
+        assert_eq!(
+            basic(netrc.lookup(&url("https://evil.example.com/"))),
+            Some(("anonymous".into(), "guest".into()))
+        );
+        assert!(netrc
+            .lookup(&url("https://dave@git.example.com/"))
+            .is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_askpass_prompts() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let program = dir.path().join("askpass");
+        std::fs::write(
+            &program,
+            "#!/bin/sh\ncase \"$1\" in\n  Username*) echo alice ;;\n  *) echo \"secret for $1\" ;;\nesac\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let auth = ask(&program, &url("https://git.example.com/repo.git/info/lfs")).unwrap();
+        assert_eq!(
+            basic(Some(auth)),
+            Some((
+                "alice".into(),
+                "secret for Password for 'https://alice@git.example.com': ".into()
+            ))
+        );
+
+        let err = ask(
+            &dir.path().join("missing"),
+            &url("https://git.example.com/"),
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::Config { key, .. } if key == "askpass"));
+    }
+}
@@ -2,35 +2,107 @@
 
 use thiserror::Error;
 
+use crate::{CredentialSource, Oid, PointerDiagnostic};
+
 /// Result type for git2-lfs operations.
 pub type Result<T> = std::result::Result<T, Error>;
 
 /// Errors that can occur during LFS operations.
 #[derive(Error, Debug)]
 pub enum Error {
-    /// Invalid LFS pointer format
-    #[error("invalid LFS pointer: {0}")]
-    InvalidPointer(String),
+    /// Malformed LFS pointer, with the 1-based line number if known
+    #[error(
+        "malformed LFS pointer{}: {message}",
+        .line.map(|line| format!(" (line {})", line)).unwrap_or_default()
+    )]
+    MalformedPointer {
+        line: Option<usize>,
+        message: String,
+    },
+
+    /// Pointer uses an unsupported spec version
+    #[error("unsupported LFS pointer version: {0}")]
+    UnsupportedVersion(String),
+
+    /// Content hash does not match the expected OID
+    #[error("content hash mismatch: expected {expected}, got {actual}")]
+    HashMismatch { expected: Oid, actual: Oid },
+
+    /// Content size does not match the expected size
+    #[error("content size mismatch for {oid}: expected {expected} bytes, got {actual}")]
+    SizeMismatch {
+        oid: Oid,
+        expected: u64,
+        actual: u64,
+    },
+
+    /// Pointer is valid but not in canonical form
+    #[error("non-canonical LFS pointer: {}", join_diagnostics(.0))]
+    NonCanonicalPointer(Vec<PointerDiagnostic>),
 
     /// OID parsing error
     #[error("invalid OID: {0}")]
     InvalidOid(String),
 
-    /// HTTP request failed
-    #[error("HTTP error: {0}")]
-    Http(String),
+    /// HTTP request failed before a response was received
+    #[error("HTTP request{} failed: {source}", .url.as_ref().map(|url| format!(" to {}", url)).unwrap_or_default())]
+    Http {
+        url: Option<String>,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    /// Redirects could not be followed: they loop, exceed the redirect
+    /// policy's limit, or would need a streamed body sent again
+    #[error("redirect from {url} not followed: {message}")]
+    Redirect { url: String, message: String },
 
-    /// Server returned an error
-    #[error("LFS server error: {message} (code: {code})")]
-    ServerError { code: u16, message: String },
+    /// Server returned an error, either as an HTTP status or for a single
+    /// object in a batch response
+    #[error("LFS server error{}{}: {message} (code: {code})", for_object(.oid), at(.url))]
+    ServerError {
+        code: u16,
+        message: String,
+        url: Option<String>,
+        oid: Option<Oid>,
+    },
 
-    /// Object not found on server
-    #[error("object not found: {0}")]
-    NotFound(String),
+    /// Server chose a transfer adapter the request did not offer
+    #[error("server chose transfer adapter {0:?}, which was not offered")]
+    UnsupportedTransfer(String),
 
-    /// Authentication required
-    #[error("authentication required")]
-    AuthRequired,
+    /// Server chose a hash algorithm the request did not offer
+    #[error("server chose hash algorithm {0:?}, which was not offered")]
+    UnsupportedHashAlgo(String),
+
+    /// Server response did not follow the LFS protocol
+    #[error("unexpected LFS server response: {0}")]
+    Protocol(String),
+
+    /// Object not found on the server or in the cache
+    #[error("object{} not found{}", .oid.as_ref().map(|oid| format!(" {}", oid)).unwrap_or_default(), at(.url))]
+    NotFound {
+        oid: Option<Oid>,
+        url: Option<String>,
+    },
+
+    /// Server rejected the request's credentials (HTTP 401 or 403), with
+    /// where the credentials came from if any were sent and why no others
+    /// could be found
+    #[error(
+        "authentication required{}{} (code: {code}{})",
+        for_object(.oid),
+        at(.url),
+        .credentials.map(|source| format!(", credentials from {}", source)).unwrap_or_default()
+    )]
+    AuthRequired {
+        code: u16,
+        url: Option<String>,
+        oid: Option<Oid>,
+        credentials: Option<CredentialSource>,
+        #[source]
+        cause: Option<Box<Error>>,
+    },
 
     /// Invalid URL
     #[error("invalid URL: {0}")]
@@ -48,24 +120,357 @@ pub enum Error {
     #[error("URL parse error: {0}")]
     UrlParse(#[from] url::ParseError),
 
+    /// Pointer extension command failed or is misconfigured
+    #[error("extension {name}: {message}")]
+    Extension { name: String, message: String },
+
+    /// Configuration value that can't be used, from git config or a builder
+    #[error("invalid configuration {key}: {message}")]
+    Config { key: String, message: String },
+
     /// Git operation error
     #[cfg(feature = "git2-integration")]
     #[error("Git error: {0}")]
-    Git(String),
+    Git(#[from] git2::Error),
+}
+
+/// Whether an HTTP transport failure is worth retrying.
+///
+/// Only failures known to be transient are: errors from other transports,
+/// such as TLS or configuration problems, won't go away on a retry.
+fn transport_retryable(source: &(dyn std::error::Error + Send + Sync + 'static)) -> bool {
+    if let Some(transport) = source.downcast_ref::<ureq::Transport>() {
+        return matches!(
+            transport.kind(),
+            ureq::ErrorKind::Dns
+                | ureq::ErrorKind::ConnectionFailed
+                | ureq::ErrorKind::Io
+                | ureq::ErrorKind::ProxyConnect
+        );
+    }
+    #[cfg(feature = "async")]
+    if let Some(err) = source.downcast_ref::<reqwest::Error>() {
+        return err.is_connect() || err.is_timeout() || err.is_request() || err.is_body();
+    }
+    if let Some(err) = source.downcast_ref::<std::io::Error>() {
+        return io_retryable(err);
+    }
+    false
+}
+
+/// Whether an I/O error is a dropped or timed out connection.
+fn io_retryable(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::TimedOut
+            | std::io::ErrorKind::Interrupted
+            | std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::BrokenPipe
+            | std::io::ErrorKind::UnexpectedEof
+    )
+}
+
+fn for_object(oid: &Option<Oid>) -> String {
+    oid.as_ref()
+        .map(|oid| format!(" for object {}", oid))
+        .unwrap_or_default()
+}
+
+fn at(url: &Option<String>) -> String {
+    url.as_ref()
+        .map(|url| format!(" at {}", url))
+        .unwrap_or_default()
+}
+
+fn join_diagnostics(diagnostics: &[PointerDiagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|d| d.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+impl Error {
+    /// Malformed pointer error at a 1-based line.
+    pub(crate) fn malformed_at(line: usize, message: impl Into<String>) -> Self {
+        Error::MalformedPointer {
+            line: Some(line),
+            message: message.into(),
+        }
+    }
+
+    /// Malformed pointer error not tied to a line.
+    pub(crate) fn malformed(message: impl Into<String>) -> Self {
+        Error::MalformedPointer {
+            line: None,
+            message: message.into(),
+        }
+    }
+
+    /// Error for an HTTP error status, reading the body only when its
+    /// message is reported.
+    pub(crate) fn from_status(
+        code: u16,
+        url: Option<String>,
+        message: impl FnOnce() -> String,
+    ) -> Self {
+        match code {
+            401 | 403 => Error::AuthRequired {
+                code,
+                url,
+                oid: None,
+                credentials: None,
+                cause: None,
+            },
+            404 => Error::NotFound { oid: None, url },
+            _ => Error::ServerError {
+                code,
+                message: message(),
+                url,
+                oid: None,
+            },
+        }
+    }
+
+    /// Error for a single object reported in a batch response.
+    pub(crate) fn object_error(oid: &str, code: u16, message: &str) -> Self {
+        let oid = Oid::from_hex(oid).ok();
+        match code {
+            404 => Error::NotFound { oid, url: None },
+            401 | 403 => Error::AuthRequired {
+                code,
+                url: None,
+                oid,
+                credentials: None,
+                cause: None,
+            },
+            _ => Error::ServerError {
+                code,
+                message: message.to_string(),
+                url: None,
+                oid,
+            },
+        }
+    }
+
+    /// Attach the object a request was made for, if not already known.
+    pub(crate) fn with_oid(mut self, object: &Oid) -> Self {
+        match &mut self {
+            Error::ServerError { oid, .. }
+            | Error::NotFound { oid, .. }
+            | Error::AuthRequired { oid, .. } => {
+                oid.get_or_insert_with(|| object.clone());
+            }
+            _ => {}
+        }
+        self
+    }
+
+    /// Record where the credentials of a rejected request came from.
+    pub(crate) fn with_credentials(mut self, source: Option<CredentialSource>) -> Self {
+        if let Error::AuthRequired { credentials, .. } = &mut self {
+            *credentials = source;
+        }
+        self
+    }
+
+    /// Record why no credentials could be found after the server asked for
+    /// them, keeping the server's error.
+    pub(crate) fn caused_by(mut self, error: Error) -> Self {
+        if let Error::AuthRequired { cause, .. } = &mut self {
+            *cause = Some(Box::new(error));
+        }
+        self
+    }
+
+    /// Where the credentials the server rejected came from, or `None` if
+    /// the request had none or the error isn't an authentication failure.
+    pub fn credential_source(&self) -> Option<CredentialSource> {
+        match self {
+            Error::AuthRequired { credentials, .. } => *credentials,
+            _ => None,
+        }
+    }
+
+    /// URL of the request that failed, if any.
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            Error::Http { url, .. }
+            | Error::ServerError { url, .. }
+            | Error::NotFound { url, .. }
+            | Error::AuthRequired { url, .. } => url.as_deref(),
+            Error::Redirect { url, .. } => Some(url),
+            _ => None,
+        }
+    }
+
+    /// OID of the object the error relates to, if any.
+    pub fn oid(&self) -> Option<&Oid> {
+        match self {
+            Error::ServerError { oid, .. }
+            | Error::NotFound { oid, .. }
+            | Error::AuthRequired { oid, .. } => oid.as_ref(),
+            Error::HashMismatch { expected, .. } => Some(expected),
+            Error::SizeMismatch { oid, .. } => Some(oid),
+            _ => None,
+        }
+    }
+
+    /// Whether retrying the same operation may succeed.
+    ///
+    /// True for connection failures, timeouts, and server responses that
+    /// signal a transient condition (408, 429 and most 5xx codes).
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Http { source, .. } => transport_retryable(source.as_ref()),
+            Error::ServerError { code, .. } => {
+                matches!(code, 408 | 429 | 500 | 502 | 503 | 504)
+            }
+            Error::Io(err) => io_retryable(err),
+            _ => false,
+        }
+    }
+
+    /// Whether the server rejected the request's credentials.
+    pub fn is_auth(&self) -> bool {
+        #[cfg(feature = "git2-integration")]
+        if let Error::Git(err) = self {
+            return err.code() == git2::ErrorCode::Auth;
+        }
+        matches!(self, Error::AuthRequired { .. })
+    }
+
+    /// Whether the requested object, reference or file does not exist.
+    pub fn is_not_found(&self) -> bool {
+        #[cfg(feature = "git2-integration")]
+        if let Error::Git(err) = self {
+            return err.code() == git2::ErrorCode::NotFound;
+        }
+        match self {
+            Error::NotFound { .. } => true,
+            Error::Io(err) => err.kind() == std::io::ErrorKind::NotFound,
+            _ => false,
+        }
+    }
+
+    /// Check content read for `pointer` against its size and OID.
+    pub(crate) fn check_content(
+        pointer: &crate::Pointer,
+        actual: &Oid,
+        actual_size: u64,
+    ) -> Result<()> {
+        if actual_size != pointer.size() {
+            return Err(Error::SizeMismatch {
+                oid: pointer.oid().clone(),
+                expected: pointer.size(),
+                actual: actual_size,
+            });
+        }
+        if actual != pointer.oid() {
+            return Err(Error::HashMismatch {
+                expected: pointer.oid().clone(),
+                actual: actual.clone(),
+            });
+        }
+        Ok(())
+    }
 }
 
 impl From<ureq::Error> for Error {
     fn from(err: ureq::Error) -> Self {
         match err {
-            ureq::Error::Status(401, _) | ureq::Error::Status(403, _) => Error::AuthRequired,
-            ureq::Error::Status(404, _) => Error::NotFound("object not found".into()),
             ureq::Error::Status(code, response) => {
-                let message = response
-                    .into_string()
-                    .unwrap_or_else(|_| "unknown error".into());
-                Error::ServerError { code, message }
+                let url = Some(response.get_url().to_string());
+                Error::from_status(code, url, || {
+                    response
+                        .into_string()
+                        .unwrap_or_else(|_| "unknown error".into())
+                })
             }
-            other => Error::Http(other.to_string()),
+            ureq::Error::Transport(transport) => Error::Http {
+                url: transport.url().map(|url| url.to_string()),
+                source: Box::new(transport),
+            },
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Http {
+            url: err.url().map(|url| url.to_string()),
+            source: Box::new(err),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(code: u16, body: &str) -> ureq::Error {
+        ureq::Error::Status(code, ureq::Response::new(code, "Status", body).unwrap())
+    }
+
+    #[test]
+    fn test_status_classification() {
+        let oid = Oid::from_content(b"hello");
+
+        let err = Error::from(status(404, "")).with_oid(&oid);
+        assert!(err.is_not_found());
+        assert!(!err.is_retryable());
+        assert_eq!(err.oid(), Some(&oid));
+
+        let err = Error::from(status(401, ""));
+        assert!(err.is_auth());
+        assert!(matches!(err, Error::AuthRequired { code: 401, .. }));
+
+        let askpass = Error::Config {
+            key: "askpass".into(),
+            message: "cancelled".into(),
+        };
+        let err = Error::from(status(401, "")).caused_by(askpass);
+        assert!(matches!(err, Error::AuthRequired { code: 401, .. }));
+        let cause = std::error::Error::source(&err).unwrap();
+        assert!(cause.to_string().contains("cancelled"), "{}", cause);
+
+        let err = Error::from(status(503, "try later"));
+        assert!(err.is_retryable());
+        assert!(err.to_string().contains("try later"));
+        assert!(!Error::from(status(422, "bad")).is_retryable());
+    }
+
+    #[test]
+    fn test_transport_error_keeps_source_and_url() {
+        // Nothing listens on the discard port
+        let err = Error::from(ureq::get("http://127.0.0.1:9/objects").call().unwrap_err());
+        assert!(matches!(err, Error::Http { .. }));
+        assert!(err.is_retryable());
+        assert_eq!(err.url(), Some("http://127.0.0.1:9/objects"));
+        assert!(std::error::Error::source(&err).is_some());
+
+        // Failures from other transports are only retried if known transient
+        let custom = |source: Box<dyn std::error::Error + Send + Sync>| Error::Http {
+            url: None,
+            source,
+        };
+        assert!(!custom("certificate verify failed".into()).is_retryable());
+        let reset = std::io::Error::from(std::io::ErrorKind::ConnectionReset);
+        assert!(custom(Box::new(reset)).is_retryable());
+    }
+
+    #[test]
+    fn test_object_error() {
+        let oid = Oid::from_content(b"hello");
+        let err = Error::object_error(&oid.to_hex(), 404, "missing");
+        assert!(err.is_not_found());
+        assert_eq!(err.oid(), Some(&oid));
+        assert!(err.to_string().contains(&oid.to_hex()));
+
+        let err = Error::object_error(&oid.to_hex(), 500, "boom");
+        assert!(err.is_retryable());
+        assert!(err.to_string().contains("boom"));
+    }
+}
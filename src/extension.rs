@@ -0,0 +1,414 @@
+//! Pointer extensions (`lfs.extension.*`).
+//!
+//! An extension is a pair of external commands that transform content on
+//! its way into and out of LFS. Clean commands run in ascending priority
+//! order before the content is hashed and stored; smudge commands undo them
+//! in reverse order. Each extension's input OID is recorded in the pointer
+//! (see [`PointerExtension`]) so the smudge output can be verified at every
+//! step.
+//!
+//! Commands run through `sh -c`, reading content on stdin and writing the
+//! result to stdout. `%f` in a command is replaced with the (shell-quoted)
+//! path of the file being filtered.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::pointer::{is_extension_name, MAX_EXTENSION_PRIORITY};
+use crate::{Error, Oid, Pointer, PointerExtension, Result};
+
+/// A configured extension: `lfs.extension.<name>.{clean,smudge,priority}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extension {
+    name: String,
+    clean: String,
+    smudge: String,
+    priority: u8,
+}
+
+impl Extension {
+    /// Create an extension from its clean and smudge commands.
+    ///
+    /// The name must be one a pointer can record: ASCII letters, digits and
+    /// underscores.
+    pub fn new(name: &str, clean: &str, smudge: &str, priority: u8) -> Result<Self> {
+        if !is_extension_name(name) {
+            return Err(Error::Extension {
+                name: name.to_string(),
+                message: "name may only contain ASCII letters, digits and underscores".into(),
+            });
+        }
+        if priority > MAX_EXTENSION_PRIORITY {
+            return Err(Error::Extension {
+                name: name.to_string(),
+                message: format!("priority {} is out of range", priority),
+            });
+        }
+
+        Ok(Extension {
+            name: name.to_string(),
+            clean: clean.to_string(),
+            smudge: smudge.to_string(),
+            priority,
+        })
+    }
+
+    /// Get the extension name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the clean command.
+    pub fn clean_command(&self) -> &str {
+        &self.clean
+    }
+
+    /// Get the smudge command.
+    pub fn smudge_command(&self) -> &str {
+        &self.smudge
+    }
+
+    /// Get the priority; lower priorities clean first.
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    /// Run one of the extension's commands on `input`.
+    fn run(&self, command: &str, path: &str, input: &[u8]) -> Result<Vec<u8>> {
+        let err = |message: String| Error::Extension {
+            name: self.name.clone(),
+            message,
+        };
+
+        let command = command.replace("%f", &shell_quote(path));
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| err(format!("failed to run `{}`: {}", command, e)))?;
+
+        // Feed stdin from another thread so a command that writes before
+        // reading all its input can't deadlock against us
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let output = std::thread::scope(|scope| {
+            let writer = scope.spawn(move || stdin.write_all(input));
+            let output = child.wait_with_output();
+            (writer.join(), output)
+        });
+
+        let output = match output {
+            (_, Err(e)) => return Err(err(format!("failed to run `{}`: {}", command, e))),
+            (_, Ok(output)) if !output.status.success() => {
+                return Err(err(format!(
+                    "`{}` failed ({}): {}",
+                    command,
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+            // A command may legitimately exit without reading all its input
+            (_, Ok(output)) => output,
+        };
+
+        Ok(output.stdout)
+    }
+}
+
+/// The set of configured extensions, applied in priority order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtensionPipeline {
+    /// Sorted by ascending priority.
+    extensions: Vec<Extension>,
+}
+
+impl ExtensionPipeline {
+    /// Create an empty pipeline, which leaves content unchanged.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an extension. Fails if another extension has the same priority.
+    pub fn with_extension(mut self, extension: Extension) -> Result<Self> {
+        if let Some(other) = self
+            .extensions
+            .iter()
+            .find(|other| other.priority == extension.priority)
+        {
+            return Err(Error::Extension {
+                name: extension.name,
+                message: format!(
+                    "priority {} is already used by extension {}",
+                    other.priority, other.name
+                ),
+            });
+        }
+
+        self.extensions.push(extension);
+        self.extensions.sort_by_key(|ext| ext.priority);
+        Ok(self)
+    }
+
+    /// Read extensions from `lfs.extension.<name>.*` git config entries.
+    ///
+    /// Each extension needs a `clean` and a `smudge` command; `priority`
+    /// defaults to 0.
+    #[cfg(feature = "git2-integration")]
+    pub fn from_config(config: &git2::Config) -> Result<Self> {
+        use std::collections::BTreeMap;
+
+        #[derive(Default)]
+        struct Settings {
+            clean: Option<String>,
+            smudge: Option<String>,
+            priority: Option<String>,
+        }
+
+        let mut found: BTreeMap<String, Settings> = BTreeMap::new();
+        config
+            .entries(Some(r"lfs\.extension\..*"))
+            .and_then(|entries| {
+                entries.for_each(|entry| {
+                    let (name, value) = match (entry.name(), entry.value()) {
+                        (Some(name), Some(value)) => (name, value),
+                        _ => return,
+                    };
+                    let (ext, key) = match name
+                        .strip_prefix("lfs.extension.")
+                        .and_then(|rest| rest.rsplit_once('.'))
+                    {
+                        Some(parts) => parts,
+                        None => return,
+                    };
+                    let slot = found.entry(ext.to_string()).or_default();
+                    match key {
+                        "clean" => slot.clean = Some(value.to_string()),
+                        "smudge" => slot.smudge = Some(value.to_string()),
+                        "priority" => slot.priority = Some(value.to_string()),
+                        _ => {}
+                    }
+                })
+            })?;
+
+        let mut pipeline = Self::new();
+        for (name, settings) in found {
+            let missing = |key: &str| Error::Extension {
+                name: name.clone(),
+                message: format!("lfs.extension.{}.{} is not set", name, key),
+            };
+            let clean = settings.clean.ok_or_else(|| missing("clean"))?;
+            let smudge = settings.smudge.ok_or_else(|| missing("smudge"))?;
+            let priority = match settings.priority {
+                Some(priority) => priority.trim().parse().map_err(|_| Error::Extension {
+                    name: name.clone(),
+                    message: format!("invalid priority: {}", priority),
+                })?,
+                None => 0,
+            };
+
+            pipeline =
+                pipeline.with_extension(Extension::new(&name, &clean, &smudge, priority)?)?;
+        }
+
+        Ok(pipeline)
+    }
+
+    /// Check if no extensions are configured.
+    pub fn is_empty(&self) -> bool {
+        self.extensions.is_empty()
+    }
+
+    /// Get the configured extensions in priority order.
+    pub fn extensions(&self) -> &[Extension] {
+        &self.extensions
+    }
+
+    /// Run the clean commands over `content`.
+    ///
+    /// Returns the content to store in LFS and its pointer, which records
+    /// the input OID of every extension.
+    pub fn clean(&self, path: &str, content: &[u8]) -> Result<(Vec<u8>, Pointer)> {
+        let mut content = content.to_vec();
+        let mut applied = Vec::with_capacity(self.extensions.len());
+
+        for ext in &self.extensions {
+            applied.push(PointerExtension::new(
+                &ext.name,
+                ext.priority,
+                Oid::from_content(&content),
+            )?);
+            content = ext.run(&ext.clean, path, &content)?;
+        }
+
+        let pointer = Pointer::from_content(&content).with_extensions(applied)?;
+        Ok((content, pointer))
+    }
+
+    /// Run the smudge commands over stored content, in reverse order.
+    ///
+    /// Every extension recorded in the pointer must be configured, and each
+    /// step's output must match the OID the pointer recorded for it.
+    pub fn smudge(&self, path: &str, pointer: &Pointer, content: Vec<u8>) -> Result<Vec<u8>> {
+        let mut content = content;
+
+        for recorded in pointer.extensions().iter().rev() {
+            let ext = self
+                .extensions
+                .iter()
+                .find(|ext| ext.name == recorded.name())
+                .ok_or_else(|| Error::Extension {
+                    name: recorded.name().to_string(),
+                    message: "extension is not configured".into(),
+                })?;
+
+            content = ext.run(&ext.smudge, path, &content)?;
+
+            let actual = Oid::from_content(&content);
+            if &actual != recorded.oid() {
+                return Err(Error::Extension {
+                    name: ext.name.clone(),
+                    message: format!(
+                        "smudge output {} does not match recorded OID {}",
+                        actual,
+                        recorded.oid()
+                    ),
+                });
+            }
+        }
+
+        Ok(content)
+    }
+}
+
+/// Quote a string for safe use as a single `sh` word.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn pipeline() -> ExtensionPipeline {
+        ExtensionPipeline::new()
+            .with_extension(Extension::new("upper", "tr a-z A-Z", "tr A-Z a-z", 1).unwrap())
+            .unwrap()
+            .with_extension(Extension::new("rev", "rev", "rev", 0).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_clean_smudge_roundtrip() {
+        let pipeline = pipeline();
+        let names: Vec<_> = pipeline.extensions().iter().map(|e| e.name()).collect();
+        assert_eq!(names, ["rev", "upper"]);
+
+        let (stored, pointer) = pipeline.clean("a.txt", b"hello\n").unwrap();
+        assert_eq!(stored, b"OLLEH\n");
+        assert_eq!(pointer.oid(), &Oid::from_content(&stored));
+        assert_eq!(pointer.size(), stored.len() as u64);
+        assert_eq!(
+            pointer.extensions()[0].oid(),
+            &Oid::from_content(b"hello\n")
+        );
+        assert_eq!(
+            pointer.extensions()[1].oid(),
+            &Oid::from_content(b"olleh\n")
+        );
+
+        let restored = pipeline.smudge("a.txt", &pointer, stored).unwrap();
+        assert_eq!(restored, b"hello\n");
+    }
+
+    #[test]
+    fn test_path_substitution() {
+        let pipeline = ExtensionPipeline::new()
+            .with_extension(Extension::new("name", "printf %s %f", "cat", 0).unwrap())
+            .unwrap();
+
+        let (stored, _) = pipeline.clean("it's a file.psd", b"").unwrap();
+        assert_eq!(stored, b"it's a file.psd");
+    }
+
+    #[test]
+    fn test_failures() {
+        let pipeline = pipeline();
+        let (stored, pointer) = pipeline.clean("a.txt", b"hello\n").unwrap();
+
+        // Smudge output that doesn't match the recorded OID
+        let lossy = ExtensionPipeline::new()
+            .with_extension(Extension::new("upper", "tr a-z A-Z", "cat", 1).unwrap())
+            .unwrap()
+            .with_extension(Extension::new("rev", "rev", "rev", 0).unwrap())
+            .unwrap();
+        assert!(matches!(
+            lossy.smudge("a.txt", &pointer, stored.clone()),
+            Err(Error::Extension { ref name, .. }) if name == "upper"
+        ));
+
+        // Extension recorded in the pointer but not configured
+        assert!(ExtensionPipeline::new()
+            .smudge("a.txt", &pointer, stored)
+            .is_err());
+
+        // Failing command
+        let failing = ExtensionPipeline::new()
+            .with_extension(Extension::new("fail", "echo broken >&2; exit 3", "cat", 0).unwrap())
+            .unwrap();
+        match failing.clean("a.txt", b"x") {
+            Err(Error::Extension { message, .. }) => assert!(message.contains("broken")),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // Duplicate priority
+        assert!(pipeline
+            .with_extension(Extension::new("other", "cat", "cat", 1).unwrap())
+            .is_err());
+
+        // Names a pointer can't record
+        assert!(Extension::new("foo-bar", "cat", "cat", 0).is_err());
+        assert!(Extension::new("", "cat", "cat", 0).is_err());
+    }
+
+    #[cfg(feature = "git2-integration")]
+    #[test]
+    fn test_from_config() {
+        let td = tempfile::TempDir::new().unwrap();
+        let repo = git2::Repository::init(td.path()).unwrap();
+        let mut config = repo.config().unwrap();
+        config
+            .set_str("lfs.extension.psd.clean", "psd-normalize clean %f")
+            .unwrap();
+        config
+            .set_str("lfs.extension.psd.smudge", "psd-normalize smudge %f")
+            .unwrap();
+        config.set_i32("lfs.extension.psd.priority", 2).unwrap();
+        config.set_str("lfs.extension.gz.clean", "gzip -n").unwrap();
+        config.set_str("lfs.extension.gz.smudge", "gunzip").unwrap();
+
+        let pipeline = ExtensionPipeline::from_config(&repo.config().unwrap()).unwrap();
+        let names: Vec<_> = pipeline.extensions().iter().map(|e| e.name()).collect();
+        assert_eq!(names, ["gz", "psd"]);
+        assert_eq!(pipeline.extensions()[1].priority(), 2);
+        assert_eq!(
+            pipeline.extensions()[1].clean_command(),
+            "psd-normalize clean %f"
+        );
+
+        // Both commands are required
+        config.remove("lfs.extension.gz.smudge").unwrap();
+        assert!(ExtensionPipeline::from_config(&repo.config().unwrap()).is_err());
+        config.set_str("lfs.extension.gz.smudge", "gunzip").unwrap();
+
+        // Names that can't go in a pointer are rejected up front
+        config.set_str("lfs.extension.a.b.clean", "cat").unwrap();
+        config.set_str("lfs.extension.a.b.smudge", "cat").unwrap();
+        config.set_i32("lfs.extension.a.b.priority", 5).unwrap();
+        match ExtensionPipeline::from_config(&repo.config().unwrap()) {
+            Err(Error::Extension { name, .. }) => assert_eq!(name, "a.b"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -21,41 +21,143 @@
 //! ```
 
 use git2::Repository;
+use std::borrow::Cow;
 use std::fs;
 use std::path::Path;
 
-use crate::{LfsClient, ObjectCache, Pointer, Result};
+use crate::cache::replace_file;
+use crate::{
+    CheckoutMode, Error, ExtensionPipeline, LfsClient, ObjectCache, ObjectSource, Pointer, Result,
+};
 
 /// LFS filter helper for git2 repositories.
 pub struct LfsFilter<'repo> {
     repo: &'repo Repository,
     client: LfsClient,
     cache: Option<ObjectCache>,
+    extensions: ExtensionPipeline,
 }
 
 impl<'repo> LfsFilter<'repo> {
     /// Create a new LFS filter for a repository.
     ///
-    /// Automatically derives the LFS endpoint from the remote URL and
-    /// initializes the object cache at `.git/lfs/objects`.
+    /// Automatically finds the LFS download and push endpoints (see
+    /// [`LfsClient::from_repo`]), falling back to any remote's URL if
+    /// there's no configured endpoint or "origin" remote. Also initializes
+    /// the object cache from the repository's storage settings (see
+    /// [`ObjectCache::from_repo`]), and loads pointer extensions from
+    /// `lfs.extension.*` (see [`ExtensionPipeline::from_config`]).
     pub fn new(repo: &'repo Repository) -> Result<Self> {
-        let remote_url = Self::get_remote_url(repo)?;
-        let client = LfsClient::new(&remote_url)?;
-        let cache = Some(ObjectCache::for_repo(repo.path()));
-        Ok(LfsFilter { repo, client, cache })
+        let client = match LfsClient::from_repo(repo) {
+            Ok(client) => client,
+            Err(e) if e.is_not_found() => LfsClient::new(&Self::get_remote_url(repo)?)?,
+            Err(e) => return Err(e),
+        };
+        let cache = Some(ObjectCache::from_repo(repo)?);
+        let extensions = Self::repo_extensions(repo)?;
+        Ok(LfsFilter {
+            repo,
+            client,
+            cache,
+            extensions,
+        })
     }
 
     /// Create a new LFS filter with a specific client.
     ///
-    /// Initializes the object cache at `.git/lfs/objects`.
+    /// Initializes the object cache from the repository's storage settings,
+    /// falling back to `.git/lfs/objects` if they can't be read. Pointer
+    /// extensions are loaded from the repository config, if valid. Use
+    /// [`try_with_client`](Self::try_with_client) to fail on unreadable
+    /// settings instead.
     pub fn with_client(repo: &'repo Repository, client: LfsClient) -> Self {
-        let cache = Some(ObjectCache::for_repo(repo.path()));
-        LfsFilter { repo, client, cache }
+        let cache = Some(
+            ObjectCache::from_repo(repo).unwrap_or_else(|_| ObjectCache::for_repo(repo.path())),
+        );
+        LfsFilter {
+            repo,
+            client,
+            cache,
+            extensions: Self::repo_extensions(repo).unwrap_or_default(),
+        }
+    }
+
+    /// Like [`with_client`](Self::with_client), but fails if the storage
+    /// settings or the `lfs.extension.*` config can't be read.
+    pub fn try_with_client(repo: &'repo Repository, client: LfsClient) -> Result<Self> {
+        Ok(LfsFilter {
+            repo,
+            client,
+            cache: Some(ObjectCache::from_repo(repo)?),
+            extensions: Self::repo_extensions(repo)?,
+        })
+    }
+
+    /// Create a new LFS filter with a specific client and object cache.
+    ///
+    /// Use this to share a cache directory or to set a size limit.
+    /// Pointer extensions are loaded from the repository config, if valid.
+    pub fn with_cache(repo: &'repo Repository, client: LfsClient, cache: ObjectCache) -> Self {
+        LfsFilter {
+            repo,
+            client,
+            cache: Some(cache),
+            extensions: Self::repo_extensions(repo).unwrap_or_default(),
+        }
+    }
+
+    /// Like [`with_cache`](Self::with_cache), but fails if the
+    /// `lfs.extension.*` config can't be read.
+    pub fn try_with_cache(
+        repo: &'repo Repository,
+        client: LfsClient,
+        cache: ObjectCache,
+    ) -> Result<Self> {
+        Ok(LfsFilter {
+            repo,
+            client,
+            cache: Some(cache),
+            extensions: Self::repo_extensions(repo)?,
+        })
     }
 
     /// Create a new LFS filter without a cache.
+    ///
+    /// Pointer extensions are loaded from the repository config, if valid.
     pub fn without_cache(repo: &'repo Repository, client: LfsClient) -> Self {
-        LfsFilter { repo, client, cache: None }
+        LfsFilter {
+            repo,
+            client,
+            cache: None,
+            extensions: Self::repo_extensions(repo).unwrap_or_default(),
+        }
+    }
+
+    /// Like [`without_cache`](Self::without_cache), but fails if the
+    /// `lfs.extension.*` config can't be read.
+    pub fn try_without_cache(repo: &'repo Repository, client: LfsClient) -> Result<Self> {
+        Ok(LfsFilter {
+            repo,
+            client,
+            cache: None,
+            extensions: Self::repo_extensions(repo)?,
+        })
+    }
+
+    /// Replace the pointer extensions run by `clean` and `smudge`.
+    pub fn with_extensions(mut self, extensions: ExtensionPipeline) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    /// Get the pointer extensions run by `clean` and `smudge`.
+    pub fn extensions(&self) -> &ExtensionPipeline {
+        &self.extensions
+    }
+
+    fn repo_extensions(repo: &Repository) -> Result<ExtensionPipeline> {
+        let config = repo.config()?;
+        ExtensionPipeline::from_config(&config)
     }
 
     /// Get the object cache if available.
@@ -189,24 +291,31 @@ impl<'repo> LfsFilter<'repo> {
 
     /// Clean content (working tree -> ODB).
     ///
-    /// If the file is tracked by LFS, this generates an LFS pointer,
-    /// uploads the content to the LFS server, and stores in local cache.
+    /// If the file is tracked by LFS, this runs any configured extensions,
+    /// generates an LFS pointer, uploads the content to the LFS server, and
+    /// stores in local cache.
     pub fn clean(&self, path: &str, content: &[u8]) -> Result<Vec<u8>> {
         if !self.is_tracked(path) {
             return Ok(content.to_vec());
         }
 
-        // Generate pointer
-        let pointer = Pointer::from_content(content);
+        // Run extensions and generate pointer
+        let (content, pointer) = clean_with_extensions(&self.extensions, path, content)?;
+        let content = &*content;
 
-        // Store in cache before upload (for later smudge without network)
+        // Store in cache before upload (for later smudge without network).
+        // Eviction keeps cleaned objects until they are marked as pushed.
         if let Some(cache) = &self.cache {
-            let _ = cache.put_verified(&pointer, content);
+            let _ = cache.put_verified_from(&pointer, content, ObjectSource::Clean);
         }
 
         // Upload to LFS server
         self.client.upload(&pointer, content)?;
 
+        if let Some(cache) = &self.cache {
+            let _ = cache.mark_pushed(pointer.oid());
+        }
+
         // Return pointer content
         Ok(pointer.encode_bytes())
     }
@@ -214,8 +323,9 @@ impl<'repo> LfsFilter<'repo> {
     /// Smudge content (ODB -> working tree).
     ///
     /// If the content is an LFS pointer, this checks the local cache first,
-    /// then downloads from the LFS server if not cached.
-    pub fn smudge(&self, _path: &str, content: &[u8]) -> Result<Vec<u8>> {
+    /// then downloads from the LFS server if not cached. Extensions recorded
+    /// in the pointer are then undone by their smudge commands.
+    pub fn smudge(&self, path: &str, content: &[u8]) -> Result<Vec<u8>> {
         // Check if content is an LFS pointer
         if !Pointer::is_pointer(content) {
             return Ok(content.to_vec());
@@ -223,25 +333,88 @@ impl<'repo> LfsFilter<'repo> {
 
         // Parse pointer
         let pointer = Pointer::parse(content)?;
+        let object = self.smudge_object(&pointer)?;
+        self.extensions.smudge(path, &pointer, object)
+    }
 
+    /// Get the stored object for a pointer, from the cache or the server.
+    fn smudge_object(&self, pointer: &Pointer) -> Result<Vec<u8>> {
         // Check cache first
         if let Some(cache) = &self.cache {
-            if let Some(cached) = cache.get_verified(&pointer) {
+            if let Some(cached) = cache.get_verified(pointer) {
                 return Ok(cached);
             }
         }
 
         // Download from LFS server
-        let downloaded = self.client.download(&pointer)?;
+        let downloaded = self.client.download(pointer)?;
 
         // Store in cache for future use
         if let Some(cache) = &self.cache {
-            let _ = cache.put_verified(&pointer, &downloaded);
+            let _ = cache.put_verified_from(pointer, &downloaded, ObjectSource::Download);
         }
 
         Ok(downloaded)
     }
 
+    /// Smudge content straight into a working-tree file.
+    ///
+    /// Unlike [`smudge`](Self::smudge), the object is never held in memory: it
+    /// is downloaded into the cache if needed and then materialized at `dest`
+    /// using `mode` (see [`ObjectCache::checkout`]). Without a cache, the
+    /// download is streamed to `dest`. Content that isn't a pointer is
+    /// written unchanged.
+    ///
+    /// Pointers with extensions are smudged in memory instead, since the
+    /// working-tree content differs from the stored object. Their `%f` is
+    /// `dest`.
+    pub fn smudge_to_file(&self, content: &[u8], dest: &Path, mode: CheckoutMode) -> Result<()> {
+        if !Pointer::is_pointer(content) {
+            replace_file(dest, content)?;
+            return Ok(());
+        }
+
+        let pointer = Pointer::parse(content)?;
+
+        if !pointer.extensions().is_empty() {
+            let content = self.smudge(&dest.to_string_lossy(), content)?;
+            replace_file(dest, &content)?;
+            return Ok(());
+        }
+
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.client.download_to_file(&pointer, dest),
+        };
+
+        if !cache.contains_valid(&pointer) {
+            self.download_to_cache(cache, &pointer)?;
+        }
+
+        if let Err(e) = cache.checkout(&pointer, dest, mode) {
+            // Only a cached copy that failed verification is fetched again;
+            // other errors are about `dest` and say nothing about the cache
+            let corrupt = matches!(e, Error::HashMismatch { .. } | Error::SizeMismatch { .. });
+            if !corrupt || !cache.is_discardable(pointer.oid()) {
+                return Err(e);
+            }
+            cache.remove(pointer.oid())?;
+            self.download_to_cache(cache, &pointer)?;
+            cache.checkout(&pointer, dest, mode)?;
+        }
+
+        Ok(())
+    }
+
+    /// Stream an object from the LFS server into the cache.
+    fn download_to_cache(&self, cache: &ObjectCache, pointer: &Pointer) -> Result<()> {
+        let mut writer = cache
+            .writer(pointer.oid())?
+            .with_source(ObjectSource::Download);
+        self.client.download_to_writer(pointer, &mut writer)?;
+        writer.finish()
+    }
+
     /// Get the remote URL from the repository.
     fn get_remote_url(repo: &Repository) -> Result<String> {
         // Try "origin" first
@@ -252,9 +425,7 @@ impl<'repo> LfsFilter<'repo> {
         }
 
         // Try any remote
-        let remotes = repo
-            .remotes()
-            .map_err(|e| crate::Error::InvalidUrl(format!("failed to list remotes: {}", e)))?;
+        let remotes = repo.remotes()?;
 
         for name in remotes.iter().flatten() {
             if let Ok(remote) = repo.find_remote(name) {
@@ -280,14 +451,8 @@ use git2::{Filter, FilterCheck, FilterMode, FilterSource};
 /// for all files matching `filter=lfs` in `.gitattributes`.
 struct GlobalLfsFilter {
     client: LfsClient,
-    /// Cache directory path (we can't hold ObjectCache directly due to thread safety)
-    cache_path: Option<std::path::PathBuf>,
-}
-
-impl GlobalLfsFilter {
-    fn get_cache(&self) -> Option<ObjectCache> {
-        self.cache_path.as_ref().map(|p| ObjectCache::new(p))
-    }
+    cache: Option<ObjectCache>,
+    extensions: ExtensionPipeline,
 }
 
 impl Filter for GlobalLfsFilter {
@@ -312,23 +477,31 @@ impl Filter for GlobalLfsFilter {
 
 impl GlobalLfsFilter {
     /// Clean: working tree -> repository (upload to LFS, return pointer)
+    ///
+    /// The filter source's path isn't available here, so extension commands
+    /// see an empty `%f`.
     fn clean(&self, content: &[u8]) -> Result<Vec<u8>> {
         // Already a pointer? Pass through
         if Pointer::is_pointer(content) {
             return Ok(content.to_vec());
         }
 
-        // Generate pointer
-        let pointer = Pointer::from_content(content);
+        // Run extensions and generate pointer
+        let (content, pointer) = clean_with_extensions(&self.extensions, "", content)?;
+        let content = &*content;
 
-        // Store in cache
-        if let Some(cache) = self.get_cache() {
-            let _ = cache.put_verified(&pointer, content);
+        // Store in cache, kept from eviction until marked as pushed
+        if let Some(cache) = &self.cache {
+            let _ = cache.put_verified_from(&pointer, content, ObjectSource::Clean);
         }
 
         // Upload to LFS server
         self.client.upload(&pointer, content)?;
 
+        if let Some(cache) = &self.cache {
+            let _ = cache.mark_pushed(pointer.oid());
+        }
+
         // Return pointer bytes
         Ok(pointer.encode_bytes())
     }
@@ -344,9 +517,9 @@ impl GlobalLfsFilter {
         let pointer = Pointer::parse(content)?;
 
         // Check cache first
-        if let Some(cache) = self.get_cache() {
+        if let Some(cache) = &self.cache {
             if let Some(cached) = cache.get_verified(&pointer) {
-                return Ok(cached);
+                return self.extensions.smudge("", &pointer, cached);
             }
         }
 
@@ -354,12 +527,26 @@ impl GlobalLfsFilter {
         let downloaded = self.client.download(&pointer)?;
 
         // Store in cache
-        if let Some(cache) = self.get_cache() {
-            let _ = cache.put_verified(&pointer, &downloaded);
+        if let Some(cache) = &self.cache {
+            let _ = cache.put_verified_from(&pointer, &downloaded, ObjectSource::Download);
         }
 
-        Ok(downloaded)
+        // Undo extensions recorded in the pointer
+        self.extensions.smudge("", &pointer, downloaded)
+    }
+}
+
+/// Run the clean extensions, borrowing the content when there are none.
+fn clean_with_extensions<'a>(
+    extensions: &ExtensionPipeline,
+    path: &str,
+    content: &'a [u8],
+) -> Result<(Cow<'a, [u8]>, Pointer)> {
+    if extensions.is_empty() {
+        return Ok((Cow::Borrowed(content), Pointer::from_content(content)));
     }
+    let (content, pointer) = extensions.clean(path, content)?;
+    Ok((Cow::Owned(content), pointer))
 }
 
 /// Handle to a registered LFS filter.
@@ -425,15 +612,74 @@ pub fn register_lfs_filter_with_cache(
     client: LfsClient,
     cache_path: Option<std::path::PathBuf>,
 ) -> Result<LfsFilterRegistration> {
-    let filter = GlobalLfsFilter { client, cache_path };
+    register_lfs_filter_with_extensions(
+        client,
+        cache_path.map(ObjectCache::new),
+        ExtensionPipeline::new(),
+    )
+}
+
+/// Register an LFS filter with a configured object cache.
+///
+/// Unlike [`register_lfs_filter_with_cache`], this keeps the cache's
+/// settings, such as a maximum size.
+///
+/// # Example
+///
+/// ```ignore
+/// use git2_lfs::{register_lfs_filter_with_object_cache, LfsClient, ObjectCache};
+///
+/// let client = LfsClient::new("https://github.com/owner/repo.git")?;
+/// let cache = ObjectCache::new("/shared/lfs/objects").with_max_size(50 << 30);
+///
+/// let _reg = register_lfs_filter_with_object_cache(client, cache)?;
+/// ```
+pub fn register_lfs_filter_with_object_cache(
+    client: LfsClient,
+    cache: ObjectCache,
+) -> Result<LfsFilterRegistration> {
+    register_lfs_filter_with_extensions(client, Some(cache), ExtensionPipeline::new())
+}
 
+/// Register an LFS filter that also runs pointer extensions.
+///
+/// Clean runs the extensions' clean commands before hashing; smudge undoes
+/// the extensions recorded in each pointer. The filter can't see the path
+/// being filtered, so `%f` in extension commands is empty.
+///
+/// # Example
+///
+/// ```ignore
+/// use git2_lfs::{register_lfs_filter_with_extensions, ExtensionPipeline, LfsClient};
+///
+/// let client = LfsClient::from_repo(&repo)?;
+/// let extensions = ExtensionPipeline::from_config(&repo.config()?)?;
+///
+/// let _reg = register_lfs_filter_with_extensions(client, None, extensions)?;
+/// ```
+pub fn register_lfs_filter_with_extensions(
+    client: LfsClient,
+    cache: Option<ObjectCache>,
+    extensions: ExtensionPipeline,
+) -> Result<LfsFilterRegistration> {
+    let filter = GlobalLfsFilter {
+        client,
+        cache,
+        extensions,
+    };
     let registration = git2::filter_register(
         "lfs",
         "filter=lfs",
         git2::filter_priority::DRIVER,
         filter,
     )
-    .map_err(|e| crate::Error::Git(format!("failed to register filter: {}", e)))?;
+    .map_err(|e| {
+        git2::Error::new(
+            e.code(),
+            e.class(),
+            format!("failed to register filter: {}", e.message()),
+        )
+    })?;
 
     Ok(LfsFilterRegistration { _inner: registration })
 }
@@ -441,7 +687,8 @@ pub fn register_lfs_filter_with_cache(
 /// Register an LFS filter for a specific repository.
 ///
 /// This is a convenience function that reads the LFS configuration from the
-/// repository and sets up the cache in the standard location.
+/// repository, sets up the cache from its storage settings (`lfs.storage`
+/// and alternates), and loads pointer extensions from `lfs.extension.*`.
 ///
 /// # Arguments
 ///
@@ -460,9 +707,10 @@ pub fn register_lfs_filter_with_cache(
 /// ```
 pub fn register_lfs_filter_for_repo(repo: &Repository) -> Result<LfsFilterRegistration> {
     let client = LfsClient::from_repo(repo)?;
-    let cache_path = Some(repo.path().join("lfs").join("objects"));
+    let cache = ObjectCache::from_repo(repo)?;
+    let extensions = LfsFilter::repo_extensions(repo)?;
 
-    register_lfs_filter_with_cache(client, cache_path)
+    register_lfs_filter_with_extensions(client, Some(cache), extensions)
 }
 
 #[cfg(test)]
@@ -593,7 +841,8 @@ mod tests {
         let client = LfsClient::new("https://github.com/test/repo.git").unwrap();
         let filter = GlobalLfsFilter {
             client,
-            cache_path: None,
+            cache: None,
+            extensions: ExtensionPipeline::new(),
         };
 
         // Smudge non-pointer content should pass through
@@ -606,4 +855,63 @@ mod tests {
         let result = filter.clean(pointer_content).unwrap();
         assert_eq!(result, pointer_content);
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_smudge_runs_configured_extensions() {
+        let (td, repo) = repo_init();
+        {
+            let mut config = repo.config().unwrap();
+            config
+                .set_str("lfs.extension.upper.clean", "tr a-z A-Z")
+                .unwrap();
+            config
+                .set_str("lfs.extension.upper.smudge", "tr A-Z a-z")
+                .unwrap();
+        }
+
+        // Unreachable server: the object must come from the cache
+        let client = LfsClient::new("http://127.0.0.1:9/test/repo.git").unwrap();
+        let filter = LfsFilter::with_client(&repo, client);
+        assert_eq!(filter.extensions().extensions().len(), 1);
+
+        let (stored, pointer) = filter.extensions().clean("doc.txt", b"layers\n").unwrap();
+        assert_eq!(stored, b"LAYERS\n");
+        filter.cache().unwrap().put(pointer.oid(), &stored).unwrap();
+
+        let smudged = filter.smudge("doc.txt", &pointer.encode_bytes()).unwrap();
+        assert_eq!(smudged, b"layers\n");
+
+        let dest = td.path().join("doc.txt");
+        filter
+            .smudge_to_file(&pointer.encode_bytes(), &dest, CheckoutMode::Hardlink)
+            .unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), b"layers\n");
+
+        // Without the extension configured, the pointer can't be smudged
+        let filter = filter.with_extensions(ExtensionPipeline::new());
+        assert!(filter.smudge("doc.txt", &pointer.encode_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_malformed_extension_config() {
+        let (_td, repo) = repo_init();
+        repo.config()
+            .unwrap()
+            .set_str("lfs.extension.upper.clean", "tr a-z A-Z")
+            .unwrap();
+
+        // The smudge command is missing
+        let client = LfsClient::new("https://github.com/test/repo.git").unwrap();
+        match LfsFilter::try_with_client(&repo, client.clone()) {
+            Err(Error::Extension { name, .. }) => assert_eq!(name, "upper"),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("malformed extension config was ignored"),
+        }
+        assert!(LfsFilter::try_without_cache(&repo, client.clone()).is_err());
+
+        // Like unreadable storage settings, it is skipped by `with_client`
+        let filter = LfsFilter::with_client(&repo, client);
+        assert!(filter.extensions().is_empty());
+    }
 }
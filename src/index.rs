@@ -0,0 +1,578 @@
+//! Persistent index of cached objects.
+//!
+//! The index is an append-only log of JSON records stored next to the
+//! objects in `index.jsonl`. Each process replays the log into memory and
+//! afterwards only reads records appended since its last look, so queries
+//! don't need to walk the object directory. The log is compacted once it
+//! grows well beyond the number of live entries, and rebuilt from the
+//! objects on disk if it is missing.
+//!
+//! Appends take a shared lock on `index.lock` and compaction an exclusive
+//! one, so several processes can use the same cache.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cache::{is_object_path, walkdir};
+use crate::Oid;
+
+/// Compact once the log has this many more records than live entries.
+const COMPACT_SLACK: u64 = 1000;
+
+/// How an object came to be in the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ObjectSource {
+    /// Produced locally by the clean filter; the server may not have it yet.
+    Clean,
+    /// Downloaded from the LFS server.
+    Download,
+    /// Stored without a source, or found on disk when rebuilding the index.
+    Unknown,
+}
+
+/// Metadata recorded for a cached object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// Object size in bytes.
+    pub size: u64,
+    /// When the object was first stored.
+    pub inserted: SystemTime,
+    /// When the object was last stored or read.
+    pub last_access: SystemTime,
+    /// How the object arrived in the cache.
+    pub source: ObjectSource,
+    /// Whether the object is known to be on the server.
+    ///
+    /// Downloaded objects are always pushed. Cleaned objects are pushed once
+    /// [`ObjectCache::mark_pushed`](crate::ObjectCache::mark_pushed) is
+    /// called after uploading them.
+    pub pushed: bool,
+}
+
+/// One line of the index log.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Record {
+    Add {
+        oid: String,
+        size: u64,
+        time: u64,
+        source: ObjectSource,
+    },
+    Access {
+        oid: String,
+        time: u64,
+    },
+    Pushed {
+        oid: String,
+    },
+    Remove {
+        oid: String,
+    },
+}
+
+/// Handle to the index of one object directory.
+///
+/// Clones share the in-memory state.
+#[derive(Debug, Clone)]
+pub(crate) struct CacheIndex {
+    objects_dir: PathBuf,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Entries keyed by hex OID, with the log position of their last access.
+    entries: HashMap<String, (IndexEntry, u64)>,
+    /// Bytes of the log replayed so far.
+    offset: u64,
+    /// Identity of the replayed log file, to notice compaction elsewhere.
+    file_id: Option<(u64, u64)>,
+    /// Records replayed so far.
+    records: u64,
+    lock_file: Option<File>,
+}
+
+impl CacheIndex {
+    pub(crate) fn new(objects_dir: &Path) -> Self {
+        CacheIndex {
+            objects_dir: objects_dir.to_path_buf(),
+            state: Arc::new(Mutex::new(State::default())),
+        }
+    }
+
+    fn log_path(&self) -> PathBuf {
+        self.objects_dir.join("index.jsonl")
+    }
+
+    fn lock_path(&self) -> PathBuf {
+        self.objects_dir.join("index.lock")
+    }
+
+    /// Record a newly stored object, or a fresh copy of an existing one.
+    pub(crate) fn add(&self, oid: &Oid, size: u64, source: ObjectSource) -> io::Result<()> {
+        self.append(Record::Add {
+            oid: oid.to_hex(),
+            size,
+            time: now_millis(),
+            source,
+        })
+    }
+
+    /// Record a read of a stored object.
+    ///
+    /// Objects missing from the index (e.g. stored by another tool) are
+    /// added with an unknown source.
+    pub(crate) fn access(&self, oid: &Oid, size: u64) -> io::Result<()> {
+        let hex = oid.to_hex();
+        let time = now_millis();
+        self.update(|state| {
+            if state.entries.contains_key(&hex) {
+                Record::Access { oid: hex, time }
+            } else {
+                Record::Add {
+                    oid: hex,
+                    size,
+                    time,
+                    source: ObjectSource::Unknown,
+                }
+            }
+        })
+    }
+
+    /// Record that an object is on the server.
+    pub(crate) fn mark_pushed(&self, oid: &Oid) -> io::Result<()> {
+        self.append(Record::Pushed { oid: oid.to_hex() })
+    }
+
+    /// Record that an object was removed.
+    pub(crate) fn remove(&self, oid: &Oid) -> io::Result<()> {
+        self.append(Record::Remove { oid: oid.to_hex() })
+    }
+
+    /// Get the entry for an object.
+    pub(crate) fn get(&self, oid: &Oid) -> Option<IndexEntry> {
+        self.query(|state| state.entries.get(&oid.to_hex()).map(|(entry, _)| *entry))
+    }
+
+    /// Number of indexed objects.
+    pub(crate) fn len(&self) -> usize {
+        self.query(|state| state.entries.len())
+    }
+
+    /// Total size of indexed objects in bytes.
+    pub(crate) fn total_size(&self) -> u64 {
+        self.query(|state| state.entries.values().map(|(entry, _)| entry.size).sum())
+    }
+
+    /// All entries matching `filter`, least recently used first.
+    pub(crate) fn entries(&self, filter: impl Fn(&IndexEntry) -> bool) -> Vec<(Oid, IndexEntry)> {
+        self.query(|state| {
+            let mut entries: Vec<_> = state
+                .entries
+                .iter()
+                .filter(|(_, (entry, _))| filter(entry))
+                .filter_map(|(hex, (entry, seq))| Some((*seq, Oid::from_hex(hex).ok()?, *entry)))
+                .collect();
+            entries.sort_by_key(|e| e.0);
+            entries
+                .into_iter()
+                .map(|(_, oid, entry)| (oid, entry))
+                .collect()
+        })
+    }
+
+    /// Discard the log and rebuild it from the objects on disk.
+    ///
+    /// Every object is recorded with an unknown source and its file's
+    /// modification time as both insertion and access time.
+    pub(crate) fn rebuild(&self) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let lock_file = self.lock_file(&mut state)?;
+        lock_file.lock()?;
+        let result = self.write_rebuilt(&mut state);
+        let _ = state.lock_file.as_ref().map(File::unlock);
+        result
+    }
+
+    /// Run a read-only query against the up-to-date index.
+    ///
+    /// Errors reading the log fall back to the last state seen, since the
+    /// index is only an accelerator for information also on disk.
+    fn query<T>(&self, f: impl FnOnce(&State) -> T) -> T {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if self.objects_dir.is_dir() {
+            let _ = self.sync(&mut state);
+        }
+        f(&state)
+    }
+
+    fn append(&self, record: Record) -> io::Result<()> {
+        self.update(|_| record)
+    }
+
+    /// Append the record built by `f` from the up-to-date state.
+    fn update(&self, f: impl FnOnce(&State) -> Record) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        self.sync(&mut state)?;
+
+        let mut line = serde_json::to_vec(&f(&state)).map_err(io::Error::other)?;
+        line.push(b'\n');
+
+        let lock_file = self.lock_file(&mut state)?;
+        lock_file.lock_shared()?;
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.log_path())
+            .and_then(|mut log| log.write_all(&line))
+            .and_then(|()| self.replay(&mut state));
+
+        if result.is_ok()
+            && state.records > 2 * state.entries.len() as u64 + COMPACT_SLACK
+            && lock_file_for(&state).try_lock().is_ok()
+        {
+            // Best effort; another process may be appending or compacting
+            let _ = self
+                .replay(&mut state)
+                .and_then(|()| self.compact(&mut state));
+        }
+
+        let _ = lock_file_for(&state).unlock();
+        result
+    }
+
+    /// Bring the in-memory state up to date, creating the log if missing.
+    fn sync(&self, state: &mut State) -> io::Result<()> {
+        if !self.log_path().exists() {
+            let lock_file = self.lock_file(state)?;
+            lock_file.lock()?;
+            // Another process may have created it while we waited
+            let result = if self.log_path().exists() {
+                Ok(())
+            } else {
+                self.write_rebuilt(state)
+            };
+            let _ = lock_file_for(state).unlock();
+            result?;
+        }
+
+        let lock_file = self.lock_file(state)?;
+        lock_file.lock_shared()?;
+        let result = self.replay(state);
+        let _ = lock_file_for(state).unlock();
+        result
+    }
+
+    /// Read records appended to the log since the last replay.
+    fn replay(&self, state: &mut State) -> io::Result<()> {
+        let mut file = match File::open(self.log_path()) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                state.reset(None);
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+
+        let meta = file.metadata()?;
+        let id = file_id(&meta);
+        if id != state.file_id || meta.len() < state.offset {
+            // Compacted or rebuilt since the last replay
+            state.reset(id);
+        }
+
+        file.seek(SeekFrom::Start(state.offset))?;
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+
+        // Leave a partially written last line for the next replay
+        let end = match buf.iter().rposition(|&b| b == b'\n') {
+            Some(pos) => pos + 1,
+            None => return Ok(()),
+        };
+        for line in buf[..end].split(|&b| b == b'\n') {
+            if line.is_empty() {
+                continue;
+            }
+            // Skip lines we can't parse, e.g. torn writes after a crash
+            if let Ok(record) = serde_json::from_slice(line) {
+                state.apply(record);
+            }
+        }
+        state.offset += end as u64;
+
+        Ok(())
+    }
+
+    /// Rewrite the log with one set of records per live entry.
+    ///
+    /// Must be called with the exclusive lock held.
+    fn compact(&self, state: &mut State) -> io::Result<()> {
+        let mut entries: Vec<_> = state.entries.iter().collect();
+        entries.sort_by_key(|(_, (_, seq))| *seq);
+
+        let mut records = Vec::new();
+        for (hex, (entry, _)) in entries {
+            records.push(Record::Add {
+                oid: hex.clone(),
+                size: entry.size,
+                time: to_millis(entry.inserted),
+                source: entry.source,
+            });
+            records.push(Record::Access {
+                oid: hex.clone(),
+                time: to_millis(entry.last_access),
+            });
+            if entry.pushed && entry.source != ObjectSource::Download {
+                records.push(Record::Pushed { oid: hex.clone() });
+            }
+        }
+
+        self.write_log(state, &records)
+    }
+
+    /// Write a fresh log from the objects on disk.
+    ///
+    /// Must be called with the exclusive lock held.
+    fn write_rebuilt(&self, state: &mut State) -> io::Result<()> {
+        let mut found: Vec<_> = walkdir(self.objects_dir.clone())
+            .filter(|path| is_object_path(path))
+            .filter_map(|path| {
+                let meta = fs::metadata(&path).ok()?;
+                let hex = path.file_name()?.to_str()?.to_string();
+                Some((hex, meta.len(), meta.modified().unwrap_or(UNIX_EPOCH)))
+            })
+            .collect();
+        // Oldest first, so access order survives the rebuild
+        found.sort_by_key(|f| f.2);
+
+        let records: Vec<_> = found
+            .into_iter()
+            .map(|(oid, size, modified)| Record::Add {
+                oid,
+                size,
+                time: to_millis(modified),
+                source: ObjectSource::Unknown,
+            })
+            .collect();
+
+        self.write_log(state, &records)
+    }
+
+    /// Atomically replace the log with `records` and replay it.
+    fn write_log(&self, state: &mut State, records: &[Record]) -> io::Result<()> {
+        let mut content = Vec::new();
+        for record in records {
+            serde_json::to_writer(&mut content, record).map_err(io::Error::other)?;
+            content.push(b'\n');
+        }
+
+        let temp_path = self.objects_dir.join("index.jsonl.new");
+        let result = File::create(&temp_path)
+            .and_then(|mut file| file.write_all(&content).and_then(|()| file.sync_all()))
+            .and_then(|()| fs::rename(&temp_path, self.log_path()));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        result?;
+
+        state.reset(None);
+        self.replay(state)
+    }
+
+    /// Open the lock file, creating the object directory if needed.
+    fn lock_file<'a>(&self, state: &'a mut State) -> io::Result<&'a File> {
+        if state.lock_file.is_none() {
+            fs::create_dir_all(&self.objects_dir)?;
+            let file = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(self.lock_path())?;
+            state.lock_file = Some(file);
+        }
+        Ok(lock_file_for(state))
+    }
+}
+
+/// The lock file of a state that has already opened it.
+fn lock_file_for(state: &State) -> &File {
+    state.lock_file.as_ref().expect("lock file is open")
+}
+
+impl State {
+    /// Forget everything replayed so far.
+    fn reset(&mut self, file_id: Option<(u64, u64)>) {
+        self.entries.clear();
+        self.offset = 0;
+        self.records = 0;
+        self.file_id = file_id;
+    }
+
+    fn apply(&mut self, record: Record) {
+        self.records += 1;
+        let seq = self.records;
+
+        match record {
+            Record::Add {
+                oid,
+                size,
+                time,
+                source,
+            } => {
+                let time = from_millis(time);
+                let (entry, last_seq) = self.entries.entry(oid).or_insert((
+                    IndexEntry {
+                        size,
+                        inserted: time,
+                        last_access: time,
+                        source,
+                        pushed: false,
+                    },
+                    seq,
+                ));
+                entry.size = size;
+                entry.last_access = time;
+                if entry.source == ObjectSource::Unknown {
+                    entry.source = source;
+                }
+                entry.pushed |= source == ObjectSource::Download;
+                *last_seq = seq;
+            }
+            Record::Access { oid, time } => {
+                if let Some((entry, last_seq)) = self.entries.get_mut(&oid) {
+                    entry.last_access = from_millis(time);
+                    *last_seq = seq;
+                }
+            }
+            Record::Pushed { oid } => {
+                if let Some((entry, _)) = self.entries.get_mut(&oid) {
+                    entry.pushed = true;
+                }
+            }
+            Record::Remove { oid } => {
+                self.entries.remove(&oid);
+            }
+        }
+    }
+}
+
+/// Identity of a file, to detect when a path has been replaced.
+#[cfg(unix)]
+fn file_id(meta: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((meta.dev(), meta.ino()))
+}
+
+/// Replacement is only detected by the log shrinking.
+#[cfg(not(unix))]
+fn file_id(_meta: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+fn now_millis() -> u64 {
+    to_millis(SystemTime::now())
+}
+
+fn to_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn from_millis(millis: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_records_round_trip() {
+        let td = TempDir::new().unwrap();
+        let index = CacheIndex::new(td.path());
+
+        let a = Oid::from_content(b"a");
+        let b = Oid::from_content(b"b");
+        index.add(&a, 10, ObjectSource::Clean).unwrap();
+        index.add(&b, 20, ObjectSource::Download).unwrap();
+        index.access(&a, 10).unwrap();
+
+        // A second handle replays the log from disk
+        let other = CacheIndex::new(td.path());
+        assert_eq!(other.len(), 2);
+        assert_eq!(other.total_size(), 30);
+        assert_eq!(other.get(&a).unwrap().source, ObjectSource::Clean);
+        assert!(!other.get(&a).unwrap().pushed);
+        assert!(other.get(&b).unwrap().pushed);
+
+        // Least recently used first
+        let order: Vec<_> = other.entries(|_| true).into_iter().map(|e| e.0).collect();
+        assert_eq!(order, vec![b.clone(), a.clone()]);
+
+        // Appends through one handle are picked up by the other
+        index.mark_pushed(&a).unwrap();
+        index.remove(&b).unwrap();
+        assert!(other.get(&a).unwrap().pushed);
+        assert!(other.get(&b).is_none());
+    }
+
+    #[test]
+    fn test_torn_line_is_ignored() {
+        let td = TempDir::new().unwrap();
+        let index = CacheIndex::new(td.path());
+        let a = Oid::from_content(b"a");
+        index.add(&a, 1, ObjectSource::Unknown).unwrap();
+
+        let mut log = OpenOptions::new()
+            .append(true)
+            .open(index.log_path())
+            .unwrap();
+        log.write_all(b"{\"op\":\"add\",\"oid\":").unwrap();
+        log.write_all(b"garbage\n").unwrap();
+
+        let other = CacheIndex::new(td.path());
+        assert_eq!(other.len(), 1);
+        assert!(other.get(&a).is_some());
+    }
+
+    #[test]
+    fn test_compaction() {
+        let td = TempDir::new().unwrap();
+        let index = CacheIndex::new(td.path());
+        let a = Oid::from_content(b"a");
+        let b = Oid::from_content(b"b");
+        index.add(&a, 1, ObjectSource::Clean).unwrap();
+        index.add(&b, 2, ObjectSource::Unknown).unwrap();
+        index.mark_pushed(&a).unwrap();
+
+        let reader = CacheIndex::new(td.path());
+        assert_eq!(reader.len(), 2);
+
+        for _ in 0..COMPACT_SLACK + 10 {
+            index.access(&b, 2).unwrap();
+        }
+        let lines = fs::read_to_string(index.log_path())
+            .unwrap()
+            .lines()
+            .count();
+        assert!(lines < COMPACT_SLACK as usize);
+
+        // A handle that replayed the old log notices the rewrite
+        let order: Vec<_> = reader.entries(|_| true).into_iter().map(|e| e.0).collect();
+        assert_eq!(order, vec![a.clone(), b.clone()]);
+        assert!(reader.get(&a).unwrap().pushed);
+        assert_eq!(reader.get(&a).unwrap().source, ObjectSource::Clean);
+    }
+}
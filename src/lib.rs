@@ -30,28 +30,52 @@
 //! ```
 
 mod batch;
+mod builder;
 mod cache;
+mod canonical;
 mod client;
+mod credentials;
 mod error;
+mod extension;
+mod index;
 mod oid;
 mod pointer;
+mod throttle;
+mod tls;
+mod transport;
 
+#[cfg(feature = "async")]
+mod async_client;
+#[cfg(feature = "git2-integration")]
+mod config;
 #[cfg(feature = "git2-integration")]
 mod filter;
 #[cfg(feature = "git2-integration")]
 mod repo;
 
 pub use batch::{Action, BatchObject, BatchRequest, BatchRequestObject, BatchResponse, Operation};
-pub use cache::{CacheWriter, ObjectCache};
-pub use client::LfsClient;
+pub use builder::LfsClientBuilder;
+pub use cache::{CacheWriter, CheckoutMode, ObjectCache};
+pub use canonical::{DiagnosticKind, PointerDiagnostic};
+pub use client::{AccessMode, LfsClient, ObjectStatus, DEFAULT_BATCH_SIZE};
+pub use credentials::CredentialSource;
 pub use error::{Error, Result};
+pub use extension::{Extension, ExtensionPipeline};
+pub use index::{IndexEntry, ObjectSource};
 pub use oid::{HashingWriter, Oid};
-pub use pointer::Pointer;
+pub use pointer::{Pointer, PointerExtension};
+pub use transport::{
+    HttpMethod, HttpRequest, HttpResponse, HttpTransport, RedirectPolicy, RequestBody,
+    UreqTransport, DEFAULT_MAX_REDIRECTS,
+};
 
+#[cfg(feature = "async")]
+pub use async_client::{AsyncLfsClient, DEFAULT_CONCURRENCY};
 #[cfg(feature = "git2-integration")]
 pub use filter::{
     register_lfs_filter, register_lfs_filter_for_repo, register_lfs_filter_with_cache,
-    LfsFilter, LfsFilterRegistration,
+    register_lfs_filter_with_extensions, register_lfs_filter_with_object_cache, LfsFilter,
+    LfsFilterRegistration,
 };
 #[cfg(feature = "git2-integration")]
 pub use repo::LfsRepo;
@@ -5,6 +5,7 @@
 
 use std::io::Read;
 
+use crate::canonical::{self, PointerDiagnostic};
 use crate::{Error, Oid, Result};
 
 /// LFS specification version.
@@ -13,6 +14,101 @@ pub const LFS_SPEC_V1: &str = "https://git-lfs.github.com/spec/v1";
 /// Maximum size of an LFS pointer file (1KB).
 pub const MAX_POINTER_SIZE: usize = 1024;
 
+/// Highest priority a pointer extension can have (priorities are one digit).
+pub const MAX_EXTENSION_PRIORITY: u8 = 9;
+
+/// Whether `name` is a valid extension name: non-empty ASCII letters,
+/// digits and underscores.
+pub(crate) fn is_extension_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+/// A pointer extension entry: `ext-<priority>-<name> sha256:<oid>`.
+///
+/// Records that the content was passed through the named extension's clean
+/// command, and the OID of the content that went into it. Extensions run in
+/// ascending priority order; the pointer's own OID is that of the final
+/// output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PointerExtension {
+    name: String,
+    priority: u8,
+    oid: Oid,
+}
+
+impl PointerExtension {
+    /// Create an extension entry.
+    ///
+    /// The name must be non-empty and contain only ASCII letters, digits and
+    /// underscores, and the priority must be at most
+    /// [`MAX_EXTENSION_PRIORITY`].
+    pub fn new(name: &str, priority: u8, oid: Oid) -> Result<Self> {
+        if !is_extension_name(name) {
+            return Err(Error::malformed(format!(
+                "invalid extension name: {:?}",
+                name
+            )));
+        }
+        if priority > MAX_EXTENSION_PRIORITY {
+            return Err(Error::malformed(format!(
+                "invalid extension priority: {}",
+                priority
+            )));
+        }
+
+        Ok(PointerExtension {
+            name: name.to_string(),
+            priority,
+            oid,
+        })
+    }
+
+    /// Get the extension name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the extension priority.
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    /// Get the OID of the content that was input to the extension.
+    pub fn oid(&self) -> &Oid {
+        &self.oid
+    }
+
+    /// Parse an `ext-<priority>-<name>` key and its `sha256:<oid>` value.
+    pub(crate) fn parse(key: &str, value: &str) -> Result<Self> {
+        let rest = key.strip_prefix("ext-").unwrap_or(key);
+        let (priority, name) = rest
+            .split_once('-')
+            .filter(|(priority, _)| priority.len() == 1)
+            .ok_or_else(|| Error::malformed(format!("invalid extension key: {}", key)))?;
+        let priority = priority
+            .parse()
+            .map_err(|_| Error::malformed(format!("invalid extension key: {}", key)))?;
+        let oid = value
+            .strip_prefix("sha256:")
+            .and_then(|hex| Oid::from_hex(hex).ok())
+            .ok_or_else(|| {
+                Error::malformed(format!("invalid extension OID for {}: {}", key, value))
+            })?;
+
+        Self::new(name, priority, oid)
+    }
+
+    /// Encode as a pointer line, without the trailing newline.
+    fn encode(&self) -> String {
+        format!(
+            "ext-{}-{} sha256:{}",
+            self.priority,
+            self.name,
+            self.oid.to_hex()
+        )
+    }
+}
+
 /// An LFS pointer representing a file stored in LFS.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Pointer {
@@ -20,22 +116,45 @@ pub struct Pointer {
     oid: Oid,
     /// The size of the file in bytes.
     size: u64,
+    /// Extensions applied to the content, in ascending priority order.
+    extensions: Vec<PointerExtension>,
 }
 
 impl Pointer {
     /// Create a new pointer with the given OID and size.
     pub fn new(oid: Oid, size: u64) -> Self {
-        Pointer { oid, size }
+        Pointer {
+            oid,
+            size,
+            extensions: Vec::new(),
+        }
+    }
+
+    /// Set the extensions applied to the content.
+    ///
+    /// They are stored in ascending priority order. Fails if two extensions
+    /// share a priority.
+    pub fn with_extensions(mut self, mut extensions: Vec<PointerExtension>) -> Result<Self> {
+        extensions.sort_by_key(|ext| ext.priority);
+        if let Some(pair) = extensions
+            .windows(2)
+            .find(|pair| pair[0].priority == pair[1].priority)
+        {
+            return Err(Error::malformed(format!(
+                "duplicate extension priority: {}",
+                pair[0].priority
+            )));
+        }
+
+        self.extensions = extensions;
+        Ok(self)
     }
 
     /// Create a pointer from file content.
     ///
     /// This computes the SHA256 hash of the content.
     pub fn from_content(content: &[u8]) -> Self {
-        Pointer {
-            oid: Oid::from_content(content),
-            size: content.len() as u64,
-        }
+        Pointer::new(Oid::from_content(content), content.len() as u64)
     }
 
     /// Create a pointer by streaming content from a reader.
@@ -44,26 +163,31 @@ impl Pointer {
     /// the entire content into memory at once.
     pub fn from_reader<R: Read>(reader: R) -> std::io::Result<Self> {
         let (oid, size) = Oid::from_reader(reader)?;
-        Ok(Pointer { oid, size })
+        Ok(Pointer::new(oid, size))
     }
 
     /// Parse a pointer from its text representation.
+    ///
+    /// Extension lines must have distinct priorities and appear in ascending
+    /// priority order.
+    ///
+    /// Fails with [`Error::MalformedPointer`], carrying the offending line
+    /// where there is one, or [`Error::UnsupportedVersion`].
     pub fn parse(content: &[u8]) -> Result<Self> {
         // Check size first
         if content.len() > MAX_POINTER_SIZE {
-            return Err(Error::InvalidPointer(
-                "content too large to be a pointer".into(),
-            ));
+            return Err(Error::malformed("content too large to be a pointer"));
         }
 
-        let text = std::str::from_utf8(content)
-            .map_err(|_| Error::InvalidPointer("invalid UTF-8".into()))?;
+        let text = std::str::from_utf8(content).map_err(|_| Error::malformed("invalid UTF-8"))?;
 
         let mut version_found = false;
         let mut oid: Option<Oid> = None;
         let mut size: Option<u64> = None;
+        let mut extensions: Vec<PointerExtension> = Vec::new();
 
-        for line in text.lines() {
+        for (i, line) in text.lines().enumerate() {
+            let n = i + 1;
             let line = line.trim();
             if line.is_empty() {
                 continue;
@@ -74,31 +198,72 @@ impl Pointer {
                 if ver == LFS_SPEC_V1 || ver == "https://hawser.github.com/spec/v1" {
                     version_found = true;
                 } else {
-                    return Err(Error::InvalidPointer(format!(
-                        "unsupported version: {}",
-                        ver
-                    )));
+                    return Err(Error::UnsupportedVersion(ver.to_string()));
                 }
             } else if let Some(rest) = line.strip_prefix("oid sha256:") {
-                oid = Some(Oid::from_hex(rest.trim())?);
+                oid = Some(
+                    Oid::from_hex(rest.trim())
+                        .map_err(|_| Error::malformed_at(n, format!("invalid oid: {}", rest)))?,
+                );
             } else if let Some(rest) = line.strip_prefix("size ") {
                 size = Some(
                     rest.trim()
                         .parse()
-                        .map_err(|_| Error::InvalidPointer("invalid size".into()))?,
+                        .map_err(|_| Error::malformed_at(n, format!("invalid size: {}", rest)))?,
                 );
+            } else if line.starts_with("ext-") {
+                let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+                let ext = PointerExtension::parse(key, value.trim()).map_err(|e| match e {
+                    Error::MalformedPointer { message, .. } => Error::malformed_at(n, message),
+                    other => other,
+                })?;
+                if let Some(prev) = extensions.last() {
+                    if ext.priority <= prev.priority {
+                        return Err(Error::malformed_at(
+                            n,
+                            format!("extension {} out of priority order", key),
+                        ));
+                    }
+                }
+                extensions.push(ext);
             }
         }
 
         if !version_found {
-            return Err(Error::InvalidPointer("missing version".into()));
+            return Err(Error::malformed("missing version"));
         }
 
         match (oid, size) {
-            (Some(oid), Some(size)) => Ok(Pointer { oid, size }),
-            (None, _) => Err(Error::InvalidPointer("missing oid".into())),
-            (_, None) => Err(Error::InvalidPointer("missing size".into())),
+            (Some(oid), Some(size)) => Ok(Pointer {
+                oid,
+                size,
+                extensions,
+            }),
+            (None, _) => Err(Error::malformed("missing oid")),
+            (_, None) => Err(Error::malformed("missing size")),
+        }
+    }
+
+    /// Parse a pointer, requiring the canonical format from the spec.
+    ///
+    /// Fails with [`Error::NonCanonicalPointer`] listing every problem found
+    /// (see [`Pointer::validate_canonical`]).
+    pub fn parse_strict(content: &[u8]) -> Result<Self> {
+        let diagnostics = Self::validate_canonical(content);
+        if !diagnostics.is_empty() {
+            return Err(Error::NonCanonicalPointer(diagnostics));
         }
+        Self::parse(content)
+    }
+
+    /// Check content against the canonical pointer format.
+    ///
+    /// Returns every deviation found, such as keys out of order, CRLF line
+    /// endings, trailing whitespace, leading zeros in the size or a missing
+    /// final newline. An empty list means the content is a canonical pointer,
+    /// exactly as [`Pointer::encode`] would write it.
+    pub fn validate_canonical(content: &[u8]) -> Vec<PointerDiagnostic> {
+        canonical::validate(content)
     }
 
     /// Check if content looks like an LFS pointer.
@@ -120,14 +285,27 @@ impl Pointer {
         self.size
     }
 
+    /// Get the extensions applied to the content, in priority order.
+    pub fn extensions(&self) -> &[PointerExtension] {
+        &self.extensions
+    }
+
     /// Encode the pointer to its text representation.
+    ///
+    /// Extension lines go between `version` and `oid`, in priority order,
+    /// matching the key order git-lfs writes.
     pub fn encode(&self) -> String {
-        format!(
-            "version {}\noid sha256:{}\nsize {}\n",
-            LFS_SPEC_V1,
+        let mut out = format!("version {}\n", LFS_SPEC_V1);
+        for ext in &self.extensions {
+            out.push_str(&ext.encode());
+            out.push('\n');
+        }
+        out.push_str(&format!(
+            "oid sha256:{}\nsize {}\n",
             self.oid.to_hex(),
             self.size
-        )
+        ));
+        out
     }
 
     /// Encode the pointer to bytes.
@@ -219,4 +397,126 @@ mod tests {
         let large = vec![b'x'; 2000];
         assert!(!Pointer::is_pointer(&large));
     }
+
+    const OID_A: &str = "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393";
+    const OID_B: &str = "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f";
+
+    #[test]
+    fn test_pointer_extensions_roundtrip() {
+        let text = format!(
+            "version https://git-lfs.github.com/spec/v1\n\
+             ext-0-foo sha256:{}\n\
+             ext-1-bar sha256:{}\n\
+             oid sha256:{}\n\
+             size 12345\n",
+            OID_A, OID_B, OID_A
+        );
+
+        let pointer = Pointer::parse(text.as_bytes()).unwrap();
+        let names: Vec<_> = pointer.extensions().iter().map(|e| e.name()).collect();
+        assert_eq!(names, ["foo", "bar"]);
+        assert_eq!(pointer.extensions()[1].priority(), 1);
+        assert_eq!(pointer.extensions()[1].oid().to_hex(), OID_B);
+
+        assert_eq!(pointer.encode(), text);
+    }
+
+    #[test]
+    fn test_pointer_extensions_canonical_order() {
+        let oid = Oid::from_hex(OID_A).unwrap();
+        let pointer = Pointer::new(oid.clone(), 1)
+            .with_extensions(vec![
+                PointerExtension::new("second", 5, oid.clone()).unwrap(),
+                PointerExtension::new("first", 2, oid.clone()).unwrap(),
+            ])
+            .unwrap();
+
+        let encoded = pointer.encode();
+        let first = encoded.find("ext-2-first").unwrap();
+        let second = encoded.find("ext-5-second").unwrap();
+        assert!(first < second && second < encoded.find("oid ").unwrap());
+
+        let duplicate = Pointer::new(oid.clone(), 1).with_extensions(vec![
+            PointerExtension::new("a", 1, oid.clone()).unwrap(),
+            PointerExtension::new("b", 1, oid.clone()).unwrap(),
+        ]);
+        assert!(duplicate.is_err());
+    }
+
+    #[test]
+    fn test_pointer_extensions_invalid() {
+        let parse = |ext: &str| {
+            let text = format!(
+                "version https://git-lfs.github.com/spec/v1\n{}oid sha256:{}\nsize 1\n",
+                ext, OID_A
+            );
+            Pointer::parse(text.as_bytes())
+        };
+
+        // Out of order and duplicate priorities
+        assert!(parse(&format!(
+            "ext-1-a sha256:{}\next-0-b sha256:{}\n",
+            OID_A, OID_A
+        ))
+        .is_err());
+        assert!(parse(&format!(
+            "ext-1-a sha256:{}\next-1-b sha256:{}\n",
+            OID_A, OID_A
+        ))
+        .is_err());
+
+        // Malformed keys and values
+        assert!(parse(&format!("ext-10-a sha256:{}\n", OID_A)).is_err());
+        assert!(parse(&format!("ext-x-a sha256:{}\n", OID_A)).is_err());
+        assert!(parse(&format!("ext-1- sha256:{}\n", OID_A)).is_err());
+        assert!(parse(&format!("ext-1-a-b sha256:{}\n", OID_A)).is_err());
+        assert!(parse(&format!("ext-1-a md5:{}\n", OID_A)).is_err());
+        assert!(parse("ext-1-a sha256:abc\n").is_err());
+
+        assert!(PointerExtension::new("a", 10, Oid::from_hex(OID_A).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_pointer_parse_strict() {
+        let pointer = Pointer::from_content(b"strict");
+        let encoded = pointer.encode_bytes();
+        assert_eq!(Pointer::parse_strict(&encoded).unwrap(), pointer);
+
+        // Accepted by the lenient parser, but not canonical
+        let crlf = pointer.encode().replace('\n', "\r\n");
+        assert!(Pointer::parse(crlf.as_bytes()).is_ok());
+        match Pointer::parse_strict(crlf.as_bytes()) {
+            Err(Error::NonCanonicalPointer(diagnostics)) => assert_eq!(diagnostics.len(), 3),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_pointer_parse_errors() {
+        let err = Pointer::parse(b"version https://example.com/spec/v2\noid sha256:abc\nsize 1\n")
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::UnsupportedVersion(ref v) if v == "https://example.com/spec/v2")
+        );
+
+        let err =
+            Pointer::parse(b"version https://git-lfs.github.com/spec/v1\noid sha256:abc\nsize 1\n")
+                .unwrap_err();
+        assert!(matches!(err, Error::MalformedPointer { line: Some(2), .. }));
+
+        let text = format!(
+            "version https://git-lfs.github.com/spec/v1\noid sha256:{}\nsize -5\n",
+            "a".repeat(64)
+        );
+        let err = Pointer::parse(text.as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::MalformedPointer { line: Some(3), .. }));
+        assert_eq!(
+            err.to_string(),
+            "malformed LFS pointer (line 3): invalid size: -5"
+        );
+
+        let err =
+            Pointer::parse(b"version https://git-lfs.github.com/spec/v1\nsize 1\n").unwrap_err();
+        assert!(matches!(err, Error::MalformedPointer { line: None, .. }));
+    }
 }
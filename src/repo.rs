@@ -3,10 +3,13 @@
 //! Provides automatic LFS filtering for git operations.
 
 use git2::{Repository, Signature};
-use std::fs;
+use std::fs::{self, File};
+use std::io::Read;
 use std::path::Path;
 
-use crate::{LfsClient, LfsFilter, Pointer, Result};
+use crate::cache::replace_file;
+use crate::pointer::MAX_POINTER_SIZE;
+use crate::{CheckoutMode, LfsClient, LfsFilter, Pointer, Result};
 
 /// LFS-aware repository wrapper.
 ///
@@ -34,6 +37,7 @@ use crate::{LfsClient, LfsFilter, Pointer, Result};
 pub struct LfsRepo {
     repo: Repository,
     filter: LfsFilter<'static>,
+    checkout_mode: CheckoutMode,
     // We need 'static because LfsFilter borrows Repository,
     // but we own both. Use unsafe to extend lifetime.
     _repo_box: Box<Repository>,
@@ -41,37 +45,61 @@ pub struct LfsRepo {
 
 impl LfsRepo {
     /// Create a new LFS-aware repository wrapper.
+    ///
+    /// Unreadable storage or `lfs.extension.*` settings fall back to the
+    /// defaults, like [`LfsFilter::with_client`]; use
+    /// [`try_new`](Self::try_new) to fail on them instead.
     pub fn new(repo: Repository, client: LfsClient) -> Self {
+        Self::wrap(repo, |repo| Ok(LfsFilter::with_client(repo, client)))
+            .expect("failed to reopen repository")
+    }
+
+    /// Like [`new`](Self::new), but fails if the repository's storage or
+    /// `lfs.extension.*` settings can't be read.
+    pub fn try_new(repo: Repository, client: LfsClient) -> Result<Self> {
+        Self::wrap(repo, |repo| LfsFilter::try_with_client(repo, client))
+    }
+
+    fn wrap(
+        repo: Repository,
+        filter: impl FnOnce(&'static Repository) -> Result<LfsFilter<'static>>,
+    ) -> Result<Self> {
         // Box the repo so it has a stable address
         let repo_box = Box::new(repo);
 
         // Create filter with 'static lifetime (safe because we own the repo)
         let filter = unsafe {
             let repo_ref: &'static Repository = &*(&*repo_box as *const Repository);
-            LfsFilter::with_client(repo_ref, client)
+            filter(repo_ref)?
         };
 
         // Re-open the repo from its path
         let repo_path = repo_box.path();
-        let repo = Repository::open(repo_path).expect("failed to reopen repository");
+        let repo = Repository::open(repo_path)?;
 
-        LfsRepo {
+        Ok(LfsRepo {
             repo,
             filter,
+            checkout_mode: CheckoutMode::default(),
             _repo_box: repo_box,
-        }
+        })
     }
 
     /// Open an existing repository with LFS support.
+    ///
+    /// Fails if the repository's storage or `lfs.extension.*` settings
+    /// can't be read.
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let repo = Repository::open(path.as_ref())
-            .map_err(|e| crate::Error::InvalidUrl(e.to_string()))?;
+        let repo = Repository::open(path.as_ref())?;
 
-        let client = LfsFilter::get_remote_url_static(&repo)
-            .and_then(|url| LfsClient::new(&url).ok())
+        let client = LfsClient::from_repo(&repo)
+            .ok()
+            .or_else(|| {
+                LfsFilter::get_remote_url_static(&repo).and_then(|url| LfsClient::new(&url).ok())
+            })
             .unwrap_or_else(|| LfsClient::new("https://example.com/repo.git").unwrap());
 
-        Ok(Self::new(repo, client))
+        Self::try_new(repo, client)
     }
 
     /// Get a reference to the underlying repository.
@@ -90,6 +118,22 @@ impl LfsRepo {
         self
     }
 
+    /// Set how smudged files are materialized from the object cache.
+    ///
+    /// The default, [`CheckoutMode::Copy`], gives every working-tree file
+    /// its own copy. [`CheckoutMode::Reflink`] and [`CheckoutMode::Hardlink`]
+    /// share storage with the cache instead, halving disk usage for large
+    /// objects.
+    pub fn with_checkout_mode(mut self, mode: CheckoutMode) -> Self {
+        self.checkout_mode = mode;
+        self
+    }
+
+    /// Get the checkout mode used when smudging.
+    pub fn checkout_mode(&self) -> CheckoutMode {
+        self.checkout_mode
+    }
+
     /// Add a file to the index with automatic LFS handling.
     ///
     /// If the file is tracked by LFS (per .gitattributes):
@@ -104,8 +148,7 @@ impl LfsRepo {
         let path = path.as_ref();
         let path_str = path.to_string_lossy();
 
-        let workdir = self.repo.workdir()
-            .ok_or_else(|| crate::Error::InvalidUrl("bare repository".into()))?;
+        let workdir = self.workdir()?;
         let full_path = workdir.join(path);
 
         // Read content from disk
@@ -115,19 +158,16 @@ impl LfsRepo {
         // Apply clean filter (handles LFS upload if tracked)
         let cleaned = self.filter.clean(&path_str, &content)?;
 
-        // If content was transformed (is a pointer), write it back
+        // If content was transformed (is a pointer), write it back. The file
+        // is replaced rather than overwritten in case it links into the cache.
         if cleaned != content {
-            fs::write(&full_path, &cleaned)
-                .map_err(crate::Error::Io)?;
+            replace_file(&full_path, &cleaned).map_err(crate::Error::Io)?;
         }
 
         // Add to index
-        let mut index = self.repo.index()
-            .map_err(|e| crate::Error::InvalidUrl(e.to_string()))?;
-        index.add_path(path)
-            .map_err(|e| crate::Error::InvalidUrl(e.to_string()))?;
-        index.write()
-            .map_err(|e| crate::Error::InvalidUrl(e.to_string()))?;
+        let mut index = self.repo.index()?;
+        index.add_path(path)?;
+        index.write()?;
 
         Ok(())
     }
@@ -144,12 +184,10 @@ impl LfsRepo {
     ///
     /// After a git checkout, call this to download LFS content.
     pub fn smudge_all(&self) -> Result<()> {
-        let workdir = self.repo.workdir()
-            .ok_or_else(|| crate::Error::InvalidUrl("bare repository".into()))?;
+        let workdir = self.workdir()?;
 
         // Find all files that are LFS pointers
-        let index = self.repo.index()
-            .map_err(|e| crate::Error::InvalidUrl(e.to_string()))?;
+        let index = self.repo.index()?;
 
         for entry in index.iter() {
             let path_bytes = &entry.path;
@@ -157,15 +195,13 @@ impl LfsRepo {
             let full_path = workdir.join(&*path_str);
 
             if full_path.exists() {
-                let content = fs::read(&full_path)
-                    .map_err(crate::Error::Io)?;
-
-                // Check if it's a pointer
-                if Pointer::is_pointer(&content) {
-                    // Smudge (download from LFS)
-                    let smudged = self.filter.smudge(&path_str, &content)?;
-                    fs::write(&full_path, &smudged)
-                        .map_err(crate::Error::Io)?;
+                // Check if it's a pointer, without reading large files
+                if let Some(content) = read_pointer_candidate(&full_path)? {
+                    if Pointer::is_pointer(&content) {
+                        // Smudge (download from LFS)
+                        self.filter
+                            .smudge_to_file(&content, &full_path, self.checkout_mode)?;
+                    }
                 }
             }
         }
@@ -176,19 +212,15 @@ impl LfsRepo {
     /// Smudge a single file.
     pub fn smudge<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let path = path.as_ref();
-        let path_str = path.to_string_lossy();
 
-        let workdir = self.repo.workdir()
-            .ok_or_else(|| crate::Error::InvalidUrl("bare repository".into()))?;
+        let workdir = self.workdir()?;
         let full_path = workdir.join(path);
 
-        let content = fs::read(&full_path)
-            .map_err(crate::Error::Io)?;
-
-        if Pointer::is_pointer(&content) {
-            let smudged = self.filter.smudge(&path_str, &content)?;
-            fs::write(&full_path, &smudged)
-                .map_err(crate::Error::Io)?;
+        if let Some(content) = read_pointer_candidate(&full_path)? {
+            if Pointer::is_pointer(&content) {
+                self.filter
+                    .smudge_to_file(&content, &full_path, self.checkout_mode)?;
+            }
         }
 
         Ok(())
@@ -197,15 +229,11 @@ impl LfsRepo {
     /// Create a commit with the current index.
     pub fn commit(&self, message: &str) -> Result<git2::Oid> {
         let sig = self.repo.signature()
-            .or_else(|_| Signature::now("git2-lfs", "git2-lfs@example.com"))
-            .map_err(|e| crate::Error::InvalidUrl(e.to_string()))?;
+            .or_else(|_| Signature::now("git2-lfs", "git2-lfs@example.com"))?;
 
-        let mut index = self.repo.index()
-            .map_err(|e| crate::Error::InvalidUrl(e.to_string()))?;
-        let tree_id = index.write_tree()
-            .map_err(|e| crate::Error::InvalidUrl(e.to_string()))?;
-        let tree = self.repo.find_tree(tree_id)
-            .map_err(|e| crate::Error::InvalidUrl(e.to_string()))?;
+        let mut index = self.repo.index()?;
+        let tree_id = index.write_tree()?;
+        let tree = self.repo.find_tree(tree_id)?;
 
         let parent = self.repo.head()
             .ok()
@@ -220,10 +248,36 @@ impl LfsRepo {
             message,
             &tree,
             &parents,
-        ).map_err(|e| crate::Error::InvalidUrl(e.to_string()))?;
+        )?;
 
         Ok(oid)
     }
+
+    /// Working directory, or a `BareRepo` git error for bare repositories.
+    fn workdir(&self) -> Result<&Path> {
+        self.repo.workdir().ok_or_else(|| {
+            git2::Error::new(
+                git2::ErrorCode::BareRepo,
+                git2::ErrorClass::Repository,
+                "bare repository has no working directory",
+            )
+            .into()
+        })
+    }
+}
+
+/// Read a working-tree file if it is small enough to be a pointer.
+fn read_pointer_candidate(path: &Path) -> Result<Option<Vec<u8>>> {
+    let file = File::open(path).map_err(crate::Error::Io)?;
+    let mut content = Vec::new();
+    file.take(MAX_POINTER_SIZE as u64 + 1)
+        .read_to_end(&mut content)
+        .map_err(crate::Error::Io)?;
+
+    if content.len() > MAX_POINTER_SIZE {
+        return Ok(None);
+    }
+    Ok(Some(content))
 }
 
 impl LfsFilter<'_> {
@@ -263,4 +317,30 @@ mod tests {
         let content = fs::read_to_string(td.path().join("readme.txt")).unwrap();
         assert_eq!(content, "Hello");
     }
+
+    #[test]
+    fn test_lfs_repo_smudge_from_cache() {
+        let td = TempDir::new().unwrap();
+        let repo = Repository::init(td.path()).unwrap();
+        let cache = crate::ObjectCache::for_repo(repo.path());
+
+        let content = b"large dataset content";
+        let pointer = Pointer::from_content(content);
+        cache.put(pointer.oid(), content).unwrap();
+
+        // Unreachable server: the object must come from the cache
+        let client = LfsClient::new("http://127.0.0.1:9/test/repo.git").unwrap();
+        let lfs = LfsRepo::new(repo, client).with_checkout_mode(CheckoutMode::Hardlink);
+        assert_eq!(lfs.checkout_mode(), CheckoutMode::Hardlink);
+
+        let path = td.path().join("data.bin");
+        fs::write(&path, pointer.encode_bytes()).unwrap();
+        lfs.smudge("data.bin").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), content);
+
+        // Smudging an already smudged file leaves it alone
+        lfs.smudge("data.bin").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), content);
+        assert_eq!(cache.get_verified(&pointer).unwrap(), content);
+    }
 }
@@ -0,0 +1,158 @@
+//! Bandwidth limiting for transfers.
+//!
+//! A [`Throttle`] is a token bucket shared by every transfer of a client and
+//! its clones, so the limit applies to their combined rate. Readers and
+//! writers wrapped in [`Throttled`] move at most a tenth of a second's worth
+//! of bytes per call and wait for the bucket to cover them.
+
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Largest number of bytes passed through in one call.
+const MAX_CHUNK: usize = 64 * 1024;
+
+/// Token bucket refilled at a fixed number of bytes per second.
+#[derive(Debug)]
+pub(crate) struct Throttle {
+    bytes_per_second: u64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Bytes that may pass without waiting; negative when callers have
+    /// taken more than was available and are waiting it off.
+    available: f64,
+    updated: Instant,
+}
+
+impl Throttle {
+    /// Create a throttle passing `bytes_per_second`, which must not be zero.
+    pub(crate) fn new(bytes_per_second: u64) -> Self {
+        Throttle {
+            bytes_per_second,
+            bucket: Mutex::new(Bucket {
+                available: 0.0,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    pub(crate) fn bytes_per_second(&self) -> u64 {
+        self.bytes_per_second
+    }
+
+    /// Bytes allowed through in one call.
+    fn chunk(&self) -> usize {
+        ((self.bytes_per_second / 10) as usize).clamp(1, MAX_CHUNK)
+    }
+
+    /// Take `bytes` from the bucket, sleeping until the rate allows them.
+    fn consume(&self, bytes: usize) {
+        let rate = self.bytes_per_second as f64;
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = Instant::now();
+            let refill = now.duration_since(bucket.updated).as_secs_f64() * rate;
+            // Idle time builds up at most a chunk's worth of burst
+            bucket.available = (bucket.available + refill).min(self.chunk() as f64);
+            bucket.updated = now;
+            bucket.available -= bytes as f64;
+            // Later callers queue behind the debt left by earlier ones
+            Duration::from_secs_f64((-bucket.available).max(0.0) / rate)
+        };
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+/// A reader or writer whose transfers are limited by an optional
+/// [`Throttle`]. Without one it passes calls straight through.
+pub(crate) struct Throttled<T> {
+    inner: T,
+    throttle: Option<Arc<Throttle>>,
+}
+
+impl<T> Throttled<T> {
+    pub(crate) fn new(inner: T, throttle: Option<Arc<Throttle>>) -> Self {
+        Throttled { inner, throttle }
+    }
+}
+
+impl<R: Read> Read for Throttled<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(throttle) = &self.throttle else {
+            return self.inner.read(buf);
+        };
+        let len = buf.len().min(throttle.chunk());
+        let n = self.inner.read(&mut buf[..len])?;
+        throttle.consume(n);
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for Throttled<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(throttle) = &self.throttle else {
+            return self.inner.write(buf);
+        };
+        let len = buf.len().min(throttle.chunk());
+        let n = self.inner.write(&buf[..len])?;
+        throttle.consume(n);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttled_copy_keeps_to_rate() {
+        let throttle = Arc::new(Throttle::new(100_000));
+        let content = vec![7u8; 30_000];
+
+        let start = Instant::now();
+        let mut reader = Throttled::new(&content[..], Some(throttle.clone()));
+        let mut writer = Throttled::new(Vec::new(), None);
+        io::copy(&mut reader, &mut writer).unwrap();
+
+        assert_eq!(writer.inner, content);
+        assert!(start.elapsed() >= Duration::from_millis(250));
+    }
+
+    #[test]
+    fn test_throttle_is_shared_between_transfers() {
+        let throttle = Arc::new(Throttle::new(100_000));
+
+        let start = Instant::now();
+        let threads: Vec<_> = (0..3)
+            .map(|_| {
+                let throttle = throttle.clone();
+                std::thread::spawn(move || {
+                    let mut writer = Throttled::new(io::sink(), Some(throttle));
+                    writer.write_all(&[0u8; 10_000]).unwrap();
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // Each alone would take a tenth of a second
+        assert!(start.elapsed() >= Duration::from_millis(250));
+    }
+
+    #[test]
+    fn test_chunk_size_follows_rate() {
+        assert_eq!(Throttle::new(1).chunk(), 1);
+        assert_eq!(Throttle::new(50_000).chunk(), 5_000);
+        assert_eq!(Throttle::new(u64::MAX).chunk(), MAX_CHUNK);
+    }
+}
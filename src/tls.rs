@@ -0,0 +1,314 @@
+//! TLS settings for the default HTTP transport.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+
+use crate::{Error, Result};
+
+/// PEM data, or the file holding it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Pem {
+    File(PathBuf),
+    Data(Vec<u8>),
+}
+
+impl Pem {
+    fn certificates(&self) -> std::result::Result<Vec<CertificateDer<'static>>, String> {
+        let certs = match self {
+            Pem::File(path) => CertificateDer::pem_file_iter(path)
+                .map_err(|e| e.to_string())?
+                .collect::<std::result::Result<Vec<_>, _>>(),
+            Pem::Data(data) => CertificateDer::pem_slice_iter(data).collect(),
+        };
+        certs.map_err(|e| e.to_string())
+    }
+
+    fn private_key(&self) -> std::result::Result<PrivateKeyDer<'static>, String> {
+        let key = match self {
+            Pem::File(path) => PrivateKeyDer::from_pem_file(path),
+            Pem::Data(data) => PrivateKeyDer::from_pem_slice(data),
+        };
+        key.map_err(|e| e.to_string())
+    }
+
+    /// Where the PEM came from, for error messages.
+    fn describe(&self) -> String {
+        match self {
+            Pem::File(path) => path.display().to_string(),
+            Pem::Data(_) => "PEM data".into(),
+        }
+    }
+}
+
+/// Client certificate chain and private key.
+type Identity = (Vec<CertificateDer<'static>>, PrivateKeyDer<'static>);
+
+/// How the default transport authenticates servers, and itself to them.
+#[derive(Debug, Clone)]
+pub(crate) struct TlsSettings {
+    /// PEM bundle of CA certificates to trust instead of the built-in roots
+    /// (`http.sslCAInfo`).
+    pub(crate) ca_info: Option<PathBuf>,
+    /// PEM certificates trusted in addition to the built-in roots or
+    /// `ca_info`.
+    pub(crate) extra_roots: Vec<Pem>,
+    /// Whether server certificates are verified at all (`http.sslVerify`).
+    pub(crate) verify: bool,
+    /// Client certificate chain (`http.sslCert`).
+    pub(crate) client_cert: Option<Pem>,
+    /// Client private key (`http.sslKey`); read from `client_cert` if unset.
+    pub(crate) client_key: Option<Pem>,
+}
+
+impl Default for TlsSettings {
+    fn default() -> Self {
+        TlsSettings {
+            ca_info: None,
+            extra_roots: Vec::new(),
+            verify: true,
+            client_cert: None,
+            client_key: None,
+        }
+    }
+}
+
+impl TlsSettings {
+    /// Build a rustls configuration, or `None` if the transport's default
+    /// configuration already matches these settings.
+    pub(crate) fn client_config(&self) -> Result<Option<Arc<ClientConfig>>> {
+        if self.ca_info.is_none()
+            && self.extra_roots.is_empty()
+            && self.verify
+            && self.client_cert.is_none()
+            && self.client_key.is_none()
+        {
+            return Ok(None);
+        }
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| config_error("tls", e.to_string()))?;
+
+        let builder = if self.verify {
+            builder.with_root_certificates(self.root_store()?)
+        } else {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(NoVerification(provider)))
+        };
+        let config = match self.client_identity()? {
+            Some((certs, key)) => builder
+                .with_client_auth_cert(certs, key)
+                .map_err(|e| config_error("http.sslKey", e.to_string()))?,
+            None => builder.with_no_client_auth(),
+        };
+        Ok(Some(Arc::new(config)))
+    }
+
+    /// Trusted roots: the CA bundle if one is set, otherwise the built-in
+    /// webpki roots, plus any extra roots.
+    fn root_store(&self) -> Result<RootCertStore> {
+        let mut roots = RootCertStore::empty();
+        match &self.ca_info {
+            Some(path) => add_roots(&mut roots, "http.sslCAInfo", &Pem::File(path.clone()))?,
+            None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+        }
+        for pem in &self.extra_roots {
+            add_roots(&mut roots, "tls.roots", pem)?;
+        }
+        Ok(roots)
+    }
+
+    /// Client certificate chain and key, if a certificate is set.
+    fn client_identity(&self) -> Result<Option<Identity>> {
+        let Some(cert) = &self.client_cert else {
+            return match self.client_key {
+                Some(_) => Err(config_error(
+                    "http.sslKey",
+                    "set without a client certificate".into(),
+                )),
+                None => Ok(None),
+            };
+        };
+
+        let certs = cert
+            .certificates()
+            .map_err(|e| pem_error("http.sslCert", cert, e))?;
+        if certs.is_empty() {
+            return Err(pem_error("http.sslCert", cert, "no certificates".into()));
+        }
+        let key_pem = self.client_key.as_ref().unwrap_or(cert);
+        let key = key_pem
+            .private_key()
+            .map_err(|e| pem_error("http.sslKey", key_pem, e))?;
+        Ok(Some((certs, key)))
+    }
+}
+
+/// Add every certificate in `pem` to `roots`, failing if none is usable.
+fn add_roots(roots: &mut RootCertStore, key: &str, pem: &Pem) -> Result<()> {
+    let certs = pem.certificates().map_err(|e| pem_error(key, pem, e))?;
+    let (added, _) = roots.add_parsable_certificates(certs);
+    if added == 0 {
+        return Err(pem_error(key, pem, "no usable certificates".into()));
+    }
+    Ok(())
+}
+
+fn pem_error(key: &str, pem: &Pem, message: String) -> Error {
+    config_error(key, format!("{}: {}", pem.describe(), message))
+}
+
+fn config_error(key: &str, message: String) -> Error {
+    Error::Config {
+        key: key.into(),
+        message,
+    }
+}
+
+/// Verifier for `http.sslVerify=false`: accepts any server certificate,
+/// but still checks handshake signatures.
+#[derive(Debug)]
+struct NoVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_settings_keep_transport_config() {
+        assert!(TlsSettings::default().client_config().unwrap().is_none());
+
+        let insecure = TlsSettings {
+            verify: false,
+            ..TlsSettings::default()
+        };
+        assert!(insecure.client_config().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_bad_ca_info() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let missing = TlsSettings {
+            ca_info: Some(dir.path().join("missing.pem")),
+            ..TlsSettings::default()
+        };
+        assert!(matches!(
+            missing.client_config(),
+            Err(Error::Config { key, .. }) if key == "http.sslCAInfo"
+        ));
+
+        let empty_path = dir.path().join("empty.pem");
+        std::fs::write(&empty_path, "not a certificate\n").unwrap();
+        let empty = TlsSettings {
+            ca_info: Some(empty_path),
+            ..TlsSettings::default()
+        };
+        let err = empty.client_config().unwrap_err();
+        assert!(
+            err.to_string().contains("no usable certificates"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_client_identity() {
+        let key = rcgen::KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec!["client".to_string()])
+            .unwrap()
+            .self_signed(&key)
+            .unwrap();
+
+        // Certificate and key in one PEM, or apart
+        let combined = TlsSettings {
+            client_cert: Some(Pem::Data(
+                format!("{}{}", cert.pem(), key.serialize_pem()).into_bytes(),
+            )),
+            ..TlsSettings::default()
+        };
+        assert!(combined
+            .client_config()
+            .unwrap()
+            .unwrap()
+            .client_auth_cert_resolver
+            .has_certs());
+        let separate = TlsSettings {
+            client_cert: Some(Pem::Data(cert.pem().into_bytes())),
+            client_key: Some(Pem::Data(key.serialize_pem().into_bytes())),
+            extra_roots: vec![Pem::Data(cert.pem().into_bytes())],
+            ..TlsSettings::default()
+        };
+        assert!(separate.client_config().unwrap().is_some());
+
+        let keyless = TlsSettings {
+            client_cert: Some(Pem::Data(cert.pem().into_bytes())),
+            ..TlsSettings::default()
+        };
+        assert!(matches!(
+            keyless.client_config(),
+            Err(Error::Config { key, .. }) if key == "http.sslKey"
+        ));
+        let certless = TlsSettings {
+            client_key: Some(Pem::Data(key.serialize_pem().into_bytes())),
+            ..TlsSettings::default()
+        };
+        assert!(matches!(
+            certless.client_config(),
+            Err(Error::Config { key, .. }) if key == "http.sslKey"
+        ));
+    }
+}
@@ -0,0 +1,658 @@
+//! Pluggable HTTP transport for the LFS client.
+//!
+//! [`LfsClient`](crate::LfsClient) sends every request through an
+//! [`HttpTransport`]. The default, [`UreqTransport`], uses a pooled
+//! `ureq::Agent`; other implementations can route requests through a
+//! different HTTP stack or answer them in memory.
+//!
+//! Transports return redirect responses as they are; the client follows
+//! them according to its [`RedirectPolicy`].
+
+use std::fmt;
+use std::io::{self, Read};
+
+use url::Url;
+
+use crate::{Error, Result};
+
+/// Default maximum number of redirects followed for one request.
+pub const DEFAULT_MAX_REDIRECTS: usize = 5;
+
+/// HTTP request method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HttpMethod {
+    Get,
+    Post,
+    Put,
+}
+
+impl HttpMethod {
+    /// Method name as sent on the wire.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpMethod::Get => "GET",
+            HttpMethod::Post => "POST",
+            HttpMethod::Put => "PUT",
+        }
+    }
+}
+
+impl fmt::Display for HttpMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How the client follows HTTP redirects.
+///
+/// Redirects are followed for batch and transfer requests alike:
+///
+/// - 307 and 308 repeat the request, with its method and body, at the new
+///   location. Streamed upload bodies are rewound and streamed again.
+/// - 303, and 301 or 302 in answer to a `POST`, switch to a `GET` without a
+///   body; other 301 and 302 responses are treated like 307 and 308.
+/// - `Authorization` is dropped when the new location is on another origin
+///   (scheme, host and port), so credentials never follow a request to
+///   third-party storage.
+///
+/// A redirect back to a URL already visited, or more than
+/// [`max_redirects`](Self::max_redirects) in a row, fails with
+/// [`Error::Redirect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RedirectPolicy {
+    max_redirects: usize,
+}
+
+impl RedirectPolicy {
+    /// Follow at most `max_redirects` redirects per request.
+    pub fn limited(max_redirects: usize) -> Self {
+        RedirectPolicy { max_redirects }
+    }
+
+    /// Never follow redirects; the client reports redirect responses as
+    /// [`Error::ServerError`]s.
+    pub fn none() -> Self {
+        Self::limited(0)
+    }
+
+    /// Maximum number of redirects followed for one request.
+    pub fn max_redirects(&self) -> usize {
+        self.max_redirects
+    }
+
+    /// Send `request` through `transport`, following redirects. A 307 or
+    /// 308 redirect of a streamed body calls `replay`, if given, for the
+    /// body again.
+    pub(crate) fn send<'a>(
+        &self,
+        transport: &dyn HttpTransport,
+        request: HttpRequest<'a>,
+        replay: Option<&dyn Fn() -> Result<RequestBody<'a>>>,
+    ) -> Result<HttpResponse> {
+        let HttpRequest {
+            mut method,
+            url,
+            mut headers,
+            mut body,
+        } = request;
+        let mut url = url.to_string();
+        let mut visited = Vec::new();
+
+        loop {
+            // Keep a copy of the body in case it must be sent again
+            let copy = match &body {
+                RequestBody::Empty => Some(RequestBody::Empty),
+                RequestBody::Bytes(bytes) => Some(RequestBody::Bytes(bytes)),
+                RequestBody::Reader(_) => None,
+            };
+            let response = transport.send(HttpRequest {
+                method,
+                url: &url,
+                headers: headers.clone(),
+                body,
+            })?;
+
+            let location = match response.status {
+                301 | 302 | 303 | 307 | 308 if self.max_redirects > 0 => {
+                    response.header("Location")
+                }
+                _ => None,
+            };
+            let Some(location) = location else {
+                return Ok(response);
+            };
+
+            let redirect_error = |message: String| Error::Redirect {
+                url: url.clone(),
+                message,
+            };
+            let current = Url::parse(&url)?;
+            let next = current
+                .join(location)
+                .map_err(|e| redirect_error(format!("bad location {:?}: {}", location, e)))?;
+            visited.push(url.clone());
+            if visited.iter().any(|seen| seen == next.as_str()) {
+                return Err(redirect_error(format!("redirect loop back to {}", next)));
+            }
+            if visited.len() > self.max_redirects {
+                return Err(redirect_error(format!(
+                    "more than {} redirects",
+                    self.max_redirects
+                )));
+            }
+
+            let to_get = response.status == 303
+                || (matches!(response.status, 301 | 302) && method == HttpMethod::Post);
+            if to_get {
+                method = HttpMethod::Get;
+                body = RequestBody::Empty;
+                headers.retain(|(name, _)| {
+                    !name.eq_ignore_ascii_case("Content-Type")
+                        && !name.eq_ignore_ascii_case("Content-Length")
+                });
+            } else {
+                body = match (copy, replay) {
+                    (Some(copy), _) => copy,
+                    (None, Some(replay)) => replay()?,
+                    (None, None) => {
+                        return Err(redirect_error(format!(
+                            "{} redirect would send a streamed body again",
+                            response.status
+                        )))
+                    }
+                };
+            }
+            if next.origin() != current.origin() {
+                headers.retain(|(name, _)| !name.eq_ignore_ascii_case("Authorization"));
+            }
+            url = next.into();
+        }
+    }
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self::limited(DEFAULT_MAX_REDIRECTS)
+    }
+}
+
+/// Body of an HTTP request.
+pub enum RequestBody<'a> {
+    /// No body.
+    Empty,
+    /// Body held in memory.
+    Bytes(&'a [u8]),
+    /// Body streamed from a reader. The client always sets
+    /// `Content-Length` for streamed bodies.
+    Reader(Box<dyn Read + 'a>),
+}
+
+impl fmt::Debug for RequestBody<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestBody::Empty => f.write_str("Empty"),
+            RequestBody::Bytes(bytes) => write!(f, "Bytes({} bytes)", bytes.len()),
+            RequestBody::Reader(_) => f.write_str("Reader"),
+        }
+    }
+}
+
+/// An HTTP request sent by the LFS client.
+#[derive(Debug)]
+pub struct HttpRequest<'a> {
+    /// Request method.
+    pub method: HttpMethod,
+    /// Absolute request URL.
+    pub url: &'a str,
+    /// Request headers, in the order they were set.
+    pub headers: Vec<(String, String)>,
+    /// Request body.
+    pub body: RequestBody<'a>,
+}
+
+impl<'a> HttpRequest<'a> {
+    /// Create a request with no headers and no body.
+    pub fn new(method: HttpMethod, url: &'a str) -> Self {
+        HttpRequest {
+            method,
+            url,
+            headers: Vec::new(),
+            body: RequestBody::Empty,
+        }
+    }
+
+    /// Add a header.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Set the body.
+    pub fn with_body(mut self, body: RequestBody<'a>) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Get the first header named `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+}
+
+/// An HTTP response, whatever its status code.
+pub struct HttpResponse {
+    /// Status code.
+    pub status: u16,
+    /// Final URL of the response, after any redirects.
+    pub url: String,
+    /// Response headers.
+    pub headers: Vec<(String, String)>,
+    /// Response body, read on demand.
+    pub body: Box<dyn Read + Send>,
+}
+
+impl HttpResponse {
+    /// Create a response with an in-memory body.
+    pub fn new(status: u16, url: &str, body: impl Into<Vec<u8>>) -> Self {
+        HttpResponse {
+            status,
+            url: url.to_string(),
+            headers: Vec::new(),
+            body: Box::new(io::Cursor::new(body.into())),
+        }
+    }
+
+    /// Add a header.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Get the first header named `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// Whether the status code is 2xx.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Turn an error status into an [`Error`], keeping successful responses.
+    pub(crate) fn error_for_status(self) -> Result<Self> {
+        if self.is_success() {
+            return Ok(self);
+        }
+        let HttpResponse {
+            status,
+            url,
+            mut body,
+            ..
+        } = self;
+        Err(Error::from_status(status, Some(url), || {
+            let mut message = String::new();
+            match body.read_to_string(&mut message) {
+                Ok(_) => message,
+                Err(_) => "unknown error".into(),
+            }
+        }))
+    }
+}
+
+impl fmt::Debug for HttpResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpResponse")
+            .field("status", &self.status)
+            .field("url", &self.url)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+fn find_header<'h>(headers: &'h [(String, String)], name: &str) -> Option<&'h str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Content codings the client can decode, offered on downloads.
+pub(crate) const ACCEPT_ENCODING: &str = "gzip, zstd";
+
+/// Undo the content codings named in a `Content-Encoding` header, the last
+/// applied first.
+///
+/// Fails with [`Error::Protocol`] for codings the client never offered.
+pub(crate) fn decode_body(
+    content_encoding: Option<&str>,
+    body: Box<dyn Read + Send>,
+) -> Result<Box<dyn Read + Send>> {
+    content_encoding
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|coding| !coding.is_empty())
+        .rev()
+        .try_fold(body, |body, coding| -> Result<Box<dyn Read + Send>> {
+            Ok(match coding.to_ascii_lowercase().as_str() {
+                "identity" => body,
+                "gzip" | "x-gzip" => Box::new(flate2::read::MultiGzDecoder::new(body)),
+                "zstd" => Box::new(zstd::stream::read::Decoder::new(body)?),
+                other => {
+                    return Err(Error::Protocol(format!(
+                        "unsupported content encoding: {}",
+                        other
+                    )))
+                }
+            })
+        })
+}
+
+/// Sends HTTP requests for the LFS client.
+///
+/// Implementations return a response for every status code; the client
+/// turns error statuses into [`Error`]s itself. `Err` is reserved for
+/// failures before a response arrives, usually [`Error::Http`], so that
+/// [`Error::is_retryable`] can classify them.
+pub trait HttpTransport: Send + Sync {
+    /// Send `request` and return the server's response.
+    fn send(&self, request: HttpRequest<'_>) -> Result<HttpResponse>;
+}
+
+impl<T: HttpTransport + ?Sized> HttpTransport for std::sync::Arc<T> {
+    fn send(&self, request: HttpRequest<'_>) -> Result<HttpResponse> {
+        (**self).send(request)
+    }
+}
+
+/// The default transport, backed by a `ureq::Agent`.
+///
+/// Clones share the agent's connection pool. The default agent doesn't
+/// follow redirects, leaving them to the client's [`RedirectPolicy`].
+#[derive(Clone)]
+pub struct UreqTransport {
+    agent: ureq::Agent,
+}
+
+impl UreqTransport {
+    /// Create a transport with a default agent.
+    pub fn new() -> Self {
+        UreqTransport {
+            agent: ureq::AgentBuilder::new().redirects(0).build(),
+        }
+    }
+
+    /// Create a transport using a preconfigured agent, e.g. one with
+    /// timeouts, a proxy or custom TLS settings. Build it with
+    /// `redirects(0)` so that the client's [`RedirectPolicy`] applies.
+    pub fn with_agent(agent: ureq::Agent) -> Self {
+        UreqTransport { agent }
+    }
+
+    /// Get the underlying agent.
+    pub fn agent(&self) -> &ureq::Agent {
+        &self.agent
+    }
+}
+
+impl Default for UreqTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<ureq::Agent> for UreqTransport {
+    fn from(agent: ureq::Agent) -> Self {
+        Self::with_agent(agent)
+    }
+}
+
+impl HttpTransport for UreqTransport {
+    fn send(&self, request: HttpRequest<'_>) -> Result<HttpResponse> {
+        let mut req = self.agent.request(request.method.as_str(), request.url);
+        for (name, value) in &request.headers {
+            req = req.set(name, value);
+        }
+
+        let result = match request.body {
+            RequestBody::Empty => req.call(),
+            RequestBody::Bytes(bytes) => req.send_bytes(bytes),
+            RequestBody::Reader(reader) => req.send(reader),
+        };
+
+        // ureq reports error statuses as errors; the client wants them as
+        // responses
+        let response = match result {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(err) => return Err(err.into()),
+        };
+
+        let headers = response
+            .headers_names()
+            .into_iter()
+            .filter_map(|name| {
+                let value = response.header(&name)?.to_string();
+                Some((name, value))
+            })
+            .collect();
+        Ok(HttpResponse {
+            status: response.status(),
+            url: response.get_url().to_string(),
+            headers,
+            body: response.into_reader(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_error_for_status() {
+        let ok = HttpResponse::new(200, "https://example.com/a", "body")
+            .with_header("Content-Type", "text/plain");
+        assert_eq!(ok.header("content-type"), Some("text/plain"));
+        assert!(ok.error_for_status().is_ok());
+
+        let err = HttpResponse::new(503, "https://example.com/a", "overloaded")
+            .error_for_status()
+            .unwrap_err();
+        assert!(matches!(
+            &err,
+            Error::ServerError { code: 503, message, .. } if message == "overloaded"
+        ));
+        assert_eq!(err.url(), Some("https://example.com/a"));
+
+        let err = HttpResponse::new(401, "https://example.com/a", "")
+            .error_for_status()
+            .unwrap_err();
+        assert!(err.is_auth());
+    }
+
+    #[test]
+    fn test_decode_body() {
+        let decode = |encoding: Option<&str>, body: Vec<u8>| -> Result<Vec<u8>> {
+            let mut decoded = Vec::new();
+            decode_body(encoding, Box::new(io::Cursor::new(body)))?.read_to_end(&mut decoded)?;
+            Ok(decoded)
+        };
+        let content = b"line of a compressible dump\n".repeat(100);
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        io::Write::write_all(&mut gzip, &content).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let zstd = zstd::encode_all(&content[..], 0).unwrap();
+        // Codings are listed in the order they were applied
+        let both = zstd::encode_all(&gzip[..], 0).unwrap();
+
+        assert_eq!(decode(None, content.clone()).unwrap(), content);
+        assert_eq!(decode(Some("identity"), content.clone()).unwrap(), content);
+        assert_eq!(decode(Some("gzip"), gzip).unwrap(), content);
+        assert_eq!(decode(Some("ZSTD"), zstd).unwrap(), content);
+        assert_eq!(decode(Some("gzip, zstd"), both).unwrap(), content);
+        assert!(matches!(
+            decode(Some("br"), content.clone()),
+            Err(Error::Protocol(message)) if message.contains("br")
+        ));
+    }
+
+    /// Method, URL, `Authorization` header and body of a request.
+    type Seen = (HttpMethod, String, Option<String>, Vec<u8>);
+
+    /// Transport answering from a table of URL to status and `Location`,
+    /// recording the requests it saw.
+    #[derive(Default)]
+    struct Redirector {
+        routes: Vec<(&'static str, u16, Option<&'static str>)>,
+        seen: std::sync::Mutex<Vec<Seen>>,
+    }
+
+    impl HttpTransport for Redirector {
+        fn send(&self, request: HttpRequest<'_>) -> Result<HttpResponse> {
+            let auth = request.header("Authorization").map(str::to_string);
+            let mut body = Vec::new();
+            match request.body {
+                RequestBody::Empty => {}
+                RequestBody::Bytes(bytes) => body.extend_from_slice(bytes),
+                RequestBody::Reader(mut reader) => {
+                    reader.read_to_end(&mut body)?;
+                }
+            }
+            self.seen
+                .lock()
+                .unwrap()
+                .push((request.method, request.url.to_string(), auth, body));
+
+            let (_, status, location) = self
+                .routes
+                .iter()
+                .find(|(url, _, _)| *url == request.url)
+                .copied()
+                .unwrap_or(("", 200, None));
+            let response = HttpResponse::new(status, request.url, "");
+            Ok(match location {
+                Some(location) => response.with_header("Location", location),
+                None => response,
+            })
+        }
+    }
+
+    fn put<'a>(url: &'a str, body: &'a [u8]) -> HttpRequest<'a> {
+        HttpRequest::new(HttpMethod::Put, url)
+            .with_header("Authorization", "Bearer secret")
+            .with_body(RequestBody::Bytes(body))
+    }
+
+    #[test]
+    fn test_redirects_keep_method_and_body() {
+        let transport = Redirector {
+            routes: vec![
+                ("https://lfs.test/objects/a", 307, Some("/storage/a")),
+                (
+                    "https://lfs.test/storage/a",
+                    308,
+                    Some("https://bucket.test/a"),
+                ),
+            ],
+            ..Default::default()
+        };
+        let response = RedirectPolicy::default()
+            .send(&transport, put("https://lfs.test/objects/a", b"content"), None)
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.url, "https://bucket.test/a");
+
+        // Credentials stay on the same origin only
+        let seen = transport.seen.lock().unwrap();
+        let auth: Vec<_> = seen.iter().map(|(_, _, auth, _)| auth.as_deref()).collect();
+        assert_eq!(auth, [Some("Bearer secret"), Some("Bearer secret"), None]);
+        assert!(seen
+            .iter()
+            .all(|(method, _, _, body)| *method == HttpMethod::Put && body == b"content"));
+    }
+
+    #[test]
+    fn test_see_other_switches_to_get() {
+        let transport = Redirector {
+            routes: vec![(
+                "https://lfs.test/batch",
+                303,
+                Some("https://lfs.test/result"),
+            )],
+            ..Default::default()
+        };
+        let request = HttpRequest::new(HttpMethod::Post, "https://lfs.test/batch")
+            .with_header("Content-Type", "application/json")
+            .with_body(RequestBody::Bytes(b"{}"));
+        RedirectPolicy::default().send(&transport, request, None).unwrap();
+
+        let seen = transport.seen.lock().unwrap();
+        assert_eq!(seen[1].0, HttpMethod::Get);
+        assert!(seen[1].3.is_empty());
+    }
+
+    #[test]
+    fn test_redirect_errors() {
+        let looping = Redirector {
+            routes: vec![
+                ("https://lfs.test/a", 307, Some("https://lfs.test/b")),
+                ("https://lfs.test/b", 307, Some("https://lfs.test/a")),
+            ],
+            ..Default::default()
+        };
+        let err = RedirectPolicy::default()
+            .send(&looping, put("https://lfs.test/a", b""), None)
+            .unwrap_err();
+        assert!(matches!(&err, Error::Redirect { message, .. } if message.contains("loop")));
+        assert_eq!(err.url(), Some("https://lfs.test/b"));
+
+        let chain = Redirector {
+            routes: vec![
+                ("https://lfs.test/1", 302, Some("/2")),
+                ("https://lfs.test/2", 302, Some("/3")),
+            ],
+            ..Default::default()
+        };
+        let err = RedirectPolicy::limited(1)
+            .send(&chain, put("https://lfs.test/1", b""), None)
+            .unwrap_err();
+        assert!(err.to_string().contains("more than 1 redirects"), "{}", err);
+
+        // Without a policy, the redirect is the response
+        let response = RedirectPolicy::none()
+            .send(&chain, put("https://lfs.test/1", b""), None)
+            .unwrap();
+        assert_eq!(response.status, 302);
+
+        // A streamed body can't be sent twice unless it can be replayed
+        let streamed = || {
+            HttpRequest::new(HttpMethod::Put, "https://lfs.test/1")
+                .with_body(RequestBody::Reader(Box::new(&b"streamed"[..])))
+        };
+        let err = RedirectPolicy::default().send(&chain, streamed(), None).unwrap_err();
+        assert!(matches!(err, Error::Redirect { .. }));
+
+        let replay = || Ok(RequestBody::Reader(Box::new(&b"streamed"[..])));
+        let response = RedirectPolicy::default()
+            .send(&chain, streamed(), Some(&replay))
+            .unwrap();
+        assert_eq!(response.status, 200);
+        let seen = chain.seen.lock().unwrap();
+        let last = seen.last().unwrap();
+        assert_eq!((last.1.as_str(), &last.3[..]), ("https://lfs.test/3", &b"streamed"[..]));
+    }
+
+    #[test]
+    fn test_ureq_transport_connection_failure() {
+        let transport = UreqTransport::new();
+        let err = transport
+            .send(HttpRequest::new(
+                HttpMethod::Get,
+                "http://127.0.0.1:9/objects",
+            ))
+            .unwrap_err();
+        assert!(matches!(err, Error::Http { .. }));
+        assert!(err.is_retryable());
+    }
+}
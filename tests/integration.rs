@@ -2,20 +2,24 @@
 //!
 //! These tests verify the full LFS workflow including HTTP client operations.
 
-use git2_lfs::{BatchRequest, BatchRequestObject, LfsClient, Pointer};
+use git2_lfs::{
+    BatchRequest, BatchRequestObject, LfsClient, LfsClientBuilder, ObjectStatus, Pointer,
+};
 
 // Note: Pointer is used in test_pointer_edge_cases
 
 // Note: URL derivation and client clone tests are in src/client.rs unit tests.
 // This file focuses on integration tests that require HTTP communication.
+use std::collections::{HashMap, HashSet};
 use std::io::{Read, Write};
 use std::net::TcpListener;
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 
 /// Mock LFS server for testing.
 ///
-/// Listens on a random port and handles batch API requests.
+/// Listens on a random port and answers each request with the response
+/// built by its handler, closing the connection afterwards.
 struct MockLfsServer {
     port: u16,
     shutdown_tx: mpsc::Sender<()>,
@@ -25,13 +29,63 @@ struct MockLfsServer {
 #[derive(Debug)]
 #[allow(dead_code)]
 struct MockRequest {
+    /// Whether the request arrived over TLS.
+    tls: bool,
     method: String,
     path: String,
+    headers: Vec<(String, String)>,
     body: String,  // Captured for potential debugging
 }
 
+impl MockRequest {
+    /// Get a header value by case-insensitive name.
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Base URL of the server, as seen by the client.
+    fn base_url(&self) -> String {
+        let scheme = if self.tls { "https" } else { "http" };
+        format!("{}://{}", scheme, self.header("host").unwrap_or_default())
+    }
+}
+
 impl MockLfsServer {
+    /// Start a server answering batch requests with `mock_batch_response`.
     fn start() -> Self {
+        Self::start_with(|req| {
+            if req.path.contains("/objects/batch") {
+                mock_batch_response()
+            } else {
+                mock_404_response()
+            }
+        })
+    }
+
+    /// Start a server answering every request with `handler`.
+    fn start_with<F>(handler: F) -> Self
+    where
+        F: Fn(&MockRequest) -> String + Send + 'static,
+    {
+        Self::start_inner(None, handler)
+    }
+
+    /// Start a server that speaks TLS with `config`, answering every
+    /// request with `handler`.
+    fn start_tls_with<F>(config: rustls::ServerConfig, handler: F) -> Self
+    where
+        F: Fn(&MockRequest) -> String + Send + 'static,
+    {
+        Self::start_inner(Some(Arc::new(config)), handler)
+    }
+
+    fn start_inner<F>(tls: Option<Arc<rustls::ServerConfig>>, handler: F) -> Self
+    where
+        F: Fn(&MockRequest) -> String + Send + 'static,
+    {
         let listener = TcpListener::bind("127.0.0.1:0").unwrap();
         let port = listener.local_addr().unwrap().port();
         let (shutdown_tx, shutdown_rx) = mpsc::channel();
@@ -56,37 +110,19 @@ impl MockLfsServer {
                             .set_read_timeout(Some(std::time::Duration::from_secs(5)))
                             .unwrap();
 
-                        let mut buffer = [0u8; 4096];
-                        let n = stream.read(&mut buffer).unwrap_or(0);
-                        let request = String::from_utf8_lossy(&buffer[..n]).to_string();
-
-                        // Parse request
-                        let lines: Vec<&str> = request.lines().collect();
-                        if let Some(first_line) = lines.first() {
-                            let parts: Vec<&str> = first_line.split_whitespace().collect();
-                            if parts.len() >= 2 {
-                                let method = parts[0].to_string();
-                                let path = parts[1].to_string();
-
-                                // Find body (after empty line)
-                                let body = if let Some(pos) = request.find("\r\n\r\n") {
-                                    request[pos + 4..].to_string()
-                                } else {
-                                    String::new()
-                                };
-
-                                // Send response based on path
-                                let response = if path.contains("/objects/batch") {
-                                    mock_batch_response()
-                                } else {
-                                    mock_404_response()
-                                };
-
-                                let _ = stream.write_all(response.as_bytes());
-
-                                requests.push(MockRequest { method, path, body });
+                        // Failed handshakes are not requests
+                        let request = match &tls {
+                            None => serve(&mut stream, false, &handler),
+                            Some(config) => {
+                                let conn = rustls::ServerConnection::new(config.clone()).unwrap();
+                                let mut stream = rustls::StreamOwned::new(conn, stream);
+                                let request = serve(&mut stream, true, &handler);
+                                stream.conn.send_close_notify();
+                                let _ = stream.flush();
+                                request
                             }
-                        }
+                        };
+                        requests.extend(request);
                     }
                     Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                         thread::sleep(std::time::Duration::from_millis(10));
@@ -109,6 +145,10 @@ impl MockLfsServer {
         format!("http://127.0.0.1:{}/test/repo.git", self.port)
     }
 
+    fn https_url(&self) -> String {
+        format!("https://127.0.0.1:{}/test/repo.git", self.port)
+    }
+
     fn stop(mut self) -> Vec<MockRequest> {
         let _ = self.shutdown_tx.send(());
         if let Some(handle) = self.handle.take() {
@@ -119,6 +159,206 @@ impl MockLfsServer {
     }
 }
 
+/// Read one request from `stream` and write the handler's response.
+fn serve(
+    stream: &mut (impl Read + Write),
+    tls: bool,
+    handler: &impl Fn(&MockRequest) -> String,
+) -> Option<MockRequest> {
+    let request = read_request(stream, tls)?;
+    let response = handler(&request);
+    let _ = stream.write_all(response.as_bytes());
+    Some(request)
+}
+
+/// Read one HTTP request, including a body of `Content-Length` bytes.
+fn read_request(stream: &mut impl Read, tls: bool) -> Option<MockRequest> {
+    let mut data = Vec::new();
+    let mut buffer = [0u8; 4096];
+    let header_end = loop {
+        if let Some(pos) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        let n = stream.read(&mut buffer).ok().filter(|&n| n > 0)?;
+        data.extend_from_slice(&buffer[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&data[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut parts = lines.next()?.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    let length = headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    let mut body = data[header_end + 4..].to_vec();
+    while body.len() < length {
+        let n = stream.read(&mut buffer).ok().filter(|&n| n > 0)?;
+        body.extend_from_slice(&buffer[..n]);
+    }
+
+    Some(MockRequest {
+        tls,
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&body).to_string(),
+    })
+}
+
+/// Build an HTTP response that closes the connection.
+fn http_response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\n\
+         Content-Type: {}\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// Build a redirect response to `location`.
+fn redirect_response(status: &str, location: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        status, location
+    )
+}
+
+/// In-memory object store implementing the batch API and basic transfers.
+#[derive(Clone, Default)]
+struct MockStore {
+    objects: Arc<Mutex<HashMap<String, String>>>,
+    /// OIDs the batch API reports a 422 error for.
+    rejected: Arc<Mutex<HashSet<String>>>,
+    /// Number of upcoming batch responses whose actions have already
+    /// expired and point at URLs that refuse transfers.
+    expired_batches: Arc<Mutex<usize>>,
+    /// Base URL for transfer actions, instead of the server's own.
+    transfer_base: Option<String>,
+    /// Whether batch responses mark objects as `authenticated`.
+    authenticated: bool,
+}
+
+impl MockStore {
+    /// Store `content` on the server and return its pointer.
+    fn insert(&self, content: &str) -> Pointer {
+        let pointer = Pointer::from_content(content.as_bytes());
+        self.objects
+            .lock()
+            .unwrap()
+            .insert(pointer.oid().to_hex(), content.to_string());
+        pointer
+    }
+
+    fn contains(&self, pointer: &Pointer) -> bool {
+        self.objects
+            .lock()
+            .unwrap()
+            .contains_key(&pointer.oid().to_hex())
+    }
+
+    fn reject(&self, pointer: &Pointer) {
+        self.rejected.lock().unwrap().insert(pointer.oid().to_hex());
+    }
+
+    fn expire_next_batches(&self, count: usize) {
+        *self.expired_batches.lock().unwrap() = count;
+    }
+
+    fn handle(&self, req: &MockRequest) -> String {
+        let objects = self.objects.lock().unwrap();
+        if req.path.ends_with("/objects/batch") {
+            let expired = {
+                let mut remaining = self.expired_batches.lock().unwrap();
+                let expired = *remaining > 0;
+                *remaining = remaining.saturating_sub(1);
+                expired
+            };
+            let request: serde_json::Value = serde_json::from_str(&req.body).unwrap();
+            let upload = request["operation"] == "upload";
+            let rejected = self.rejected.lock().unwrap();
+            let base_url = self.transfer_base.clone().unwrap_or_else(|| req.base_url());
+            let response: Vec<_> = request["objects"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|obj| {
+                    let oid = obj["oid"].as_str().unwrap();
+                    let href = if expired {
+                        serde_json::json!({
+                            "href": format!("{}/stale/{}", base_url, oid),
+                            "expires_at": "2000-01-01T00:00:00Z"
+                        })
+                    } else {
+                        serde_json::json!({
+                            "href": format!("{}/objects/{}", base_url, oid),
+                            "expires_in": 3600
+                        })
+                    };
+                    let mut entry = serde_json::json!({ "oid": oid, "size": obj["size"] });
+                    if self.authenticated {
+                        entry["authenticated"] = true.into();
+                    }
+                    if rejected.contains(oid) {
+                        entry["error"] = serde_json::json!({ "code": 422, "message": "rejected" });
+                    } else if upload && !objects.contains_key(oid) {
+                        entry["actions"] = serde_json::json!({ "upload": href });
+                    } else if !upload && objects.contains_key(oid) {
+                        entry["actions"] = serde_json::json!({ "download": href });
+                    } else if !upload {
+                        entry["error"] = serde_json::json!({ "code": 404, "message": "missing" });
+                    }
+                    entry
+                })
+                .collect();
+            let body = serde_json::json!({ "transfer": "basic", "objects": response });
+            return http_response("200 OK", "application/vnd.git-lfs+json", &body.to_string());
+        }
+
+        if req.path.starts_with("/stale/") {
+            return http_response("403 Forbidden", "text/plain", "signature expired");
+        }
+        let oid = req.path.rsplit('/').next().unwrap_or_default().to_string();
+        match req.method.as_str() {
+            "GET" => match objects.get(&oid) {
+                Some(content) => http_response("200 OK", "application/octet-stream", content),
+                None => mock_404_response(),
+            },
+            "PUT" => {
+                drop(objects);
+                self.objects.lock().unwrap().insert(oid, req.body.clone());
+                http_response("200 OK", "text/plain", "")
+            }
+            _ => mock_404_response(),
+        }
+    }
+}
+
+/// Number of objects in each batch request the server received.
+fn batch_sizes(requests: &[MockRequest]) -> Vec<usize> {
+    requests
+        .iter()
+        .filter(|req| req.path.ends_with("/objects/batch"))
+        .map(|req| {
+            let body: serde_json::Value = serde_json::from_str(&req.body).unwrap();
+            body["objects"].as_array().unwrap().len()
+        })
+        .collect()
+}
+
 fn mock_batch_response() -> String {
     let body = r#"{
         "transfer": "basic",
@@ -136,22 +376,11 @@ fn mock_batch_response() -> String {
         }]
     }"#;
 
-    format!(
-        "HTTP/1.1 200 OK\r\n\
-         Content-Type: application/vnd.git-lfs+json\r\n\
-         Content-Length: {}\r\n\
-         \r\n\
-         {}",
-        body.len(),
-        body
-    )
+    http_response("200 OK", "application/vnd.git-lfs+json", body)
 }
 
 fn mock_404_response() -> String {
-    "HTTP/1.1 404 Not Found\r\n\
-     Content-Length: 0\r\n\
-     \r\n"
-        .to_string()
+    http_response("404 Not Found", "text/plain", "")
 }
 
 #[test]
@@ -213,3 +442,582 @@ fn test_pointer_edge_cases() {
         size 100\n";
     assert!(Pointer::parse(hawser_pointer).is_ok());
 }
+
+#[test]
+fn test_download_batch_report_chunks_and_reports_each_object() {
+    let store = MockStore::default();
+    let handler = store.clone();
+    let server = MockLfsServer::start_with(move |req| handler.handle(req));
+    let client = LfsClient::new(&server.url()).unwrap().with_batch_size(2);
+
+    let mut pointers: Vec<_> = (0..4)
+        .map(|i| store.insert(&format!("object {}", i)))
+        .collect();
+    let missing = Pointer::from_content(b"not on the server");
+    pointers.insert(2, missing.clone());
+    let refs: Vec<_> = pointers.iter().collect();
+
+    let report = client.download_batch_report(&refs).unwrap();
+    let requests = server.stop();
+
+    assert_eq!(batch_sizes(&requests), vec![2, 2, 1]);
+    assert_eq!(report.len(), 5);
+    for ((oid, status), pointer) in report.into_iter().zip(&pointers) {
+        assert_eq!(&oid, pointer.oid());
+        match status {
+            ObjectStatus::Done(content) => assert_eq!(Pointer::from_content(&content), *pointer),
+            ObjectStatus::Failed(err) => {
+                assert_eq!(pointer, &missing);
+                assert!(err.is_not_found());
+                assert_eq!(err.oid(), Some(missing.oid()));
+            }
+            ObjectStatus::AlreadyPresent => panic!("downloads are never already present"),
+        }
+    }
+}
+
+#[test]
+fn test_upload_batch_report_continues_past_object_errors() {
+    let store = MockStore::default();
+    let handler = store.clone();
+    let server = MockLfsServer::start_with(move |req| handler.handle(req));
+    let client = LfsClient::new(&server.url()).unwrap().with_batch_size(2);
+
+    let present = store.insert("already uploaded");
+    let rejected = Pointer::from_content(b"rejected");
+    store.reject(&rejected);
+    let new = Pointer::from_content(b"new object");
+
+    let items: Vec<(&Pointer, &[u8])> = vec![
+        (&present, b"already uploaded"),
+        (&rejected, b"rejected"),
+        (&new, b"new object"),
+    ];
+    let report = client.upload_batch_report(&items).unwrap();
+    let requests = server.stop();
+
+    assert_eq!(batch_sizes(&requests), vec![2, 1]);
+    assert!(matches!(report[0].1, ObjectStatus::AlreadyPresent));
+    assert!(matches!(
+        &report[1].1,
+        ObjectStatus::Failed(git2_lfs::Error::ServerError { code: 422, .. })
+    ));
+    assert!(matches!(report[2].1, ObjectStatus::Done(())));
+    assert!(store.contains(&new));
+}
+
+#[test]
+fn test_batch_transfers_rerequest_expired_actions() {
+    let store = MockStore::default();
+    let handler = store.clone();
+    let server = MockLfsServer::start_with(move |req| handler.handle(req));
+    let client = LfsClient::new(&server.url()).unwrap();
+
+    let stored = store.insert("stored object");
+    store.expire_next_batches(1);
+    let contents = client.download_batch(&[&stored]).unwrap();
+    assert_eq!(contents, vec![b"stored object".to_vec()]);
+
+    let new = Pointer::from_content(b"new object");
+    store.expire_next_batches(1);
+    client.upload_batch(&[(&new, b"new object")]).unwrap();
+    assert!(store.contains(&new));
+
+    let requests = server.stop();
+    assert_eq!(batch_sizes(&requests), vec![1, 1, 1, 1]);
+    assert!(requests.iter().all(|req| !req.path.starts_with("/stale/")));
+}
+
+#[test]
+fn test_batch_rejects_unoffered_transfer_adapter() {
+    let server = MockLfsServer::start_with(|_| {
+        let body = r#"{"transfer": "tus", "objects": []}"#;
+        http_response("200 OK", "application/vnd.git-lfs+json", body)
+    });
+    let client = LfsClient::new(&server.url()).unwrap();
+
+    let request = BatchRequest::upload(vec![]);
+    let err = client.batch(&request).unwrap_err();
+    assert!(matches!(err, git2_lfs::Error::UnsupportedTransfer(ref t) if t == "tus"));
+
+    let request = BatchRequest::upload(vec![]).with_transfers(["tus", "basic"]);
+    assert_eq!(client.batch(&request).unwrap().transfer, "tus");
+
+    let requests = server.stop();
+    let body: serde_json::Value = serde_json::from_str(&requests[1].body).unwrap();
+    assert_eq!(body["transfers"], serde_json::json!(["tus", "basic"]));
+    assert_eq!(body["hash_algo"], "sha256");
+}
+
+#[test]
+fn test_uploads_skip_duplicate_and_known_objects() {
+    let store = MockStore::default();
+    let handler = store.clone();
+    let server = MockLfsServer::start_with(move |req| handler.handle(req));
+    let client = LfsClient::new(&server.url()).unwrap();
+
+    let a = Pointer::from_content(b"same content");
+    let b = Pointer::from_content(b"other content");
+    let items: Vec<(&Pointer, &[u8])> = vec![
+        (&a, b"same content"),
+        (&a, b"same content"),
+        (&b, b"other content"),
+    ];
+    let report = client.upload_batch_report(&items).unwrap();
+    assert_eq!(report.len(), 2);
+    assert!(report
+        .iter()
+        .all(|(_, s)| matches!(s, ObjectStatus::Done(()))));
+
+    // Known objects need no further requests, including from clones
+    client.upload(&a, b"same content").unwrap();
+    client.clone().upload_batch(&items).unwrap();
+    assert!(client.is_known_present(b.oid()));
+
+    let elsewhere = store.insert("uploaded by someone else");
+    let missing = Pointer::from_content(b"missing");
+    let existing = client
+        .check_exists(&[&a, &elsewhere, &missing, &b])
+        .unwrap();
+    assert_eq!(
+        existing,
+        vec![a.oid().to_hex(), elsewhere.oid().to_hex(), b.oid().to_hex()]
+    );
+
+    let requests = server.stop();
+    assert_eq!(batch_sizes(&requests), vec![2, 2]);
+    assert_eq!(requests.iter().filter(|r| r.method == "PUT").count(), 2);
+}
+
+#[test]
+fn test_uploads_go_to_push_endpoint() {
+    let mirror_store = MockStore::default();
+    let handler = mirror_store.clone();
+    let mirror = MockLfsServer::start_with(move |req| handler.handle(req));
+    let primary_store = MockStore::default();
+    let handler = primary_store.clone();
+    let primary = MockLfsServer::start_with(move |req| handler.handle(req));
+
+    let push_url = LfsClient::new(&primary.url()).unwrap().lfs_url().clone();
+    let client = LfsClient::new(&mirror.url())
+        .unwrap()
+        .with_push_url(push_url.clone());
+    assert_eq!(client.push_url(), &push_url);
+
+    let content = b"written to the primary";
+    let pointer = Pointer::from_content(content);
+    client.upload(&pointer, content).unwrap();
+    assert!(primary_store.contains(&pointer));
+    assert!(!mirror_store.contains(&pointer));
+
+    // Downloads still come from the mirror, which hasn't synced yet
+    assert!(client.download(&pointer).unwrap_err().is_not_found());
+    let synced = mirror_store.insert("already on the mirror");
+    assert_eq!(client.download(&synced).unwrap(), b"already on the mirror");
+
+    // Seen on the mirror, but the primary still needs a copy
+    let existing = client.check_exists(&[&synced]).unwrap();
+    assert_eq!(existing, vec![synced.oid().to_hex()]);
+    assert!(!client.is_known_present(synced.oid()));
+    client.upload(&synced, b"already on the mirror").unwrap();
+    assert!(primary_store.contains(&synced));
+
+    assert_eq!(batch_sizes(&primary.stop()), vec![1, 1]);
+    assert_eq!(batch_sizes(&mirror.stop()), vec![1, 1, 1]);
+}
+
+#[test]
+fn test_downloads_fall_back_to_mirrors() {
+    let primary_store = MockStore::default();
+    let handler = primary_store.clone();
+    let primary = MockLfsServer::start_with(move |req| handler.handle(req));
+    let mirror_store = MockStore::default();
+    let handler = mirror_store.clone();
+    let mirror = MockLfsServer::start_with(move |req| handler.handle(req));
+
+    let url = |repo_url: &str| LfsClient::new(repo_url).unwrap().lfs_url().clone();
+    let down = url("http://127.0.0.1:9/repo.git");
+    let client = LfsClient::new(&primary.url())
+        .unwrap()
+        .with_fallback_url(down.clone())
+        .with_fallback_url(url(&mirror.url()));
+
+    // Objects missing from the primary come from the mirror, after the
+    // unreachable endpoint has been tried
+    let on_primary = primary_store.insert("on the primary");
+    let on_mirror = mirror_store.insert("only on the mirror");
+    assert_eq!(client.download(&on_primary).unwrap(), b"on the primary");
+    assert_eq!(client.download(&on_mirror).unwrap(), b"only on the mirror");
+    assert!(!client.is_endpoint_healthy(&down));
+    assert!(client.is_endpoint_healthy(client.lfs_url()));
+
+    let dir = tempfile::tempdir().unwrap();
+    let dest = dir.path().join("object");
+    client.download_to_file(&on_mirror, &dest).unwrap();
+    assert_eq!(std::fs::read(&dest).unwrap(), b"only on the mirror");
+
+    // Only objects the primary is missing are requested again
+    let missing = Pointer::from_content(b"on neither server");
+    let report = client
+        .download_batch_report(&[&on_primary, &on_mirror, &missing])
+        .unwrap();
+    assert!(matches!(&report[0].1, ObjectStatus::Done(c) if c == b"on the primary"));
+    assert!(matches!(&report[1].1, ObjectStatus::Done(c) if c == b"only on the mirror"));
+    assert!(matches!(&report[2].1, ObjectStatus::Failed(e) if e.is_not_found()));
+
+    // The unhealthy endpoint is tried last, so later single downloads go
+    // straight from the primary to the mirror
+    assert_eq!(batch_sizes(&primary.stop()), vec![1, 1, 1, 3]);
+    assert_eq!(batch_sizes(&mirror.stop()), vec![1, 1, 2]);
+}
+
+#[test]
+fn test_explicit_credentials_are_not_sent_to_mirrors() {
+    let primary_store = MockStore::default();
+    let handler = primary_store.clone();
+    let primary = MockLfsServer::start_with(move |req| handler.handle(req));
+    let mirror_store = MockStore::default();
+    let handler = mirror_store.clone();
+    let mirror = MockLfsServer::start_with(move |req| handler.handle(req));
+
+    let mirror_url = LfsClient::new(&mirror.url()).unwrap().lfs_url().clone();
+    let client = LfsClient::new(&primary.url())
+        .unwrap()
+        .with_token("secret")
+        .with_fallback_url(mirror_url);
+
+    let on_mirror = mirror_store.insert("only on the mirror");
+    assert_eq!(client.download(&on_mirror).unwrap(), b"only on the mirror");
+
+    let primary_requests = primary.stop();
+    assert_eq!(
+        primary_requests[0].header("authorization"),
+        Some("Bearer secret")
+    );
+    let mirror_requests = mirror.stop();
+    assert_eq!(mirror_requests.len(), 2);
+    assert!(mirror_requests
+        .iter()
+        .all(|req| req.header("authorization").is_none()));
+}
+
+#[test]
+fn test_transfer_credentials_stay_on_endpoint_origin() {
+    let store = MockStore::default();
+    let handler = store.clone();
+    let storage = MockLfsServer::start_with(move |req| handler.handle(req));
+    let offloading = MockStore {
+        transfer_base: Some(format!("http://127.0.0.1:{}", storage.port)),
+        ..store.clone()
+    };
+    let api = MockLfsServer::start_with(move |req| offloading.handle(req));
+    let presigned = MockStore {
+        authenticated: true,
+        ..store.clone()
+    };
+    let presigning = MockLfsServer::start_with(move |req| presigned.handle(req));
+
+    // Transfers on the endpoint's own origin carry the client's
+    // credentials; those on third-party storage or for objects the server
+    // marks authenticated don't
+    for (server, content) in [
+        (&storage, "same origin"),
+        (&api, "third-party storage"),
+        (&presigning, "authenticated"),
+    ] {
+        let client = LfsClient::new(&server.url()).unwrap().with_token("secret");
+        let pointer = Pointer::from_content(content.as_bytes());
+        client.upload(&pointer, content.as_bytes()).unwrap();
+        assert_eq!(client.download(&pointer).unwrap(), content.as_bytes());
+    }
+
+    let transfer_auth = |requests: &[MockRequest]| -> Vec<Option<String>> {
+        requests
+            .iter()
+            .filter(|req| !req.path.ends_with("/objects/batch"))
+            .map(|req| req.header("authorization").map(str::to_string))
+            .collect()
+    };
+    let bearer = Some("Bearer secret".to_string());
+    assert!(transfer_auth(&api.stop()).is_empty());
+    assert_eq!(transfer_auth(&presigning.stop()), vec![None, None]);
+    assert_eq!(
+        transfer_auth(&storage.stop()),
+        vec![bearer.clone(), bearer, None, None]
+    );
+}
+
+#[test]
+fn test_transfers_follow_redirects() {
+    let store = MockStore::default();
+    let handler = store.clone();
+    let storage = MockLfsServer::start_with(move |req| handler.handle(req));
+    let storage_base = format!("http://127.0.0.1:{}", storage.port);
+    let handler = store.clone();
+    let api = MockLfsServer::start_with(move |req| {
+        if req.path.ends_with("/objects/batch") {
+            handler.handle(req)
+        } else {
+            let location = format!("{}{}", storage_base, req.path);
+            redirect_response("307 Temporary Redirect", &location)
+        }
+    });
+
+    // Transfers land on the storage host with their method and body, but
+    // without the credentials meant for the API host
+    let client = LfsClient::new(&api.url()).unwrap().with_token("secret");
+    let content = b"redirected to storage";
+    let pointer = Pointer::from_content(content);
+    client.upload(&pointer, content).unwrap();
+    assert!(store.contains(&pointer));
+    assert_eq!(client.download(&pointer).unwrap(), content);
+
+    // Streamed uploads are rewound to where they started and sent again
+    let streamed = b"streamed to storage";
+    let streamed_pointer = Pointer::from_content(streamed);
+    let mut reader = std::io::Cursor::new([&b"skip"[..], streamed].concat());
+    reader.set_position(4);
+    client
+        .upload_reader(&streamed_pointer, reader, streamed_pointer.size())
+        .unwrap();
+    assert!(store.contains(&streamed_pointer));
+
+    // So are uploads from memory while a bandwidth limit streams them
+    let throttled = b"throttled to storage";
+    let throttled_pointer = Pointer::from_content(throttled);
+    client
+        .clone()
+        .with_bandwidth_limit(1 << 20)
+        .upload(&throttled_pointer, throttled)
+        .unwrap();
+    assert!(store.contains(&throttled_pointer));
+
+    let requests = storage.stop();
+    assert_eq!(requests.len(), 4);
+    assert_eq!(requests[0].method, "PUT");
+    assert_eq!(requests[0].body.as_bytes(), content);
+    assert_eq!(requests[1].method, "GET");
+    assert_eq!(requests[2].body.as_bytes(), streamed);
+    assert_eq!(requests[3].body.as_bytes(), throttled);
+    assert!(requests
+        .iter()
+        .all(|req| req.header("authorization").is_none()));
+    let api_requests = api.stop();
+    assert!(api_requests
+        .iter()
+        .all(|req| req.header("authorization") == Some("Bearer secret")));
+
+    // A batch endpoint redirecting to itself is reported as a loop
+    let looping = MockLfsServer::start_with(|req| {
+        let location = format!("{}{}", req.base_url(), req.path);
+        redirect_response("308 Permanent Redirect", &location)
+    });
+    let request = BatchRequest::download(vec![BatchRequestObject::new(
+        &pointer.oid().to_hex(),
+        pointer.size(),
+    )]);
+    let err = LfsClient::new(&looping.url())
+        .unwrap()
+        .batch(&request)
+        .unwrap_err();
+    assert!(
+        matches!(&err, git2_lfs::Error::Redirect { message, .. } if message.contains("loop")),
+        "{}",
+        err
+    );
+    assert_eq!(looping.stop().len(), 1);
+}
+
+/// Certificates for a TLS test server: a CA, a server certificate for
+/// 127.0.0.1 and a client certificate, all PEM encoded.
+struct TestPki {
+    ca_pem: String,
+    server_config: rustls::ServerConfig,
+    client_cert_pem: String,
+    client_key_pem: String,
+}
+
+impl TestPki {
+    /// Generate the certificates. The server requires a client certificate
+    /// issued by the CA.
+    fn generate() -> Self {
+        use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+        use rustls::pki_types::pem::PemObject;
+        use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+
+        let issue = |name: &str| {
+            let key = KeyPair::generate().unwrap();
+            let cert = CertificateParams::new(vec![name.to_string()])
+                .unwrap()
+                .signed_by(&key, &ca, &ca_key)
+                .unwrap();
+            (cert, key)
+        };
+        let (server_cert, server_key) = issue("127.0.0.1");
+        let (client_cert, client_key) = issue("lfs-client");
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(ca.der().clone()).unwrap();
+        let verifier = rustls::server::WebPkiClientVerifier::builder_with_provider(
+            Arc::new(roots),
+            provider.clone(),
+        )
+        .build()
+        .unwrap();
+        let server_config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(
+                vec![CertificateDer::from_pem_slice(server_cert.pem().as_bytes()).unwrap()],
+                PrivateKeyDer::from_pem_slice(server_key.serialize_pem().as_bytes()).unwrap(),
+            )
+            .unwrap();
+
+        TestPki {
+            ca_pem: ca.pem(),
+            server_config,
+            client_cert_pem: client_cert.pem(),
+            client_key_pem: client_key.serialize_pem(),
+        }
+    }
+}
+
+#[test]
+fn test_client_certificate_authentication() {
+    let pki = TestPki::generate();
+    let store = MockStore::default();
+    let handler = store.clone();
+    let server = MockLfsServer::start_tls_with(pki.server_config, move |req| handler.handle(req));
+
+    let dir = tempfile::tempdir().unwrap();
+    let ca_path = dir.path().join("ca.pem");
+    let cert_path = dir.path().join("client.pem");
+    let key_path = dir.path().join("client.key");
+    std::fs::write(&ca_path, &pki.ca_pem).unwrap();
+    std::fs::write(&cert_path, &pki.client_cert_pem).unwrap();
+    std::fs::write(&key_path, &pki.client_key_pem).unwrap();
+
+    // Client identity and CA bundle from files, for batch and transfer
+    // requests alike
+    let client = LfsClientBuilder::new(&server.https_url())
+        .unwrap()
+        .with_ca_info(&ca_path)
+        .with_client_cert(&cert_path)
+        .with_client_key(&key_path)
+        .build()
+        .unwrap();
+    let content = b"behind mutual TLS";
+    let pointer = Pointer::from_content(content);
+    client.upload(&pointer, content).unwrap();
+    assert_eq!(client.download(&pointer).unwrap(), content);
+
+    // The same identity and CA from memory
+    let client = LfsClientBuilder::new(&server.https_url())
+        .unwrap()
+        .with_root_certificates(pki.ca_pem.clone())
+        .with_client_identity(pki.client_cert_pem.clone(), pki.client_key_pem.clone())
+        .build()
+        .unwrap();
+    assert_eq!(client.download(&pointer).unwrap(), content);
+
+    // Without a client certificate the server refuses the handshake
+    let anonymous = LfsClientBuilder::new(&server.https_url())
+        .unwrap()
+        .with_root_certificates(pki.ca_pem.clone())
+        .build()
+        .unwrap();
+    let err = anonymous.download(&pointer).unwrap_err();
+    assert!(matches!(err, git2_lfs::Error::Http { .. }), "{}", err);
+
+    let requests = server.stop();
+    assert!(requests.iter().all(|req| req.tls));
+    assert_eq!(requests.len(), 6);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_async_client_transfers() {
+    use git2_lfs::AsyncLfsClient;
+
+    let store = MockStore::default();
+    let handler = store.clone();
+    let server = MockLfsServer::start_with(move |req| handler.handle(req));
+    let client = AsyncLfsClient::new(&server.url())
+        .unwrap()
+        .with_batch_size(2)
+        .with_concurrency(4);
+
+    // Batch upload with a duplicate, then batch download
+    let contents: Vec<String> = (0..5).map(|i| format!("async object {}", i)).collect();
+    let pointers: Vec<_> = contents
+        .iter()
+        .map(|c| Pointer::from_content(c.as_bytes()))
+        .collect();
+    let mut items: Vec<(&Pointer, &[u8])> = pointers
+        .iter()
+        .zip(&contents)
+        .map(|(p, c)| (p, c.as_bytes()))
+        .collect();
+    items.push(items[0]);
+    let report = client.upload_batch_report(&items).await.unwrap();
+    assert_eq!(report.len(), 5);
+    assert!(report
+        .iter()
+        .all(|(_, s)| matches!(s, ObjectStatus::Done(()))));
+    assert!(pointers.iter().all(|p| store.contains(p)));
+
+    let refs: Vec<_> = pointers.iter().collect();
+    store.expire_next_batches(1);
+    let downloaded = client.download_batch(&refs).await.unwrap();
+    assert_eq!(
+        downloaded,
+        contents
+            .iter()
+            .map(|c| c.as_bytes().to_vec())
+            .collect::<Vec<_>>()
+    );
+
+    // Streaming upload and download
+    let streamed = Pointer::from_content(b"streamed content");
+    let reader = std::io::Cursor::new(b"streamed content".to_vec());
+    client
+        .upload_reader(&streamed, reader, streamed.size())
+        .await
+        .unwrap();
+    let mut output = Vec::new();
+    let size = client
+        .download_to_writer(&streamed, &mut output)
+        .await
+        .unwrap();
+    assert_eq!(size, streamed.size());
+    assert_eq!(output, b"streamed content");
+
+    // Bodies are read at most one byte past the pointer's size
+    let short = Pointer::new(streamed.oid().clone(), 4);
+    let too_large = |err: git2_lfs::Error| {
+        matches!(err, git2_lfs::Error::SizeMismatch { expected: 4, actual: 5, .. })
+    };
+    assert!(too_large(client.download(&short).await.unwrap_err()));
+    let mut output = Vec::new();
+    let err = client
+        .download_to_writer(&short, &mut output)
+        .await
+        .unwrap_err();
+    assert!(too_large(err));
+    assert_eq!(output.len(), 5);
+
+    let missing = Pointer::from_content(b"missing");
+    let err = client.download(&missing).await.unwrap_err();
+    assert!(err.is_not_found());
+    assert_eq!(err.oid(), Some(missing.oid()));
+
+    let requests = server.stop();
+    assert!(requests.iter().all(|req| !req.path.starts_with("/stale/")));
+}